@@ -0,0 +1,236 @@
+/// Copyright 2020 Developers of the perf-gauge project.
+///
+/// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+/// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+/// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+/// option. This file may not be copied, modified, or distributed
+/// except according to those terms.
+use crate::extractor::JsonPath;
+use core::fmt;
+use hyper::{HeaderMap, StatusCode};
+use regex::Regex;
+use sha2::{Digest, Sha256};
+
+/// Failed checks are reported with this prefix, e.g. `Assertion failed: body contains "ok"`.
+pub const ASSERTION_FAILED_PREFIX: &str = "Assertion failed:";
+
+/// Checks of HTTP responses. A response is successful if it has an expected status
+/// and passes all checks.
+#[derive(Clone, Debug, Default)]
+pub struct ResponseAssertions {
+    /// By default, `2xx` statuses are expected.
+    expected_status: Vec<u16>,
+    checks: Vec<Check>,
+}
+
+#[derive(Clone, Debug)]
+enum Check {
+    BodyContains(String),
+    BodyMatches(Regex),
+    JsonEquals(JsonPath, String),
+    HeaderPresent(String),
+    MinBodySize(usize),
+    MaxBodySize(usize),
+    /// Lowercase hex.
+    Sha256(String),
+}
+
+impl ResponseAssertions {
+    pub fn expect_status(&mut self, status: Vec<u16>) -> &mut Self {
+        self.expected_status = status;
+        self
+    }
+
+    pub fn body_contains(&mut self, text: &str) -> &mut Self {
+        self.checks.push(Check::BodyContains(text.to_string()));
+        self
+    }
+
+    pub fn body_matches(&mut self, pattern: &str) -> Result<&mut Self, String> {
+        let regex = Regex::new(pattern).map_err(|e| e.to_string())?;
+        self.checks.push(Check::BodyMatches(regex));
+        Ok(self)
+    }
+
+    /// `PATH=VALUE`, e.g. `$.status=ok`. Non-string values are compared as JSON, e.g. `$.count=42`.
+    pub fn json_equals(&mut self, expression: &str) -> Result<&mut Self, String> {
+        let (path, value) = expression
+            .split_once('=')
+            .ok_or_else(|| format!("Expected PATH=VALUE, got {}", expression))?;
+        let path = JsonPath::parse(path.trim())?;
+        self.checks.push(Check::JsonEquals(path, value.to_string()));
+        Ok(self)
+    }
+
+    pub fn header_present(&mut self, name: &str) -> &mut Self {
+        self.checks
+            .push(Check::HeaderPresent(name.to_ascii_lowercase()));
+        self
+    }
+
+    pub fn min_body_size(&mut self, size: usize) -> &mut Self {
+        self.checks.push(Check::MinBodySize(size));
+        self
+    }
+
+    pub fn max_body_size(&mut self, size: usize) -> &mut Self {
+        self.checks.push(Check::MaxBodySize(size));
+        self
+    }
+
+    pub fn sha256(&mut self, hex: &str) -> Result<&mut Self, String> {
+        let hex = hex.trim().to_ascii_lowercase();
+        if hex.len() != 64 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(format!("Invalid SHA-256: {}", hex));
+        }
+        self.checks.push(Check::Sha256(hex));
+        Ok(self)
+    }
+
+    pub fn is_expected_status(&self, status: StatusCode) -> bool {
+        if self.expected_status.is_empty() {
+            status.is_success()
+        } else {
+            self.expected_status.contains(&status.as_u16())
+        }
+    }
+
+    /// If the response body must be kept to run the checks.
+    pub fn needs_body(&self) -> bool {
+        self.checks
+            .iter()
+            .any(|c| !matches!(c, Check::HeaderPresent(_)))
+    }
+
+    /// Returns the failure reason of the first failed check.
+    pub fn check(&self, headers: &HeaderMap, body: &[u8]) -> Result<(), String> {
+        match self.checks.iter().find(|c| !c.passes(headers, body)) {
+            Some(check) => Err(format!("{} {}", ASSERTION_FAILED_PREFIX, check)),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Check {
+    fn passes(&self, headers: &HeaderMap, body: &[u8]) -> bool {
+        match self {
+            Check::BodyContains(text) => {
+                text.is_empty() || body.windows(text.len()).any(|w| w == text.as_bytes())
+            }
+            Check::BodyMatches(regex) => regex.is_match(&String::from_utf8_lossy(body)),
+            Check::JsonEquals(path, value) => path.find(body).as_ref() == Some(value),
+            Check::HeaderPresent(name) => headers.contains_key(name.as_str()),
+            Check::MinBodySize(size) => body.len() >= *size,
+            Check::MaxBodySize(size) => body.len() <= *size,
+            Check::Sha256(hex) => {
+                let digest: String = Sha256::digest(body)
+                    .iter()
+                    .map(|b| format!("{:02x}", b))
+                    .collect();
+                &digest == hex
+            }
+        }
+    }
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Check::BodyContains(text) => write!(f, "body contains {:?}", text),
+            Check::BodyMatches(regex) => write!(f, "body matches {:?}", regex.as_str()),
+            Check::JsonEquals(path, value) => write!(f, "{} == {:?}", path, value),
+            Check::HeaderPresent(name) => write!(f, "header {}", name),
+            Check::MinBodySize(size) => write!(f, "body size >= {}", size),
+            Check::MaxBodySize(size) => write!(f, "body size <= {}", size),
+            Check::Sha256(_) => write!(f, "body SHA-256"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::assertion::ResponseAssertions;
+    use hyper::header::{HeaderValue, CONTENT_TYPE};
+    use hyper::{HeaderMap, StatusCode};
+
+    #[test]
+    fn test_expected_status() {
+        let mut assertions = ResponseAssertions::default();
+        assert!(assertions.is_expected_status(StatusCode::OK));
+        assert!(!assertions.is_expected_status(StatusCode::NOT_FOUND));
+
+        assertions.expect_status(vec![200, 404]);
+        assert!(assertions.is_expected_status(StatusCode::NOT_FOUND));
+        assert!(!assertions.is_expected_status(StatusCode::CREATED));
+        assert!(!assertions.needs_body());
+    }
+
+    #[test]
+    fn test_checks() {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        let body = br#"{"status": "ok", "count": 42}"#;
+
+        let check = |f: &dyn Fn(&mut ResponseAssertions)| {
+            let mut assertions = ResponseAssertions::default();
+            f(&mut assertions);
+            assertions.check(&headers, body)
+        };
+
+        assert_eq!(Ok(()), check(&|_| ()));
+        assert_eq!(
+            Ok(()),
+            check(&|a| {
+                a.body_contains("\"ok\"")
+                    .header_present("Content-Type")
+                    .min_body_size(10)
+                    .max_body_size(100);
+                a.body_matches("count\": [0-9]+").unwrap();
+                a.json_equals("$.status=ok").unwrap();
+                a.json_equals("$.count=42").unwrap();
+            })
+        );
+
+        assert_eq!(
+            Err("Assertion failed: body contains \"error\"".to_string()),
+            check(&|a| {
+                a.body_contains("error");
+            })
+        );
+        assert_eq!(
+            Err("Assertion failed: $.status == \"failed\"".to_string()),
+            check(&|a| {
+                a.json_equals("$.status=failed").unwrap();
+            })
+        );
+        assert_eq!(
+            Err("Assertion failed: header x-request-id".to_string()),
+            check(&|a| {
+                a.header_present("X-Request-Id");
+            })
+        );
+        assert_eq!(
+            Err("Assertion failed: body size <= 10".to_string()),
+            check(&|a| {
+                a.min_body_size(10).max_body_size(10);
+            })
+        );
+    }
+
+    #[test]
+    fn test_sha256() {
+        let mut assertions = ResponseAssertions::default();
+        // SHA-256 of `abc`
+        assertions
+            .sha256("BA7816BF8F01CFEA414140DE5DAE2223B00361A396177A9CB410FF61F20015AD")
+            .unwrap();
+        assert_eq!(Ok(()), assertions.check(&HeaderMap::new(), b"abc"));
+        assert_eq!(
+            Err("Assertion failed: body SHA-256".to_string()),
+            assertions.check(&HeaderMap::new(), b"abd")
+        );
+        assert!(ResponseAssertions::default().sha256("abc").is_err());
+        assert!(ResponseAssertions::default().json_equals("$.a").is_err());
+        assert!(ResponseAssertions::default().body_matches("(").is_err());
+    }
+}
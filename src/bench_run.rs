@@ -1,4 +1,4 @@
-use crate::metrics::RequestStats;
+use crate::metrics::{RequestStats, RequestStatsBuilder};
 use crate::rate_limiter::RateLimiter;
 /// Copyright 2020 Developers of the perf-gauge project.
 ///
@@ -8,17 +8,26 @@ use crate::rate_limiter::RateLimiter;
 /// option. This file may not be copied, modified, or distributed
 /// except according to those terms.
 use async_trait::async_trait;
+use futures_util::future::BoxFuture;
+use futures_util::stream::FuturesUnordered;
+use futures_util::StreamExt;
 use log::error;
 use std::future::Future;
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::sync::mpsc::Sender;
-use tokio::time::timeout;
+use tokio::time::{sleep_until, timeout};
 
-static STOP_ON_FATAL: AtomicBool = AtomicBool::new(false);
+/// Latency of requests without the wait for their intended send time (open model).
+pub const SERVICE_TIME_PHASE: &str = "service_time";
+
+/// Requests, which didn't complete within the request timeout (if they don't stop the batch).
+pub const TIMEOUT_STATUS: &str = "Timed out";
 
 #[derive(Clone, Debug)]
 pub struct BenchRun {
+    #[allow(dead_code)]
     pub index: usize,
     bench_begin: Instant,
     timeout: Option<Duration>,
@@ -26,6 +35,12 @@ pub struct BenchRun {
     max_requests: Option<usize>,
     max_duration: Option<Duration>,
     rate_limiter: RateLimiter,
+    /// Open model: requests are sent on schedule, with up to this number in flight.
+    max_in_flight: Option<usize>,
+    /// Set on fatal errors, shared by the runs of a batch to stop all of them.
+    stop: Arc<AtomicBool>,
+    /// Timed out requests are reported as failed, instead of being fatal.
+    timeouts_as_errors: bool,
 }
 
 #[async_trait]
@@ -33,7 +48,21 @@ pub trait BenchmarkProtocolAdapter {
     type Client;
 
     fn build_client(&self) -> Result<Self::Client, String>;
+
+    /// Called before each request. Returns `false` if the client has nothing more to send,
+    /// e.g. test data is exhausted.
+    fn has_more_work(&self, _client: &Self::Client) -> bool {
+        true
+    }
+
     async fn send_request(&self, client: &Self::Client) -> RequestStats;
+
+    /// Like `send_request`, but binds the request to the client state taken by `has_more_work`,
+    /// e.g. a data record, right away instead of when the future is first polled.
+    /// Requests of a client overlap in the open model, so the next one may move it before.
+    fn issue_request<'a>(&'a self, client: &'a Self::Client) -> BoxFuture<'a, RequestStats> {
+        self.send_request(client)
+    }
 }
 
 impl BenchRun {
@@ -75,9 +104,30 @@ impl BenchRun {
             max_requests,
             max_duration,
             rate_limiter,
+            max_in_flight: None,
+            stop: Arc::new(AtomicBool::new(false)),
+            timeouts_as_errors: false,
         }
     }
 
+    /// Sends requests on the intended schedule of the rate limiter, without waiting for responses.
+    pub fn with_max_in_flight(mut self, max_in_flight: usize) -> Self {
+        self.max_in_flight = Some(max_in_flight.max(1));
+        self
+    }
+
+    /// Stops on fatal errors of any run sharing `stop`.
+    pub fn with_stop_signal(mut self, stop: Arc<AtomicBool>) -> Self {
+        self.stop = stop;
+        self
+    }
+
+    /// Reports timed out requests as failed instead of stopping, e.g. while searching for the max rate.
+    pub fn with_timeouts_as_errors(mut self) -> Self {
+        self.timeouts_as_errors = true;
+        self
+    }
+
     pub fn has_more_work(&mut self) -> bool {
         let has_more_work = if let Some(max_requests) = self.max_requests {
             self.requests_sent < max_requests
@@ -99,13 +149,24 @@ impl BenchRun {
     ) -> Result<(), String> {
         let client = bench_protocol_adapter.build_client()?;
 
-        while self.has_more_work() {
+        if let Some(max_in_flight) = self.max_in_flight {
+            return self
+                .send_open_load(
+                    bench_protocol_adapter,
+                    &client,
+                    max_in_flight,
+                    metrics_channel,
+                )
+                .await;
+        }
+
+        while self.has_more_work() && bench_protocol_adapter.has_more_work(&client) {
             self.rate_limiter
                 .acquire_one()
                 .await
                 .expect("Unexpected LeakyBucket.acquire error");
 
-            if STOP_ON_FATAL.load(Ordering::Relaxed) {
+            if self.stop.load(Ordering::Relaxed) {
                 break;
             }
 
@@ -113,32 +174,136 @@ impl BenchRun {
                 .timed_operation(bench_protocol_adapter.send_request(&client))
                 .await;
 
-            let fatal_error = match timed_request {
-                Ok(request_stats) => {
-                    let failed = request_stats.fatal_error;
-                    metrics_channel
-                        .try_send(request_stats)
-                        .map_err(|e| {
-                            error!("Error sending metrics: {}", e);
-                        })
-                        .unwrap_or_default();
-                    failed
+            if self.report(timed_request, &metrics_channel) {
+                self.stop.store(true, Ordering::Relaxed);
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Requests are dispatched on the intended schedule regardless of responses.
+    /// The latency is measured from the intended send time, so a slow server can't
+    /// hide its queueing delays by slowing down the load (coordinated omission).
+    /// The latency of the request itself is reported as the `service_time` phase.
+    async fn send_open_load<A: BenchmarkProtocolAdapter>(
+        mut self,
+        bench_protocol_adapter: &A,
+        client: &A::Client,
+        max_in_flight: usize,
+        metrics_channel: Sender<RequestStats>,
+    ) -> Result<(), String> {
+        let request_timeout = self.timeout;
+        let mut in_flight = FuturesUnordered::new();
+        let mut fatal_error = false;
+
+        while !fatal_error && self.has_more_work() && bench_protocol_adapter.has_more_work(client) {
+            let intended = self
+                .rate_limiter
+                .next_arrival()
+                .unwrap_or_else(Instant::now);
+
+            // the server can't keep up, so requests queue here
+            while in_flight.len() >= max_in_flight && !fatal_error {
+                if let Some(completed) = in_flight.next().await {
+                    fatal_error = self.report_open(completed, &metrics_channel);
+                }
+            }
+
+            loop {
+                tokio::select! {
+                    _ = sleep_until(intended.into()) => break,
+                    Some(completed) = in_flight.next() => {
+                        fatal_error |= self.report_open(completed, &metrics_channel);
+                    }
                 }
-                Err(_) => true,
-            };
+            }
 
-            if fatal_error {
-                STOP_ON_FATAL.store(true, Ordering::Relaxed);
+            if fatal_error || self.stop.load(Ordering::Relaxed) {
                 break;
             }
+
+            let request = bench_protocol_adapter.issue_request(client);
+            in_flight.push(async move {
+                let result = BenchRun::with_timeout(request_timeout, request).await;
+                (intended, result, Instant::now())
+            });
+        }
+
+        while let Some(completed) = in_flight.next().await {
+            fatal_error |= self.report_open(completed, &metrics_channel);
+        }
+
+        if fatal_error {
+            self.stop.store(true, Ordering::Relaxed);
         }
 
         Ok(())
     }
 
+    /// Returns `true` on fatal errors.
+    fn report(
+        &self,
+        timed_request: Result<RequestStats, ()>,
+        metrics_channel: &Sender<RequestStats>,
+    ) -> bool {
+        match timed_request.or_else(|_| self.timed_out()) {
+            Ok(request_stats) => {
+                let failed = request_stats.fatal_error;
+                metrics_channel
+                    .try_send(request_stats)
+                    .map_err(|e| {
+                        error!("Error sending metrics: {}", e);
+                    })
+                    .unwrap_or_default();
+                failed
+            }
+            Err(_) => true,
+        }
+    }
+
+    fn report_open(
+        &self,
+        (intended, timed_request, completed): (Instant, Result<RequestStats, ()>, Instant),
+        metrics_channel: &Sender<RequestStats>,
+    ) -> bool {
+        let timed_request = timed_request.or_else(|_| self.timed_out());
+        let timed_request = timed_request.map(|mut request_stats| {
+            request_stats
+                .phases
+                .push((SERVICE_TIME_PHASE, request_stats.duration));
+            request_stats.duration = completed.duration_since(intended);
+            request_stats
+        });
+        self.report(timed_request, metrics_channel)
+    }
+
+    /// A failed request, or `Err` if timeouts are fatal.
+    fn timed_out(&self) -> Result<RequestStats, ()> {
+        if !self.timeouts_as_errors {
+            return Err(());
+        }
+        Ok(RequestStatsBuilder::default()
+            .is_success(false)
+            .bytes_processed(0)
+            .status(TIMEOUT_STATUS.to_string())
+            .duration(self.timeout.unwrap_or_default())
+            .fatal_error(false)
+            .build()
+            .expect("RequestStatsBuilder failed"))
+    }
+
     /// Each async operation must be time-bound.
     pub async fn timed_operation<T: Future>(&self, f: T) -> Result<<T as Future>::Output, ()> {
-        if let Some(timeout_value) = self.timeout {
+        BenchRun::with_timeout(self.timeout, f).await
+    }
+
+    async fn with_timeout<T: Future>(
+        request_timeout: Option<Duration>,
+        f: T,
+    ) -> Result<<T as Future>::Output, ()> {
+        if let Some(timeout_value) = request_timeout {
             let result = timeout(timeout_value, f).await;
 
             if let Ok(r) = result {
@@ -147,25 +312,32 @@ impl BenchRun {
                 Err(())
             }
         } else {
-            return Ok(f.await);
+            Ok(f.await)
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::bench_run::STOP_ON_FATAL;
+    use crate::bench_run::{
+        BenchRun, BenchmarkProtocolAdapter, SERVICE_TIME_PHASE, TIMEOUT_STATUS,
+    };
     use crate::bench_session::RateLadderBuilder;
     use crate::configuration::BenchmarkMode::Http;
     use crate::configuration::{BenchmarkConfig, BenchmarkConfigBuilder};
     use crate::http_bench_session::{
         HttpBenchAdapterBuilder, HttpClientConfigBuilder, HttpRequestBuilder,
     };
-    use crate::metrics::BenchRunMetrics;
+    use crate::latency_histogram::HistogramBackend;
+    use crate::metrics::{BenchRunMetrics, RequestStats, RequestStatsBuilder};
+    use crate::rate_limiter::{ArrivalProcess, RateLimiter};
+    use async_trait::async_trait;
     use mockito::mock;
-    use std::sync::atomic::Ordering;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
     use std::thread::sleep;
     use std::time::{Duration, Instant};
+    use tokio::sync::mpsc;
 
     #[tokio::test]
     async fn test_send_load() {
@@ -218,9 +390,7 @@ mod tests {
 
         let mut session = benchmark_config.clone().new_bench_session();
 
-        let bench_run_stats = BenchRunMetrics::new();
-
-        STOP_ON_FATAL.store(false, Ordering::Relaxed);
+        let bench_run_stats = BenchRunMetrics::new(HistogramBackend::Simple);
 
         let bench_result = session
             .next()
@@ -228,7 +398,6 @@ mod tests {
             .run(bench_run_stats)
             .await;
 
-        assert!(!STOP_ON_FATAL.load(Ordering::Relaxed));
         assert!(bench_result.is_ok());
 
         let elapsed = Instant::now().duration_since(start).as_secs_f64();
@@ -298,9 +467,7 @@ mod tests {
 
         let mut session = benchmark_config.clone().new_bench_session();
 
-        let bench_run_stats = BenchRunMetrics::new();
-
-        STOP_ON_FATAL.store(false, Ordering::Relaxed);
+        let bench_run_stats = BenchRunMetrics::new(HistogramBackend::Simple);
 
         let bench_result = session
             .next()
@@ -309,8 +476,7 @@ mod tests {
             .await;
 
         // must stop on fatal
-        assert!(STOP_ON_FATAL.load(Ordering::Relaxed));
-        assert!(bench_result.is_ok());
+        assert_eq!(1, bench_result.unwrap().combined.total_requests);
     }
 
     #[tokio::test]
@@ -358,9 +524,7 @@ mod tests {
 
         let mut session = benchmark_config.clone().new_bench_session();
 
-        let bench_run_stats = BenchRunMetrics::new();
-
-        STOP_ON_FATAL.store(false, Ordering::Relaxed);
+        let bench_run_stats = BenchRunMetrics::new(HistogramBackend::Simple);
 
         let bench_result = session
             .next()
@@ -369,7 +533,147 @@ mod tests {
             .await;
 
         // must stop on timeout, treated as fatal
-        assert!(STOP_ON_FATAL.load(Ordering::Relaxed));
-        assert!(bench_result.is_ok());
+        assert_eq!(0, bench_result.unwrap().combined.total_requests);
+    }
+
+    struct SlowAdapter {
+        service_time: Duration,
+        fatal: bool,
+    }
+
+    #[async_trait]
+    impl BenchmarkProtocolAdapter for SlowAdapter {
+        type Client = ();
+
+        fn build_client(&self) -> Result<Self::Client, String> {
+            Ok(())
+        }
+
+        async fn send_request(&self, _client: &Self::Client) -> RequestStats {
+            let start = Instant::now();
+            tokio::time::sleep(self.service_time).await;
+            RequestStatsBuilder::default()
+                .bytes_processed(0)
+                .status("OK".to_string())
+                .is_success(true)
+                .duration(Instant::now().duration_since(start))
+                .fatal_error(self.fatal)
+                .build()
+                .expect("RequestStatsBuilder failed")
+        }
+    }
+
+    async fn open_load(
+        request_count: usize,
+        max_in_flight: usize,
+    ) -> (Duration, Vec<RequestStats>) {
+        let adapter = SlowAdapter {
+            service_time: Duration::from_millis(50),
+            fatal: false,
+        };
+        let (sender, mut receiver) = mpsc::channel(request_count);
+        let start = Instant::now();
+        BenchRun::from_request_limit(
+            0,
+            request_count,
+            RateLimiter::build_rate_limiter(100., ArrivalProcess::Constant),
+            None,
+        )
+        .with_max_in_flight(max_in_flight)
+        .send_load(&adapter, sender)
+        .await
+        .unwrap();
+        let elapsed = Instant::now().duration_since(start);
+
+        let mut stats = vec![];
+        while let Some(s) = receiver.recv().await {
+            stats.push(s);
+        }
+        assert_eq!(request_count, stats.len());
+        (elapsed, stats)
+    }
+
+    #[tokio::test]
+    async fn test_open_model() {
+        // requests are sent on schedule, even though each one takes 50ms
+        let (elapsed, stats) = open_load(20, 100).await;
+        assert!(
+            elapsed < Duration::from_millis(400),
+            "Expected to finish in ~250ms, but it took: {:?}",
+            elapsed
+        );
+        for s in stats.iter() {
+            assert_eq!(SERVICE_TIME_PHASE, s.phases[0].0);
+            assert!(s.duration >= s.phases[0].1);
+            assert!(s.duration < Duration::from_millis(150));
+        }
+
+        // a single request in flight, so the requests queue up,
+        // which is reported as latency, but not as service time
+        let (elapsed, stats) = open_load(20, 1).await;
+        assert!(elapsed >= Duration::from_millis(1000));
+        let max_latency = stats.iter().map(|s| s.duration).max().unwrap();
+        let max_service_time = stats.iter().map(|s| s.phases[0].1).max().unwrap();
+        assert!(max_latency > Duration::from_millis(600));
+        assert!(max_service_time < Duration::from_millis(150));
+    }
+
+    async fn send(run: BenchRun, adapter: &SlowAdapter) -> Vec<RequestStats> {
+        let (sender, mut receiver) = mpsc::channel(100);
+        run.send_load(adapter, sender).await.unwrap();
+        let mut stats = vec![];
+        while let Some(s) = receiver.recv().await {
+            stats.push(s);
+        }
+        stats
+    }
+
+    fn run(requests: usize, timeout: Option<Duration>) -> BenchRun {
+        BenchRun::from_request_limit(
+            0,
+            requests,
+            RateLimiter::build_rate_limiter(0., ArrivalProcess::Constant),
+            timeout,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_stop_signal() {
+        let fatal = SlowAdapter {
+            service_time: Duration::from_millis(1),
+            fatal: true,
+        };
+        let stop = Arc::new(AtomicBool::new(false));
+        let stats = send(run(10, None).with_stop_signal(stop.clone()), &fatal).await;
+        assert_eq!(1, stats.len());
+        assert!(stop.load(Ordering::Relaxed));
+
+        // runs sharing the signal stop too, but not the other ones
+        let adapter = SlowAdapter {
+            service_time: Duration::from_millis(1),
+            fatal: false,
+        };
+        let stats = send(run(10, None).with_stop_signal(stop.clone()), &adapter).await;
+        assert!(stats.is_empty());
+        assert_eq!(10, send(run(10, None), &adapter).await.len());
+    }
+
+    #[tokio::test]
+    async fn test_timeouts_as_errors() {
+        let adapter = SlowAdapter {
+            service_time: Duration::from_millis(50),
+            fatal: false,
+        };
+        let timeout = Some(Duration::from_millis(10));
+        // fatal by default
+        assert!(send(run(3, timeout), &adapter).await.is_empty());
+
+        let stats = send(run(3, timeout).with_timeouts_as_errors(), &adapter).await;
+        assert_eq!(3, stats.len());
+        for s in stats {
+            assert!(!s.is_success);
+            assert!(!s.fatal_error);
+            assert_eq!(TIMEOUT_STATUS, s.status);
+        }
     }
 }
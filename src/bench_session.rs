@@ -1,7 +1,9 @@
 use crate::bench_run::BenchRun;
 use crate::configuration::BenchmarkMode;
-use crate::metrics::{BenchRunMetrics, RequestStats};
-use crate::rate_limiter::RateLimiter;
+use crate::load_profile::LoadProfile;
+use crate::metrics::{BenchRunMetrics, RequestStats, Snapshot};
+use crate::rate_limiter::{ArrivalProcess, RateLimiter};
+use crate::rate_search::{RateSearch, SearchResult};
 use core::fmt;
 /// Copyright 2020 Developers of the perf-gauge project.
 ///
@@ -13,10 +15,14 @@ use core::fmt;
 use derive_builder::Builder;
 use log::error;
 use log::info;
+use std::str::FromStr;
+use std::sync::atomic::AtomicBool;
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::mpsc;
 use tokio::sync::mpsc::Sender;
+use tokio::time;
+use tokio::time::MissedTickBehavior;
 
 #[derive(Builder, Clone)]
 pub struct BenchSession {
@@ -26,11 +32,47 @@ pub struct BenchSession {
     #[builder(setter(skip))]
     current_iteration: usize,
     request_timeout: Option<Duration>,
+    /// Open model: the max number of requests in flight, shared by the clients.
+    #[builder(default)]
+    max_in_flight: Option<usize>,
+    #[builder(default)]
+    arrival: ArrivalProcess,
+    /// Replaces the rate ladder, each step of the profile is a batch.
+    #[builder(default)]
+    load_profile: Option<LoadProfile>,
+    /// Replaces the rate ladder, the rate of each batch depends on results of the previous ones.
+    #[builder(default)]
+    rate_search: Option<RateSearch>,
+    /// Replaces `concurrency`, each level is a batch with the same rate.
+    #[builder(default)]
+    concurrency_ladder: Option<ConcurrencyLadder>,
+    #[builder(default)]
+    warmup: Option<Warmup>,
+    /// Otherwise, only the first step is preceded by the warm-up.
+    #[builder(default)]
+    warmup_each_step: bool,
+    /// Postponed by the warm-up.
+    #[builder(setter(skip))]
+    pending_step: Option<Step>,
+    /// Periodic reports during each batch.
+    #[builder(default)]
+    report_interval: Option<Duration>,
+    /// Report all requests of the batch so far, instead of the ones since the previous report.
+    #[builder(default)]
+    cumulative_reports: bool,
 }
 
 pub struct BenchBatch {
     runs: Vec<BenchRun>,
+    /// Requests per second of all runs, `0` means no limit.
+    rate: f64,
+    /// The results must be discarded.
+    warmup: bool,
     mode: Arc<BenchmarkMode>,
+    report_interval: Option<Duration>,
+    cumulative_reports: bool,
+    /// Receives periodic reports, if `report_interval` is set.
+    snapshot_sender: Option<std::sync::mpsc::Sender<BenchRunMetrics>>,
 }
 
 #[derive(Builder, Debug, Clone)]
@@ -48,34 +90,128 @@ pub struct RateLadder {
     complete: bool,
 }
 
+/// Concurrency levels, e.g. `1, 2, 4, ..., 256`, each of them is a batch with the same rate.
+#[derive(Builder, Debug, Clone)]
+pub struct ConcurrencyLadder {
+    start: usize,
+    end: usize,
+    /// The concurrency is doubled if `None`.
+    #[builder(default)]
+    increment: Option<usize>,
+}
+
+/// Requests before each batch (or only the first one), which are excluded from the results,
+/// e.g. to warm up JIT-based services.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Warmup {
+    Duration(Duration),
+    /// Across all clients.
+    Requests(usize),
+}
+
+/// Parameters of a batch.
+#[derive(Clone, Copy, Debug)]
+struct Step {
+    concurrency: usize,
+    rate: f64,
+    requests: Option<usize>,
+    duration: Option<Duration>,
+}
+
 impl Iterator for BenchSession {
     type Item = BenchBatch;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.rate_ladder.complete {
-            return None;
+        if let Some(step) = self.pending_step.take() {
+            return Some(self.build_batch(step, false));
         }
 
-        let current = self.rate_ladder.get_current();
+        let step = self.next_step()?;
+        self.current_iteration += 1;
 
+        match self.warmup {
+            Some(warmup) if self.warmup_each_step || self.current_iteration == 1 => {
+                self.pending_step = Some(step);
+                let (requests, duration) = match warmup {
+                    Warmup::Duration(duration) => (None, Some(duration)),
+                    Warmup::Requests(requests) => (Some(requests.div_ceil(step.concurrency)), None),
+                };
+                Some(self.build_batch(
+                    Step {
+                        requests,
+                        duration,
+                        ..step
+                    },
+                    true,
+                ))
+            }
+            _ => Some(self.build_batch(step, false)),
+        }
+    }
+}
+
+impl BenchSession {
+    fn next_step(&mut self) -> Option<Step> {
+        let concurrency = match self.concurrency_ladder.as_ref() {
+            Some(ladder) => ladder.level(self.current_iteration)?,
+            None => self.concurrency,
+        };
+
+        let (rate, requests, duration) =
+            match (self.load_profile.as_ref(), self.rate_search.as_ref()) {
+                (Some(load_profile), _) => {
+                    let step = load_profile.steps().get(self.current_iteration)?;
+                    (step.rate, None, Some(step.duration))
+                }
+                (None, Some(rate_search)) => (
+                    rate_search.next_rate()?,
+                    self.rate_ladder.step_requests,
+                    self.rate_ladder.step_duration,
+                ),
+                (None, None) if self.concurrency_ladder.is_some() => (
+                    self.rate_ladder.get_current(),
+                    self.rate_ladder.step_requests,
+                    self.rate_ladder.step_duration,
+                ),
+                (None, None) if self.rate_ladder.complete => return None,
+                (None, None) => {
+                    let current = self.rate_ladder.get_current();
+                    self.rate_ladder.increment_rate();
+                    (
+                        current,
+                        self.rate_ladder.step_requests,
+                        self.rate_ladder.step_duration,
+                    )
+                }
+            };
+
+        Some(Step {
+            concurrency,
+            rate,
+            requests,
+            duration,
+        })
+    }
+
+    fn build_batch(&self, step: Step, warmup: bool) -> BenchBatch {
         let mut items = vec![];
 
-        let rate_per_second = current / self.concurrency as f64;
+        let rate_per_second = step.rate / step.concurrency as f64;
 
-        for i in 0..self.concurrency {
-            let idx = i + self.current_iteration * self.concurrency;
-            items.push(if let Some(requests) = self.rate_ladder.step_requests {
+        for i in 0..step.concurrency {
+            let idx = i + (self.current_iteration - 1) * step.concurrency;
+            items.push(if let Some(requests) = step.requests {
                 BenchRun::from_request_limit(
                     idx,
                     requests,
-                    RateLimiter::build_rate_limiter(rate_per_second),
+                    RateLimiter::build_rate_limiter(rate_per_second, self.arrival),
                     self.request_timeout,
                 )
-            } else if let Some(duration) = self.rate_ladder.step_duration {
+            } else if let Some(duration) = step.duration {
                 BenchRun::from_duration_limit(
                     idx,
                     duration,
-                    RateLimiter::build_rate_limiter(rate_per_second),
+                    RateLimiter::build_rate_limiter(rate_per_second, self.arrival),
                     self.request_timeout,
                 )
             } else {
@@ -83,26 +219,98 @@ impl Iterator for BenchSession {
             });
         }
 
-        self.rate_ladder.increment_rate();
-        self.current_iteration += 1;
+        if let Some(max_in_flight) = self.max_in_flight {
+            let per_client = max_in_flight.div_ceil(step.concurrency);
+            items = items
+                .into_iter()
+                .map(|run| run.with_max_in_flight(per_client))
+                .collect();
+        }
+
+        // a fatal error stops the whole batch, but not the next ones
+        let stop = Arc::new(AtomicBool::new(false));
+        items = items
+            .into_iter()
+            .map(|run| run.with_stop_signal(stop.clone()))
+            .collect();
+
+        if self.rate_search.is_some() {
+            // a timeout means the rate is too high, not that the search must stop
+            items = items
+                .into_iter()
+                .map(BenchRun::with_timeouts_as_errors)
+                .collect();
+        }
 
-        Some(BenchBatch {
+        BenchBatch {
             runs: items,
-            mode: self.mode.clone(),
-        })
+            rate: step.rate,
+            warmup,
+            mode: Arc::new(self.mode.for_batch(step.concurrency)),
+            report_interval: self.report_interval,
+            cumulative_reports: self.cumulative_reports,
+            snapshot_sender: None,
+        }
+    }
+
+    /// Results of the last batch, which are required by the rate search.
+    pub fn report(&mut self, metrics: &BenchRunMetrics) {
+        if let Some(rate_search) = self.rate_search.as_mut() {
+            rate_search.report(metrics);
+        }
+    }
+
+    /// The last batch failed to run, so the rate search (if any) treats it as an SLO breach.
+    pub fn report_failure(&mut self) {
+        if let Some(rate_search) = self.rate_search.as_mut() {
+            rate_search.report_failure();
+        }
+    }
+
+    pub fn search_result(&self) -> Option<&SearchResult> {
+        self.rate_search.as_ref().and_then(|s| s.result())
     }
 }
 
 impl BenchBatch {
+    pub fn is_warmup(&self) -> bool {
+        self.warmup
+    }
+
+    /// Periodic reports are sent to `sender`, unless it's a warm-up.
+    pub fn with_snapshot_sender(
+        mut self,
+        sender: std::sync::mpsc::Sender<BenchRunMetrics>,
+    ) -> Self {
+        self.snapshot_sender = Some(sender);
+        self
+    }
+
     pub async fn run(self, mut metrics: BenchRunMetrics) -> Result<BenchRunMetrics, String> {
-        let (metrics_sender, mut metrics_receiver) = mpsc::channel(1_000);
+        metrics.concurrency = self.runs.len();
+        let (metrics_sender, metrics_receiver) = mpsc::channel(1_000);
 
+        let snapshots = match (self.report_interval, self.snapshot_sender.clone()) {
+            (Some(interval), Some(sender)) if !self.warmup => {
+                Some((interval, self.cumulative_reports, sender))
+            }
+            _ => None,
+        };
         // single consumer to aggregate metrics
         let metrics_aggregator = tokio::spawn(async move {
-            while let Some(request_stats) = metrics_receiver.recv().await {
-                metrics.report_request(request_stats);
+            match snapshots {
+                None => BenchBatch::aggregate(metrics, metrics_receiver).await,
+                Some((interval, cumulative, sender)) => {
+                    BenchBatch::aggregate_with_snapshots(
+                        metrics,
+                        metrics_receiver,
+                        interval,
+                        cumulative,
+                        sender,
+                    )
+                    .await
+                }
             }
-            metrics
         });
 
         // while there are going to be multiple metrics producers
@@ -113,6 +321,60 @@ impl BenchBatch {
             .expect("Must return metrics object at the end"))
     }
 
+    async fn aggregate(
+        mut metrics: BenchRunMetrics,
+        mut metrics_receiver: mpsc::Receiver<RequestStats>,
+    ) -> BenchRunMetrics {
+        while let Some(request_stats) = metrics_receiver.recv().await {
+            metrics.report_request(request_stats);
+        }
+        metrics
+    }
+
+    async fn aggregate_with_snapshots(
+        mut metrics: BenchRunMetrics,
+        mut metrics_receiver: mpsc::Receiver<RequestStats>,
+        interval: Duration,
+        cumulative: bool,
+        sender: std::sync::mpsc::Sender<BenchRunMetrics>,
+    ) -> BenchRunMetrics {
+        let (concurrency, backend) = (metrics.concurrency, metrics.backend());
+        let new_interval = || {
+            let mut interval_metrics = BenchRunMetrics::new(backend);
+            interval_metrics.concurrency = concurrency;
+            interval_metrics.snapshot = Some(Snapshot::Interval);
+            interval_metrics
+        };
+        let mut interval_metrics = new_interval();
+        let mut ticker = time::interval_at(time::Instant::now() + interval, interval);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            tokio::select! {
+                request_stats = metrics_receiver.recv() => match request_stats {
+                    Some(request_stats) => {
+                        if !cumulative {
+                            interval_metrics.report_request(request_stats.clone());
+                        }
+                        metrics.report_request(request_stats);
+                    }
+                    None => break,
+                },
+                _ = ticker.tick() => {
+                    let snapshot = if cumulative {
+                        let mut snapshot = metrics.clone();
+                        snapshot.snapshot = Some(Snapshot::Cumulative);
+                        snapshot
+                    } else {
+                        std::mem::replace(&mut interval_metrics, new_interval())
+                    };
+                    sender.send(snapshot).unwrap_or_default();
+                }
+            }
+        }
+        metrics
+    }
+
     async fn execute_concurrent_sessions(
         self,
         metrics_sender: Sender<RequestStats>,
@@ -122,14 +384,41 @@ impl BenchBatch {
             let bench_protocol_adapter = self.mode.clone();
             let metrics_channel = metrics_sender.clone();
             concurrent_clients.push(tokio::spawn(async move {
-                bench_run
-                    .send_load(
-                        match bench_protocol_adapter.as_ref() {
-                            BenchmarkMode::Http(http_bench_session) => http_bench_session,
-                        },
-                        metrics_channel,
-                    )
-                    .await
+                match bench_protocol_adapter.as_ref() {
+                    BenchmarkMode::Http(http_bench_session) => {
+                        bench_run
+                            .send_load(http_bench_session, metrics_channel)
+                            .await
+                    }
+                    BenchmarkMode::Tcp(tcp_bench_session) => {
+                        bench_run
+                            .send_load(tcp_bench_session, metrics_channel)
+                            .await
+                    }
+                    BenchmarkMode::Udp(udp_bench_session) => {
+                        bench_run
+                            .send_load(udp_bench_session, metrics_channel)
+                            .await
+                    }
+                    BenchmarkMode::Grpc(grpc_bench_session) => {
+                        bench_run
+                            .send_load(grpc_bench_session, metrics_channel)
+                            .await
+                    }
+                    BenchmarkMode::Ws(ws_bench_session) => {
+                        bench_run.send_load(ws_bench_session, metrics_channel).await
+                    }
+                    BenchmarkMode::Redis(redis_bench_session) => {
+                        bench_run
+                            .send_load(redis_bench_session, metrics_channel)
+                            .await
+                    }
+                    BenchmarkMode::Memcached(memcached_bench_session) => {
+                        bench_run
+                            .send_load(memcached_bench_session, metrics_channel)
+                            .await
+                    }
+                }
             }));
         }
 
@@ -182,12 +471,46 @@ impl RateLadder {
     }
 }
 
+/// Either a duration, e.g. `30s`, or the number of requests, e.g. `1000`.
+impl FromStr for Warmup {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Ok(requests) = s.parse() {
+            return Ok(Warmup::Requests(requests));
+        }
+        humantime::Duration::from_str(s)
+            .map(|d| Warmup::Duration(d.into()))
+            .map_err(|_| format!("Expected a duration or a number of requests, got `{}`", s))
+    }
+}
+
+impl ConcurrencyLadder {
+    fn level(&self, iteration: usize) -> Option<usize> {
+        let mut level = self.start.max(1);
+        for _ in 0..iteration {
+            if level >= self.end {
+                return None;
+            }
+            level = match self.increment {
+                Some(increment) => level + increment.max(1),
+                None => level * 2,
+            }
+            .min(self.end);
+        }
+        Some(level)
+    }
+}
+
 impl fmt::Display for BenchBatch {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         writeln!(
             f,
-            "Runs: {}, first run: {:?}, mode: {}",
+            "{}Runs: {}, rate: {}, first run: {:?}, mode: {}",
+            if self.warmup { "Warm-up. " } else { "" },
             self.runs.len(),
+            self.rate,
             self.runs[0],
             self.mode
         )
@@ -196,7 +519,18 @@ impl fmt::Display for BenchBatch {
 
 #[cfg(test)]
 mod tests {
-    use crate::bench_session::RateLadderBuilder;
+    use crate::bench_session::{
+        BenchBatch, BenchSessionBuilder, ConcurrencyLadderBuilder, RateLadderBuilder, Warmup,
+    };
+    use crate::configuration::BenchmarkMode;
+    use crate::http_bench_session::{
+        HttpBenchAdapterBuilder, HttpClientConfigBuilder, HttpRequestBuilder,
+    };
+    use crate::latency_histogram::HistogramBackend;
+    use crate::load_profile::{hold, linear_ramp, LoadProfile};
+    use crate::metrics::{BenchRunMetrics, RequestStats, Snapshot};
+    use std::sync::Arc;
+    use std::time::Duration;
 
     #[test]
     fn test_rate_ladder_with_increment() {
@@ -296,4 +630,185 @@ mod tests {
             assert_eq!(i + 1 == max_iterations, rate_ladder.complete);
         }
     }
+
+    fn http_session(rate: f64) -> BenchSessionBuilder {
+        let http_adapter = HttpBenchAdapterBuilder::default()
+            .request(
+                HttpRequestBuilder::default()
+                    .url(vec!["http://localhost/".to_string()])
+                    .build()
+                    .unwrap(),
+            )
+            .config(HttpClientConfigBuilder::default().build().unwrap())
+            .build()
+            .unwrap();
+
+        let mut builder = BenchSessionBuilder::default();
+        builder
+            .concurrency(2)
+            .rate_ladder(
+                RateLadderBuilder::default()
+                    .start(rate)
+                    .end(rate)
+                    .rate_increment(None)
+                    .step_duration(None)
+                    .step_requests(Some(10))
+                    .build()
+                    .expect("RateLadderBuilder failed"),
+            )
+            .mode(Arc::new(BenchmarkMode::Http(http_adapter)))
+            .request_timeout(None);
+        builder
+    }
+
+    #[tokio::test]
+    async fn test_load_profile() {
+        let mut steps = linear_ramp(Duration::from_secs(20), 100., 200., 2);
+        steps.extend(hold(Duration::from_secs(5), 0.));
+        let session = http_session(0.)
+            .load_profile(Some(LoadProfile::new(steps).unwrap()))
+            .build()
+            .unwrap();
+
+        let batches: Vec<(f64, usize)> = session.map(|b| (b.rate, b.runs.len())).collect();
+        assert_eq!(vec![(100., 2), (200., 2), (0., 2)], batches);
+    }
+
+    #[tokio::test]
+    async fn test_concurrency_ladder() {
+        let ladder = |start: usize, end: usize, increment: Option<usize>| {
+            let ladder = ConcurrencyLadderBuilder::default()
+                .start(start)
+                .end(end)
+                .increment(increment)
+                .build()
+                .unwrap();
+            (0..).map_while(|i| ladder.level(i)).collect::<Vec<_>>()
+        };
+        assert_eq!(vec![1, 2, 4, 8, 16, 32, 64, 128, 256], ladder(1, 256, None));
+        assert_eq!(vec![3, 6, 12, 20], ladder(3, 20, None));
+        assert_eq!(vec![10, 20, 25], ladder(10, 25, Some(10)));
+        assert_eq!(vec![5], ladder(5, 1, None));
+
+        let session = http_session(100.)
+            .concurrency_ladder(Some(
+                ConcurrencyLadderBuilder::default()
+                    .start(1)
+                    .end(4)
+                    .build()
+                    .unwrap(),
+            ))
+            .build()
+            .unwrap();
+        let batches: Vec<(f64, usize)> = session.map(|b| (b.rate, b.runs.len())).collect();
+        assert_eq!(vec![(100., 1), (100., 2), (100., 4)], batches);
+    }
+
+    #[tokio::test]
+    async fn test_report_interval() {
+        let request = || RequestStats {
+            is_success: true,
+            bytes_processed: 0,
+            status: "200 OK".to_string(),
+            duration: Duration::from_millis(1),
+            operation_name: None,
+            fatal_error: false,
+            phases: vec![],
+        };
+
+        for cumulative in [false, true] {
+            let (stats_sender, stats_receiver) = tokio::sync::mpsc::channel(100);
+            let (sender, receiver) = std::sync::mpsc::channel();
+            let mut metrics = BenchRunMetrics::new(HistogramBackend::Simple);
+            metrics.concurrency = 2;
+            let aggregator = tokio::spawn(BenchBatch::aggregate_with_snapshots(
+                metrics,
+                stats_receiver,
+                Duration::from_millis(200),
+                cumulative,
+                sender,
+            ));
+
+            // 5 requests in each of the first two intervals
+            for _ in 0..2 {
+                for _ in 0..5 {
+                    stats_sender.send(request()).await.unwrap();
+                }
+                tokio::time::sleep(Duration::from_millis(300)).await;
+            }
+            drop(stats_sender);
+            let metrics = aggregator.await.unwrap();
+
+            assert_eq!(None, metrics.snapshot);
+            assert_eq!(10, metrics.combined.total_requests);
+
+            let snapshots: Vec<BenchRunMetrics> = receiver.try_iter().collect();
+            assert!(snapshots.len() >= 2, "{} snapshots", snapshots.len());
+            assert!(snapshots.iter().all(|s| s.concurrency == 2));
+            let requests: Vec<usize> = snapshots
+                .iter()
+                .map(|s| s.combined.total_requests)
+                .collect();
+            if cumulative {
+                assert!(snapshots
+                    .iter()
+                    .all(|s| s.snapshot == Some(Snapshot::Cumulative)));
+                assert_eq!(vec![5, 10], requests[..2]);
+            } else {
+                assert!(snapshots
+                    .iter()
+                    .all(|s| s.snapshot == Some(Snapshot::Interval)));
+                assert_eq!(vec![5, 5], requests[..2]);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_warmup() {
+        assert_eq!(Ok(Warmup::Requests(1000)), "1000".parse());
+        assert_eq!(
+            Ok(Warmup::Duration(Duration::from_secs(30))),
+            " 30s".parse()
+        );
+        assert!("-1".parse::<Warmup>().is_err());
+        assert!("30x".parse::<Warmup>().is_err());
+
+        let batches = |warmup_each_step: bool| {
+            let mut steps = linear_ramp(Duration::from_secs(20), 100., 200., 2);
+            steps.extend(hold(Duration::from_secs(5), 0.));
+            http_session(0.)
+                .concurrency(4)
+                .load_profile(Some(LoadProfile::new(steps).unwrap()))
+                .warmup(Some(Warmup::Requests(10)))
+                .warmup_each_step(warmup_each_step)
+                .build()
+                .unwrap()
+                .map(|b| (b.is_warmup(), b.rate, format!("{:?}", b.runs[0])))
+                .collect::<Vec<_>>()
+        };
+
+        let first_only = batches(false);
+        let warmups: Vec<(bool, f64)> = first_only.iter().map(|b| (b.0, b.1)).collect();
+        assert_eq!(
+            vec![(true, 100.), (false, 100.), (false, 200.), (false, 0.)],
+            warmups
+        );
+        // 10 requests across 4 clients
+        assert!(first_only[0].2.contains("max_requests: Some(3)"));
+        assert!(first_only[1].2.contains("max_duration: Some(10s)"));
+
+        let each = batches(true);
+        let warmups: Vec<(bool, f64)> = each.iter().map(|b| (b.0, b.1)).collect();
+        assert_eq!(
+            vec![
+                (true, 100.),
+                (false, 100.),
+                (true, 200.),
+                (false, 200.),
+                (true, 0.),
+                (false, 0.)
+            ],
+            warmups
+        );
+    }
 }
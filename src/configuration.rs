@@ -1,4 +1,8 @@
-use crate::bench_session::{BenchSession, BenchSessionBuilder, RateLadder, RateLadderBuilder};
+use crate::assertion::ResponseAssertions;
+use crate::bench_session::{
+    BenchSession, BenchSessionBuilder, ConcurrencyLadder, ConcurrencyLadderBuilder, RateLadder,
+    RateLadderBuilder, Warmup,
+};
 /// Copyright 2020 Developers of the perf-gauge project.
 ///
 /// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
@@ -6,10 +10,33 @@ use crate::bench_session::{BenchSession, BenchSessionBuilder, RateLadder, RateLa
 /// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
 /// option. This file may not be copied, modified, or distributed
 /// except according to those terms.
+use crate::csv_reporter::CsvReporter;
+use crate::data_feeder::{DataFeeder, FeedExhausted, FeedStrategy};
+use crate::grpc_bench_session::{GrpcBenchAdapter, GrpcBenchAdapterBuilder};
+use crate::har;
+use crate::hdr_log_reporter::HdrLogReporter;
+use crate::html_reporter::HtmlReporter;
 use crate::http_bench_session::{
     HttpBenchAdapter, HttpBenchAdapterBuilder, HttpClientConfigBuilder, HttpRequestBuilder,
+    OperationOrder,
+};
+use crate::http_operations;
+use crate::json_reporter::JsonReporter;
+use crate::kv_workload::{KeyValueWorkload, KeyValueWorkloadBuilder};
+use crate::latency_histogram::HistogramBackend;
+use crate::load_profile::LoadProfile;
+use crate::memcached_bench_session::{
+    MemcachedBenchAdapter, MemcachedBenchAdapterBuilder, MemcachedProtocol,
 };
 use crate::metrics::{DefaultConsoleReporter, ExternalMetricsServiceReporter};
+use crate::rate_limiter::ArrivalProcess;
+use crate::rate_search::{RateSearch, Slo};
+use crate::redis_bench_session::{RedisBenchAdapter, RedisBenchAdapterBuilder};
+#[cfg(feature = "scripting")]
+use crate::script::RequestScript;
+use crate::tcp_bench_session::{TcpBenchAdapter, TcpBenchAdapterBuilder, TcpResponseEnd};
+use crate::udp_bench_session::{UdpBenchAdapter, UdpBenchAdapterBuilder};
+use crate::ws_bench_session::{WsBenchAdapter, WsBenchAdapterBuilder};
 use clap::Args;
 use clap::Parser;
 use clap::Subcommand;
@@ -26,9 +53,17 @@ use tokio::io;
 #[derive(Clone)]
 pub enum BenchmarkMode {
     Http(HttpBenchAdapter),
+    Tcp(TcpBenchAdapter),
+    Udp(UdpBenchAdapter),
+    Grpc(GrpcBenchAdapter),
+    Ws(WsBenchAdapter),
+    Redis(RedisBenchAdapter),
+    Memcached(MemcachedBenchAdapter),
 }
 
 #[derive(Clone, Builder)]
+#[builder(build_fn(validate = "Self::validate"))]
+#[allow(dead_code)]
 pub struct BenchmarkConfig {
     #[builder(default)]
     pub name: Option<String>,
@@ -41,6 +76,27 @@ pub struct BenchmarkConfig {
     pub rate_ladder: RateLadder,
     pub mode: BenchmarkMode,
     request_timeout: Option<Duration>,
+    /// Open model: the max number of requests in flight.
+    #[builder(default)]
+    max_in_flight: Option<usize>,
+    #[builder(default)]
+    arrival: ArrivalProcess,
+    #[builder(default)]
+    load_profile: Option<LoadProfile>,
+    #[builder(default)]
+    rate_search: Option<RateSearch>,
+    #[builder(default)]
+    concurrency_ladder: Option<ConcurrencyLadder>,
+    #[builder(default)]
+    warmup: Option<Warmup>,
+    #[builder(default)]
+    warmup_each_step: bool,
+    #[builder(default)]
+    pub histogram_backend: HistogramBackend,
+    #[builder(default)]
+    report_interval: Option<Duration>,
+    #[builder(default)]
+    cumulative_reports: bool,
     #[builder(default)]
     pub reporters: Vec<Arc<dyn ExternalMetricsServiceReporter + Send + Sync + 'static>>,
 }
@@ -52,12 +108,27 @@ struct Cli {
     /// Concurrent clients. Default `1`.
     #[clap(short, long, default_value_t = 1)]
     concurrency: usize,
+    /// Max concurrency. The concurrency is doubled (or increased by --concurrency_step)
+    /// after each step, starting with --concurrency. The rate (if any) is the same for all steps.
+    #[clap(long = "concurrency_max", conflicts_with_all = &["rate-step", "rate-max", "load-profile", "find-max"])]
+    concurrency_max: Option<usize>,
+    /// Concurrency increase step (until it reaches --concurrency_max).
+    #[clap(long = "concurrency_step", requires = "concurrency-max")]
+    concurrency_step: Option<usize>,
     /// Duration of the test.
     #[clap(short, long)]
     duration: Option<String>,
     /// Number of requests per client.
     #[clap(short, long = "num_req")]
     num_req: Option<usize>,
+    /// Warm-up before the first step, either a duration (e.g. `30s`) or the number of requests (e.g. `1000`).
+    /// Its results are discarded, e.g. to warm up JIT-based services. The rate and the concurrency are the same as of the step.
+    #[clap(long)]
+    warmup: Option<Warmup>,
+    /// Warm-up before each step, not only the first one. The step opens its own connections,
+    /// so only the server is warmed up.
+    #[clap(long = "warmup_each_step", requires = "warmup")]
+    warmup_each_step: bool,
     /// Test case name. Optional. Can be used for tagging metrics.
     #[clap(short = 'N', long)]
     name: Option<String>,
@@ -70,15 +141,72 @@ struct Cli {
     /// Max rate per second. Requires --rate-step
     #[clap(long = "rate_max")]
     rate_max: Option<f64>,
+    /// Load profile: a YAML or CSV file with `duration, rate` steps, each of them is reported separately.
+    /// YAML files can contain shapes: `ramp`, `exp_ramp`, `sine`, `spike` and `hold`.
+    /// Replaces --rate, --duration and --num_req.
+    #[clap(long = "load_profile", conflicts_with_all = &["rate", "rate-step", "rate-max", "duration", "num-req"])]
+    load_profile: Option<String>,
+    /// Find the max rate meeting the SLO (--slo_p99 and/or --slo_success), starting with --rate.
+    /// The rate is doubled (or increased by --rate_step up to --rate_max) until the SLO is broken,
+    /// then binary search is used. Each step takes --duration or --num_req.
+    #[clap(long = "find_max", conflicts_with = "load-profile")]
+    find_max: bool,
+    /// SLO for --find_max: the max p99 latency, e.g. `100ms`.
+    #[clap(long = "slo_p99")]
+    slo_p99: Option<String>,
+    /// SLO for --find_max: the min success rate in percents, e.g. `99.9`.
+    #[clap(long = "slo_success")]
+    slo_success: Option<f64>,
     /// takes_value "The number of iterations with the max rate. By default `1`.
     #[clap(short, long = "max_iter", default_value_t = 1)]
     max_iter: usize,
     /// If it's a part of a continuous run. In this case metrics are not reset at the end to avoid saw-like plots.
     #[clap(long)]
     continuous: bool,
-    /// Timeout of a single request. E.g. "--request_timeout 30s". Timeouts are treated as fatal errors.
+    /// Timeout of a single request. E.g. "--request_timeout 30s". Timeouts are treated as fatal errors,
+    /// except for --find_max, which reports them as failed requests.
     #[clap(long = "request_timeout")]
     request_timeout: Option<String>,
+    /// Open model: requests are sent at --rate regardless of responses, and latency is measured
+    /// from the intended send time (no coordinated omission). Requires --rate.
+    #[clap(long = "open_model")]
+    open_model: bool,
+    /// The max number of requests in flight in the open model (across all clients). Default `1000`.
+    #[clap(long = "max_in_flight", default_value_t = 1000)]
+    max_in_flight: usize,
+    /// How requests are spread in time at --rate: `constant`, `poisson` (random independent arrivals)
+    /// or `bursty:ON/OFF`, e.g. `bursty:1s/4s` (requests are sent only during ON periods). Default `constant`.
+    #[clap(long, default_value = "constant")]
+    arrival: ArrivalProcess,
+    /// Report metrics of long steps periodically, e.g. `10s`, so dashboards are updated during the step.
+    /// Each report contains the requests since the previous one. The whole step is reported at the end as usual.
+    #[clap(long = "report_interval")]
+    report_interval: Option<String>,
+    /// Periodic reports contain all requests of the step so far, not only since the previous report.
+    #[clap(long = "report_cumulative", requires = "report-interval")]
+    report_cumulative: bool,
+    /// Record latencies in HdrHistograms of nanoseconds, instead of microsecond buckets.
+    #[clap(long)]
+    hdr: bool,
+    /// Significant digits of --hdr histograms, from 1 to 5. Default `3`.
+    #[clap(long = "hdr_digits", requires = "hdr", value_parser = clap::value_parser!(u8).range(1..=5))]
+    hdr_digits: Option<u8>,
+    /// The max latency tracked by --hdr histograms, e.g. `10m`. Longer ones are recorded as this value. Default `1h`.
+    #[clap(long = "hdr_max", requires = "hdr", value_parser = parse_hdr_max)]
+    hdr_max: Option<Duration>,
+    /// Write latency histograms of each step to this file in the HdrHistogram interval log format
+    /// (base64-encoded compressed histograms), e.g. to merge and re-analyse them later. Requires --hdr.
+    #[clap(long = "hdr_log", requires = "hdr")]
+    hdr_log: Option<String>,
+    /// Write the results of all steps to this JSON file, including per-operation results and latency histograms.
+    #[clap(long = "json_report")]
+    json_report: Option<String>,
+    /// Append one row per step to this CSV file (created with a header if it doesn't exist).
+    #[clap(long = "csv_report")]
+    csv_report: Option<String>,
+    /// Write a self-contained HTML page with charts of the results of all steps and the configuration to this file.
+    #[clap(long = "html_report")]
+    html_report: Option<String>,
     /// If you'd like to send metrics to Prometheus PushGateway, specify the server URL. E.g. 10.0.0.1:9091
     #[clap(long)]
     prometheus: Option<String>,
@@ -89,9 +217,17 @@ struct Cli {
     command: Commands,
 }
 
+// parsed once on start, so the size doesn't matter
+#[allow(clippy::large_enum_variant)]
 #[derive(Subcommand, Debug)]
 enum Commands {
     Http(HttpOptions),
+    Tcp(TcpOptions),
+    Udp(UdpOptions),
+    Grpc(GrpcOptions),
+    Ws(WsOptions),
+    Redis(RedisOptions),
+    Memcached(MemcachedOptions),
 }
 
 #[derive(Args, Debug)]
@@ -100,6 +236,7 @@ enum Commands {
 #[clap(propagate_version = true)]
 struct HttpOptions {
     /// Target, e.g. https://my-service.com:8443/8kb Can be multiple ones (with random choice balancing).
+    /// Unix domain sockets are supported in `unix:///path/to.sock:/request/path` form.
     #[clap()]
     target: Vec<String>,
     /// Headers in "Name:Value1" form. E.g. `-H "Authentication:Bearer token" -H "Date:2022-03-17"`
@@ -124,6 +261,218 @@ struct HttpOptions {
     /// Enforce HTTP/2 only.
     #[clap(long = "http2_only")]
     http2_only: bool,
+    /// Rhai script building requests, see README for details. Optional.
+    #[cfg(feature = "scripting")]
+    #[clap(long)]
+    script: Option<String>,
+    /// Send `{{...}}` in targets, headers and bodies as is, e.g. for Mustache templates or GraphQL queries.
+    #[clap(long = "no_template", conflicts_with_all = &["data", "scenario"])]
+    no_template: bool,
+    /// CSV (with a header) or JSONL (`.jsonl`, `.ndjson`) file. Each request binds one record to `{{column}}` placeholders. Optional.
+    #[clap(long)]
+    data: Option<String>,
+    /// The order of data records: `sequential`, `random` or `partitioned` (each connection has its own share). Default `sequential`.
+    #[clap(long = "data_strategy", default_value = "sequential", possible_values = ["sequential", "random", "partitioned"])]
+    data_strategy: String,
+    /// What to do when data records are exhausted: `stop` or `wrap`. Default `wrap`.
+    #[clap(long = "data_exhausted", default_value = "wrap", possible_values = ["stop", "wrap"])]
+    data_exhausted: String,
+    /// HAR file to replay instead of targets. Each entry is reported as `METHOD /path` operation. Optional.
+    #[clap(long)]
+    har: Option<String>,
+    /// The order of HAR entries: `recorded` (per connection) or `weighted` (random, all entries are equally likely). Default `recorded`.
+    #[clap(long = "har_order", default_value = "recorded", possible_values = ["recorded", "weighted"])]
+    har_order: String,
+    /// YAML file with named requests chosen by weight, see README for details. Replaces targets. Optional.
+    #[clap(long)]
+    operations: Option<String>,
+    /// YAML file with steps, which each connection sends in order, extracting values from responses.
+    /// The same format as `--operations`, see README for details. Replaces targets. Optional.
+    #[clap(long)]
+    scenario: Option<String>,
+    /// Expected status codes, e.g. `--expect_status 200 --expect_status 304`. By default, any `2xx`.
+    #[clap(long = "expect_status")]
+    expect_status: Vec<u16>,
+    /// Text, which successful response bodies must contain. Can be multiple ones.
+    #[clap(long = "expect_body")]
+    expect_body: Vec<String>,
+    /// Regex, which successful response bodies must match. Can be multiple ones.
+    #[clap(long = "expect_body_regex")]
+    expect_body_regex: Vec<String>,
+    /// JSON value in `PATH=VALUE` form, e.g. `--expect_json '$.status=ok'`. Can be multiple ones.
+    #[clap(long = "expect_json")]
+    expect_json: Vec<String>,
+    /// Header, which successful responses must have. Can be multiple ones.
+    #[clap(long = "expect_header")]
+    expect_header: Vec<String>,
+    /// Minimum size of successful response bodies in bytes.
+    #[clap(long = "min_body_size")]
+    min_body_size: Option<usize>,
+    /// Maximum size of successful response bodies in bytes.
+    #[clap(long = "max_body_size")]
+    max_body_size: Option<usize>,
+    /// SHA-256 (hex) of successful response bodies.
+    #[clap(long = "expect_sha256")]
+    expect_sha256: Option<String>,
+}
+
+#[derive(Args, Debug)]
+#[clap(about = "Run in raw TCP mode", long_about = None)]
+#[clap(author, version, long_about = None)]
+#[clap(propagate_version = true)]
+struct TcpOptions {
+    /// Target, e.g. 10.0.0.1:8080 Can be multiple ones (with random choice balancing).
+    #[clap()]
+    target: Vec<String>,
+    /// Payload to send. Could be either `random://[0-9]+`, `file://$filename` or `base64://${valid_base64}`. Optional.
+    #[clap(short = 'P', long)]
+    payload: Option<String>,
+    /// Expected response size in bytes. The response is complete once this number of bytes is received.
+    #[clap(long = "response_size")]
+    response_size: Option<usize>,
+    /// Response delimiter, e.g. `\n` or `\r\n\r\n`. The response is complete once the delimiter is received.
+    /// If neither the size nor the delimiter is set, the response is read until the server closes the connection.
+    #[clap(long)]
+    delimiter: Option<String>,
+    /// If connections should be re-used. Requires either --response_size or --delimiter.
+    #[clap(long = "conn_reuse")]
+    conn_reuse: bool,
+}
+
+#[derive(Args, Debug)]
+#[clap(about = "Run in UDP request/response mode", long_about = None)]
+#[clap(author, version, long_about = None)]
+#[clap(propagate_version = true)]
+struct UdpOptions {
+    /// Target, e.g. 10.0.0.1:53 Can be multiple ones (with random choice balancing).
+    #[clap()]
+    target: Vec<String>,
+    /// Payload to send. Could be either `random://[0-9]+`, `file://$filename` or `base64://${valid_base64}`. Optional.
+    #[clap(short = 'P', long)]
+    payload: Option<String>,
+    /// How long to wait for a reply, e.g. "--packet_timeout 100ms". Packets without a reply are reported as `Lost`. Default `1s`.
+    #[clap(long = "packet_timeout", default_value = "1s")]
+    packet_timeout: String,
+}
+
+#[derive(Args, Debug)]
+#[clap(about = "Run in gRPC mode (over HTTP/2)", long_about = None)]
+#[clap(author, version, long_about = None)]
+#[clap(propagate_version = true)]
+struct GrpcOptions {
+    /// Target, e.g. https://my-service.com:8443 Can be multiple ones (with random choice balancing).
+    #[clap()]
+    target: Vec<String>,
+    /// Service method, e.g. `/helloworld.Greeter/SayHello`.
+    #[clap(short = 'M', long)]
+    method: String,
+    /// Serialized protobuf message. Could be either `random://[0-9]+`, `file://$filename` or `base64://${valid_base64}`. By default empty.
+    #[clap(short = 'B', long)]
+    message: Option<String>,
+    /// Metadata in "Name:Value1" form. E.g. `-H "authorization:Bearer token"`
+    /// It can contain multiple values, e.g. "Name:Value1:Value2:Value3". In this case a random one is chosen for each request.
+    #[clap(short = 'H', long)]
+    header: Vec<String>,
+    /// Stop immediately on gRPC status codes. E.g. `-E 7 -E 16`
+    #[clap(short = 'E', long = "error_stop")]
+    error_stop: Vec<u16>,
+    /// Allow self signed certificates.
+    #[clap(long = "ignore_cert")]
+    ignore_cert: bool,
+    /// If connections should be re-used.
+    #[clap(long = "conn_reuse")]
+    conn_reuse: bool,
+}
+
+#[derive(Args, Debug)]
+#[clap(about = "Run in WebSocket mode", long_about = None)]
+#[clap(author, version, long_about = None)]
+#[clap(propagate_version = true)]
+struct WsOptions {
+    /// Target, e.g. wss://my-service.com:8443/echo Can be multiple ones (with random choice balancing).
+    #[clap()]
+    target: Vec<String>,
+    /// Handshake headers in "Name:Value1" form. E.g. `-H "Authentication:Bearer token"`
+    /// It can contain multiple values, e.g. "Name:Value1:Value2:Value3". In this case a random one is chosen for each connection.
+    #[clap(short = 'H', long)]
+    header: Vec<String>,
+    /// Message to send. Could be either `random://[0-9]+`, `file://$filename` or `base64://${valid_base64}`. By default empty.
+    #[clap(short = 'B', long)]
+    message: Option<String>,
+    /// Send messages as text frames. By default binary frames are sent.
+    #[clap(long)]
+    text: bool,
+    /// Allow self signed certificates.
+    #[clap(long = "ignore_cert")]
+    ignore_cert: bool,
+    /// If connections should be re-used. Otherwise, a new connection is established for each message.
+    #[clap(long = "conn_reuse")]
+    conn_reuse: bool,
+}
+
+#[derive(Args, Debug)]
+#[clap(about = "Run in Redis (RESP) mode", long_about = None)]
+#[clap(author, version, long_about = None)]
+#[clap(propagate_version = true)]
+struct RedisOptions {
+    /// Target, e.g. 10.0.0.1:6379 Can be multiple ones (with random choice balancing).
+    #[clap()]
+    target: Vec<String>,
+    /// Arbitrary command, e.g. `-C "INCR counter"`. Can be multiple ones (with random choice).
+    /// Arguments are separated by whitespace. If not specified, GET/SET commands with random keys are sent.
+    #[clap(short = 'C', long)]
+    command: Vec<String>,
+    /// Share of SET commands, e.g. 0.2. By default, only GET commands are sent.
+    #[clap(long = "set_ratio", default_value_t = 0.)]
+    set_ratio: f64,
+    /// The number of distinct keys. Default `1000`.
+    #[clap(long, default_value_t = 1000)]
+    keyspace: usize,
+    /// Key prefix. Default `key:`.
+    #[clap(long = "key_prefix", default_value = "key:")]
+    key_prefix: String,
+    /// Size of values for SET commands. Either fixed, e.g. `64`, or a range, e.g. `16-1024`. Default `64`.
+    #[clap(long = "value_size", default_value = "64")]
+    value_size: String,
+}
+
+#[derive(Args, Debug)]
+#[clap(about = "Run in Memcached mode", long_about = None)]
+#[clap(author, version, long_about = None)]
+#[clap(propagate_version = true)]
+struct MemcachedOptions {
+    /// Target, e.g. 10.0.0.1:11211 Can be multiple ones (with random choice balancing).
+    #[clap()]
+    target: Vec<String>,
+    /// Protocol: `text` or `binary`. Default `text`.
+    #[clap(long, default_value = "text", possible_values = ["text", "binary"])]
+    protocol: String,
+    /// Share of SET commands, e.g. 0.2. By default, only GET commands are sent.
+    #[clap(long = "set_ratio", default_value_t = 0.)]
+    set_ratio: f64,
+    /// The number of distinct keys. Default `1000`.
+    #[clap(long, default_value_t = 1000)]
+    keyspace: usize,
+    /// Key prefix. Default `key:`.
+    #[clap(long = "key_prefix", default_value = "key:")]
+    key_prefix: String,
+    /// Size of values for SET commands. Either fixed, e.g. `64`, or a range, e.g. `16-1024` (uniformly distributed). Default `64`.
+    #[clap(long = "value_size", default_value = "64")]
+    value_size: String,
+}
+
+/// HdrHistogram tracks nanoseconds, from 1 up to `u64::MAX / 2`.
+fn parse_hdr_max(value: &str) -> Result<Duration, String> {
+    let max_value: Duration = humantime::Duration::from_str(value.trim())
+        .map_err(|e| format!("Illegal duration `{}`: {}", value, e))?
+        .into();
+    if max_value < Duration::from_micros(1) {
+        return Err(format!("Must be at least 1us, got `{}`", value));
+    }
+    if max_value.as_nanos() > (u64::MAX / 2) as u128 {
+        return Err(format!("Too long: `{}`", value));
+    }
+    Ok(max_value)
 }
 
 impl BenchmarkConfig {
@@ -150,11 +499,67 @@ impl BenchmarkConfig {
 
         let number_of_requests = cli.num_req;
 
-        if duration.is_none() && number_of_requests.is_none() {
+        let load_profile = cli.load_profile.as_ref().map(|path| {
+            LoadProfile::from_file(path)
+                .unwrap_or_else(|e| panic!("Cannot read load profile {}: {}", path, e))
+        });
+
+        if cli.open_model && load_profile.is_none() && !rate_per_second.is_some_and(|r| r > 0.) {
+            panic!("RATE or LOAD_PROFILE is required for the open model");
+        }
+
+        if duration.is_none() && number_of_requests.is_none() && load_profile.is_none() {
             panic!("Either the number of requests or the test duration must be specified");
         }
 
-        let rate_ladder = if let Some(rate_max) = rate_max {
+        let report_interval: Option<Duration> = cli.report_interval.as_ref().map(|d| {
+            humantime::Duration::from_str(d.as_str())
+                .expect("Illegal duration")
+                .into()
+        });
+        if report_interval.is_some_and(|i| i.is_zero()) {
+            panic!("REPORT_INTERVAL must be positive");
+        }
+
+        let histogram_backend = if cli.hdr {
+            HistogramBackend::Hdr {
+                significant_digits: cli.hdr_digits.unwrap_or(3),
+                max_value: cli.hdr_max.unwrap_or(Duration::from_secs(3600)),
+            }
+        } else {
+            HistogramBackend::Simple
+        };
+
+        let rate_search = if cli.find_max {
+            let slo = Slo {
+                max_p99: cli.slo_p99.as_ref().map(|d| {
+                    humantime::Duration::from_str(d.as_str())
+                        .expect("Illegal duration")
+                        .into()
+                }),
+                min_success_rate: cli.slo_success,
+            };
+            if slo.max_p99.is_none() && slo.min_success_rate.is_none() {
+                panic!("SLO_P99 and/or SLO_SUCCESS are required if FIND_MAX is specified");
+            }
+            let start = rate_per_second
+                .filter(|r| *r > 0.)
+                .expect("RATE is required if FIND_MAX is specified");
+            Some(RateSearch::new(start, rate_step, rate_max, slo))
+        } else {
+            None
+        };
+
+        let rate_ladder = if rate_search.is_some() {
+            RateLadderBuilder::default()
+                .start(0.)
+                .end(0.)
+                .rate_increment(None)
+                .step_duration(duration)
+                .step_requests(number_of_requests)
+                .build()
+                .expect("RateLadderBuilder failed")
+        } else if let Some(rate_max) = rate_max {
             let rate_per_second =
                 rate_per_second.expect("RATE is required if RATE_MAX is specified");
             let rate_step = rate_step.expect("RATE_STEP is required if RATE_MAX is specified");
@@ -187,6 +592,23 @@ impl BenchmarkConfig {
             .verbose(false)
             .continuous(cli.continuous)
             .request_timeout(request_timeout)
+            .max_in_flight(Some(cli.max_in_flight).filter(|_| cli.open_model))
+            .arrival(cli.arrival)
+            .load_profile(load_profile)
+            .rate_search(rate_search)
+            .warmup(cli.warmup)
+            .warmup_each_step(cli.warmup_each_step)
+            .histogram_backend(histogram_backend)
+            .report_interval(report_interval)
+            .cumulative_reports(cli.report_cumulative)
+            .concurrency_ladder(cli.concurrency_max.map(|end| {
+                ConcurrencyLadderBuilder::default()
+                    .start(concurrency)
+                    .end(end)
+                    .increment(cli.concurrency_step)
+                    .build()
+                    .expect("ConcurrencyLadderBuilder failed")
+            }))
             .mode(BenchmarkConfig::build_mode(&cli))
             .reporters(BenchmarkConfig::build_metric_destinations(
                 cli.name.clone(),
@@ -208,7 +630,7 @@ impl BenchmarkConfig {
             exit(-1);
         }
 
-        vec![Arc::new(DefaultConsoleReporter::new(test_case_name))]
+        BenchmarkConfig::build_local_destinations(test_case_name, args)
     }
 
     #[cfg(feature = "report-to-prometheus")]
@@ -219,11 +641,8 @@ impl BenchmarkConfig {
         use crate::prometheus_reporter::PrometheusReporter;
         use std::net::SocketAddr;
 
-        let mut metrics_destinations: Vec<
-            Arc<dyn ExternalMetricsServiceReporter + Send + Sync + 'static>,
-        > = vec![Arc::new(DefaultConsoleReporter::new(
-            test_case_name.clone(),
-        ))];
+        let mut metrics_destinations =
+            BenchmarkConfig::build_local_destinations(test_case_name.clone(), args);
 
         if let Some(prometheus_addr) = &args.prometheus {
             if SocketAddr::from_str(prometheus_addr.as_str()).is_err() {
@@ -245,18 +664,129 @@ impl BenchmarkConfig {
         metrics_destinations
     }
 
+    /// The console and files.
+    fn build_local_destinations(
+        test_case_name: Option<String>,
+        args: &Cli,
+    ) -> Vec<Arc<dyn ExternalMetricsServiceReporter + Send + Sync>> {
+        let mut metrics_destinations: Vec<
+            Arc<dyn ExternalMetricsServiceReporter + Send + Sync + 'static>,
+        > = vec![Arc::new(DefaultConsoleReporter::new(
+            test_case_name.clone(),
+        ))];
+
+        if let Some(path) = &args.hdr_log {
+            metrics_destinations
+                .push(Arc::new(HdrLogReporter::new(path).unwrap_or_else(|e| {
+                    panic!("Cannot create HDR log {}: {}", path, e)
+                })));
+        }
+
+        if let Some(path) = &args.json_report {
+            metrics_destinations.push(Arc::new(
+                JsonReporter::new(path, test_case_name.clone())
+                    .unwrap_or_else(|e| panic!("Cannot create JSON report {}: {}", path, e)),
+            ));
+        }
+
+        if let Some(path) = &args.csv_report {
+            metrics_destinations.push(Arc::new(
+                CsvReporter::new(path, test_case_name.clone())
+                    .unwrap_or_else(|e| panic!("Cannot open CSV report {}: {}", path, e)),
+            ));
+        }
+
+        if let Some(path) = &args.html_report {
+            let command_line: Vec<String> = std::env::args().collect();
+            let configuration = BenchmarkConfig::redact_command_line(&command_line);
+            metrics_destinations.push(Arc::new(
+                HtmlReporter::new(path, test_case_name, configuration)
+                    .unwrap_or_else(|e| panic!("Cannot create HTML report {}: {}", path, e)),
+            ));
+        }
+
+        metrics_destinations
+    }
+
     fn build_mode(args: &Cli) -> BenchmarkMode {
         match &args.command {
             Commands::Http(config) => {
-                #[cfg(feature = "tls-boring")]
-                if config.ignore_cert {
-                    use std::process::exit;
+                BenchmarkConfig::check_ignore_cert(config.ignore_cert);
+
+                let mut http_config = HttpBenchAdapterBuilder::default();
+                #[cfg(feature = "scripting")]
+                if let Some(ref script) = config.script {
+                    http_config.script(Some(
+                        RequestScript::from_file(script).expect("Cannot load the script"),
+                    ));
+                }
+
+                http_config.assertions(BenchmarkConfig::build_assertions(config));
 
-                    println!("--ignore_cert is not supported for BoringSSL");
-                    exit(-1);
+                let mut variables = vec![];
+                if let Some(ref data) = config.data {
+                    // partitions are set for each batch, but the largest one needs a record per client
+                    let max_concurrency = args.concurrency_max.unwrap_or(0).max(args.concurrency);
+                    let feeder = BenchmarkConfig::build_data_feeder(data, config, max_concurrency);
+                    variables = feeder.columns().to_vec();
+                    http_config.data(Some(feeder));
+                }
+
+                match (&config.har, &config.operations, &config.scenario) {
+                    (Some(har), None, None) => {
+                        assert!(
+                            config.target.is_empty(),
+                            "Targets are taken from the HAR file"
+                        );
+                        http_config
+                            .operations(
+                                har::read_operations(har, &variables, config.no_template)
+                                    .expect("Cannot load the HAR file"),
+                            )
+                            .operation_order(match config.har_order.as_str() {
+                                "weighted" => OperationOrder::Weighted,
+                                _ => OperationOrder::Sequential,
+                            });
+                    }
+                    (None, Some(operations), None) | (None, None, Some(operations)) => {
+                        assert!(
+                            config.target.is_empty(),
+                            "Targets are taken from the operations file"
+                        );
+                        http_config
+                            .operations(
+                                http_operations::read_operations(
+                                    operations,
+                                    &variables,
+                                    config.no_template,
+                                )
+                                .expect("Cannot load the operations file"),
+                            )
+                            .operation_order(if config.scenario.is_some() {
+                                OperationOrder::Sequential
+                            } else {
+                                OperationOrder::Weighted
+                            });
+                    }
+                    (None, None, None) => {
+                        http_config.request(
+                            HttpRequestBuilder::default()
+                                .url(config.target.clone())
+                                .method(
+                                    config.method.as_ref().unwrap_or(&"GET".to_string()).clone(),
+                                )
+                                .headers(BenchmarkConfig::parse_headers(&config.header))
+                                .body(BenchmarkConfig::generate_body(&config.body))
+                                .variables(variables)
+                                .no_template(config.no_template)
+                                .build()
+                                .expect("HttpRequestBuilder failed"),
+                        );
+                    }
+                    _ => panic!("Only one of --har, --operations or --scenario can be used"),
                 }
 
-                let http_config = HttpBenchAdapterBuilder::default()
+                let http_config = http_config
                     .config(
                         HttpClientConfigBuilder::default()
                             .ignore_cert(config.ignore_cert)
@@ -266,43 +796,306 @@ impl BenchmarkConfig {
                             .build()
                             .expect("HttpClientConfigBuilder failed"),
                     )
+                    .build()
+                    .expect("BenchmarkModeBuilder failed");
+                BenchmarkMode::Http(http_config)
+            }
+            Commands::Tcp(config) => {
+                let response_end = match (config.response_size, &config.delimiter) {
+                    (Some(_), Some(_)) => {
+                        panic!("Either --response_size or --delimiter can be specified, not both")
+                    }
+                    (Some(size), None) => TcpResponseEnd::Size(size),
+                    (None, Some(delimiter)) => {
+                        TcpResponseEnd::Delimiter(BenchmarkConfig::unescape(delimiter))
+                    }
+                    (None, None) => TcpResponseEnd::Eof,
+                };
+
+                let tcp_config = TcpBenchAdapterBuilder::default()
+                    .target(config.target.clone())
+                    .conn_reuse(config.conn_reuse)
+                    .payload(BenchmarkConfig::generate_body(&config.payload))
+                    .response_end(response_end)
+                    .build()
+                    .expect("TcpBenchAdapterBuilder failed");
+                BenchmarkMode::Tcp(tcp_config)
+            }
+            Commands::Udp(config) => {
+                let udp_config = UdpBenchAdapterBuilder::default()
+                    .target(config.target.clone())
+                    .payload(BenchmarkConfig::generate_body(&config.payload))
+                    .packet_timeout(
+                        humantime::Duration::from_str(config.packet_timeout.as_str())
+                            .expect("Illegal duration")
+                            .into(),
+                    )
+                    .build()
+                    .expect("UdpBenchAdapterBuilder failed");
+                BenchmarkMode::Udp(udp_config)
+            }
+            Commands::Grpc(config) => {
+                BenchmarkConfig::check_ignore_cert(config.ignore_cert);
+
+                let method = config.method.trim_start_matches('/');
+                let mut headers = GrpcBenchAdapter::protocol_headers();
+                headers.extend(BenchmarkConfig::parse_headers(&config.header));
+
+                let grpc_config = GrpcBenchAdapterBuilder::default()
+                    .config(
+                        HttpClientConfigBuilder::default()
+                            .ignore_cert(config.ignore_cert)
+                            .conn_reuse(config.conn_reuse)
+                            .http2_only(true)
+                            .stop_on_errors(config.error_stop.clone())
+                            .build()
+                            .expect("HttpClientConfigBuilder failed"),
+                    )
                     .request(
                         HttpRequestBuilder::default()
-                            .url(config.target.clone())
-                            .method(config.method.as_ref().unwrap_or(&"GET".to_string()).clone())
-                            .headers(
+                            .url(
                                 config
-                                    .header
+                                    .target
                                     .iter()
-                                    .map(|s| {
-                                        let mut split = s.split(':');
-                                        (
-                                            split
-                                                .next()
-                                                .expect("Header name is missing")
-                                                .to_string(),
-                                            split.map(String::from).collect::<Vec<String>>(),
-                                        )
-                                    })
+                                    .map(|t| format!("{}/{}", t.trim_end_matches('/'), method))
                                     .collect(),
                             )
-                            .body(BenchmarkConfig::generate_body(config))
+                            .method("POST".to_string())
+                            .headers(headers)
+                            .body(GrpcBenchAdapter::frame_message(
+                                &BenchmarkConfig::generate_body(&config.message),
+                            ))
+                            // binary messages are framed with their length
+                            .templated_body(false)
                             .build()
                             .expect("HttpRequestBuilder failed"),
                     )
                     .build()
-                    .expect("BenchmarkModeBuilder failed");
-                BenchmarkMode::Http(http_config)
+                    .expect("GrpcBenchAdapterBuilder failed");
+                BenchmarkMode::Grpc(grpc_config)
+            }
+            Commands::Ws(config) => {
+                BenchmarkConfig::check_ignore_cert(config.ignore_cert);
+
+                let ws_config = WsBenchAdapterBuilder::default()
+                    .target(config.target.clone())
+                    .ignore_cert(config.ignore_cert)
+                    .conn_reuse(config.conn_reuse)
+                    .headers(BenchmarkConfig::parse_headers(&config.header))
+                    .message(BenchmarkConfig::generate_body(&config.message))
+                    .text(config.text)
+                    .build()
+                    .expect("WsBenchAdapterBuilder failed");
+                BenchmarkMode::Ws(ws_config)
+            }
+            Commands::Redis(config) => {
+                let redis_config = RedisBenchAdapterBuilder::default()
+                    .target(config.target.clone())
+                    .commands(
+                        config
+                            .command
+                            .iter()
+                            .map(|c| c.split_whitespace().map(String::from).collect())
+                            .collect(),
+                    )
+                    .workload(BenchmarkConfig::build_kv_workload(
+                        &config.key_prefix,
+                        config.keyspace,
+                        &config.value_size,
+                        config.set_ratio,
+                    ))
+                    .build()
+                    .expect("RedisBenchAdapterBuilder failed");
+                BenchmarkMode::Redis(redis_config)
             }
+            Commands::Memcached(config) => {
+                let memcached_config = MemcachedBenchAdapterBuilder::default()
+                    .target(config.target.clone())
+                    .protocol(match config.protocol.as_str() {
+                        "binary" => MemcachedProtocol::Binary,
+                        _ => MemcachedProtocol::Text,
+                    })
+                    .workload(BenchmarkConfig::build_kv_workload(
+                        &config.key_prefix,
+                        config.keyspace,
+                        &config.value_size,
+                        config.set_ratio,
+                    ))
+                    .build()
+                    .expect("MemcachedBenchAdapterBuilder failed");
+                BenchmarkMode::Memcached(memcached_config)
+            }
+        }
+    }
+
+    fn build_kv_workload(
+        key_prefix: &str,
+        keyspace: usize,
+        value_size: &str,
+        set_ratio: f64,
+    ) -> KeyValueWorkload {
+        KeyValueWorkloadBuilder::default()
+            .key_prefix(key_prefix.to_string())
+            .keyspace(keyspace)
+            .value_size(BenchmarkConfig::parse_size_range(value_size))
+            .set_ratio(set_ratio)
+            .build()
+            .expect("KeyValueWorkloadBuilder failed")
+    }
+
+    fn build_assertions(config: &HttpOptions) -> ResponseAssertions {
+        let mut assertions = ResponseAssertions::default();
+        assertions.expect_status(config.expect_status.clone());
+        for text in config.expect_body.iter() {
+            assertions.body_contains(text);
+        }
+        for regex in config.expect_body_regex.iter() {
+            assertions
+                .body_matches(regex)
+                .expect("Invalid --expect_body_regex");
+        }
+        for expression in config.expect_json.iter() {
+            assertions
+                .json_equals(expression)
+                .expect("Invalid --expect_json");
+        }
+        for header in config.expect_header.iter() {
+            assertions.header_present(header);
+        }
+        if let Some(size) = config.min_body_size {
+            assertions.min_body_size(size);
         }
+        if let Some(size) = config.max_body_size {
+            assertions.max_body_size(size);
+        }
+        if let Some(ref hex) = config.expect_sha256 {
+            assertions.sha256(hex).expect("Invalid --expect_sha256");
+        }
+        assertions
+    }
+
+    fn build_data_feeder(path: &str, config: &HttpOptions, concurrency: usize) -> DataFeeder {
+        let strategy = match config.data_strategy.as_str() {
+            "random" => FeedStrategy::Random,
+            "partitioned" => FeedStrategy::Partitioned,
+            _ => FeedStrategy::Sequential,
+        };
+        let exhausted = match config.data_exhausted.as_str() {
+            "stop" => FeedExhausted::Stop,
+            _ => FeedExhausted::Wrap,
+        };
+        DataFeeder::from_file(path, strategy, exhausted, concurrency)
+            .expect("Cannot load the data file")
     }
 
-    fn generate_body(args: &HttpOptions) -> Vec<u8> {
+    /// Values of headers, which may contain credentials (e.g. `Authorization`), are replaced,
+    /// as the command line is shared in reports.
+    fn redact_command_line(command_line: &[String]) -> String {
+        const SENSITIVE: [&str; 7] = [
+            "auth", "cookie", "token", "secret", "key", "password", "session",
+        ];
+        let redact = |header: &str| match header.split_once(':') {
+            Some((name, _))
+                if SENSITIVE
+                    .iter()
+                    .any(|s| name.to_ascii_lowercase().contains(s)) =>
+            {
+                format!("{}:<redacted>", name)
+            }
+            _ => header.to_string(),
+        };
+
+        let mut redacted = vec![];
+        let mut is_header = false;
+        for arg in command_line {
+            redacted.push(if is_header {
+                redact(arg)
+            } else if let Some(header) = arg.strip_prefix("--header=") {
+                format!("--header={}", redact(header))
+            } else if let Some(header) = arg.strip_prefix("-H").filter(|h| !h.is_empty()) {
+                format!("-H{}", redact(header))
+            } else {
+                arg.clone()
+            });
+            is_header = arg == "-H" || arg == "--header";
+        }
+        redacted.join(" ")
+    }
+
+    /// Either `N` or `MIN-MAX`.
+    fn parse_size_range(value: &str) -> (usize, usize) {
+        let parse = |v: &str| {
+            v.trim()
+                .parse::<usize>()
+                .unwrap_or_else(|_| panic!("Illegal size: {}", value))
+        };
+        match value.split_once('-') {
+            Some((min, max)) => {
+                let (min, max) = (parse(min), parse(max));
+                assert!(min <= max, "Illegal size range: {}", value);
+                (min, max)
+            }
+            None => {
+                let size = parse(value);
+                (size, size)
+            }
+        }
+    }
+
+    fn check_ignore_cert(ignore_cert: bool) {
+        #[cfg(feature = "tls-boring")]
+        if ignore_cert {
+            use std::process::exit;
+
+            println!("--ignore_cert is not supported for BoringSSL");
+            exit(-1);
+        }
+        #[cfg(not(feature = "tls-boring"))]
+        let _ = ignore_cert;
+    }
+
+    fn parse_headers(headers: &[String]) -> Vec<(String, Vec<String>)> {
+        headers
+            .iter()
+            .map(|s| {
+                let mut split = s.split(':');
+                (
+                    split.next().expect("Header name is missing").to_string(),
+                    split.map(String::from).collect::<Vec<String>>(),
+                )
+            })
+            .collect()
+    }
+
+    /// Supports `\n`, `\r`, `\t`, `\0` and `\\` escape sequences.
+    fn unescape(value: &str) -> Vec<u8> {
+        let mut result = vec![];
+        let mut chars = value.chars();
+        while let Some(c) = chars.next() {
+            let c = if c == '\\' {
+                match chars.next() {
+                    Some('n') => '\n',
+                    Some('r') => '\r',
+                    Some('t') => '\t',
+                    Some('0') => '\0',
+                    Some('\\') => '\\',
+                    other => panic!("Unsupported escape sequence: \\{:?}", other),
+                }
+            } else {
+                c
+            };
+            let mut buf = [0; 4];
+            result.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+        }
+        result
+    }
+
+    fn generate_body(body: &Option<String>) -> Vec<u8> {
         const RANDOM_PREFIX: &str = "random://";
         const BASE64_PREFIX: &str = "base64://";
         const FILE_PREFIX: &str = "file://";
 
-        if let Some(body_value) = &args.body {
+        if let Some(body_value) = body {
             if let Some(body_size) = body_value.strip_prefix(RANDOM_PREFIX) {
                 BenchmarkConfig::generate_random_vec(body_size)
             } else if let Some(base64) = body_value.strip_prefix(BASE64_PREFIX) {
@@ -327,12 +1120,11 @@ impl BenchmarkConfig {
     }
 
     fn read_file_as_vec(filename: &str) -> Vec<u8> {
-        let mut f = File::open(&filename).expect("File not found");
-        let metadata = fs::metadata(&filename).expect("Cannot get metadata");
+        let mut f = File::open(filename).expect("File not found");
+        let metadata = fs::metadata(filename).expect("Cannot get metadata");
         let mut buffer = vec![0; metadata.len() as usize];
         f.read_exact(&mut buffer)
-            .map_err(|e| panic!("Error reading file {}: {}", filename, e))
-            .unwrap();
+            .unwrap_or_else(|e| panic!("Error reading file {}: {}", filename, e));
 
         buffer
     }
@@ -343,6 +1135,15 @@ impl BenchmarkConfig {
             .rate_ladder(self.rate_ladder.clone())
             .mode(Arc::new(self.mode.clone()))
             .request_timeout(self.request_timeout)
+            .max_in_flight(self.max_in_flight)
+            .arrival(self.arrival)
+            .load_profile(self.load_profile.clone())
+            .rate_search(self.rate_search.clone())
+            .concurrency_ladder(self.concurrency_ladder.clone())
+            .warmup(self.warmup)
+            .warmup_each_step(self.warmup_each_step)
+            .report_interval(self.report_interval)
+            .cumulative_reports(self.cumulative_reports)
             .build()
             .expect("BenchSessionBuilder failed")
     }
@@ -352,18 +1153,200 @@ impl fmt::Display for BenchmarkConfig {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
-            "Mode={}, RateLadder={:?}, Concurrency={}",
-            self.mode, self.rate_ladder, self.concurrency
+            "Mode={}, RateLadder={:?}, Arrival={}, Concurrency={}",
+            self.mode, self.rate_ladder, self.arrival, self.concurrency
         )
     }
 }
 
+impl BenchmarkConfigBuilder {
+    /// Requests of the open model are bound to their data record when issued, but a step of
+    /// a scenario can't be issued until the previous one has responded with the values it extracts,
+    /// which the schedule of the open model doesn't wait for.
+    fn validate(&self) -> Result<(), String> {
+        let open_model = self.max_in_flight.as_ref().is_some_and(Option::is_some);
+        let sequential =
+            matches!(&self.mode, Some(BenchmarkMode::Http(mode)) if mode.is_scenario());
+        if open_model && sequential {
+            return Err("The open model cannot be used with a scenario".to_string());
+        }
+        Ok(())
+    }
+}
+
+impl BenchmarkMode {
+    /// A copy for the next batch of `concurrency` clients, so state shared by them (e.g. data positions)
+    /// starts over.
+    pub fn for_batch(&self, concurrency: usize) -> BenchmarkMode {
+        match self {
+            BenchmarkMode::Http(mode) => BenchmarkMode::Http(mode.for_batch(concurrency)),
+            mode => mode.clone(),
+        }
+    }
+}
+
 impl fmt::Display for BenchmarkMode {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             BenchmarkMode::Http(mode) => {
                 writeln!(f, "{}", mode)
             }
+            BenchmarkMode::Tcp(mode) => {
+                writeln!(f, "{}", mode)
+            }
+            BenchmarkMode::Udp(mode) => {
+                writeln!(f, "{}", mode)
+            }
+            BenchmarkMode::Grpc(mode) => {
+                writeln!(f, "{}", mode)
+            }
+            BenchmarkMode::Ws(mode) => {
+                writeln!(f, "{}", mode)
+            }
+            BenchmarkMode::Redis(mode) => {
+                writeln!(f, "{}", mode)
+            }
+            BenchmarkMode::Memcached(mode) => {
+                writeln!(f, "{}", mode)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bench_session::RateLadderBuilder;
+    use crate::configuration::{BenchmarkConfig, BenchmarkConfigBuilder, BenchmarkMode, Cli};
+    use crate::extractor::Extractor;
+    use crate::http_bench_session::{
+        HttpBenchAdapterBuilder, HttpClientConfigBuilder, HttpOperationBuilder, HttpRequestBuilder,
+        OperationOrder,
+    };
+    use clap::Parser;
+    use std::time::Duration;
+
+    #[test]
+    fn test_open_model_with_scenario() {
+        for (order, max_in_flight, is_valid) in [
+            (OperationOrder::Sequential, Some(10), false),
+            (OperationOrder::Sequential, None, true),
+            (OperationOrder::Weighted, Some(10), true),
+        ] {
+            let operation = HttpOperationBuilder::default()
+                .name("login".to_string())
+                .extractors(vec![Extractor::json_path("token", "$.token").unwrap()])
+                .request(
+                    HttpRequestBuilder::default()
+                        .url(vec!["http://localhost/login".to_string()])
+                        .build()
+                        .unwrap(),
+                )
+                .build()
+                .unwrap();
+            let mode = HttpBenchAdapterBuilder::default()
+                .config(HttpClientConfigBuilder::default().build().unwrap())
+                .operations(vec![operation])
+                .operation_order(order)
+                .build()
+                .unwrap();
+
+            let config = BenchmarkConfigBuilder::default()
+                .rate_ladder(
+                    RateLadderBuilder::default()
+                        .start(0.)
+                        .end(0.)
+                        .rate_increment(None)
+                        .step_duration(None)
+                        .step_requests(Some(1))
+                        .build()
+                        .unwrap(),
+                )
+                .request_timeout(None)
+                .max_in_flight(max_in_flight)
+                .mode(BenchmarkMode::Http(mode))
+                .build();
+
+            assert_eq!(is_valid, config.is_ok(), "{:?} {:?}", order, max_in_flight);
+        }
+    }
+
+    #[test]
+    fn test_hdr_options() {
+        let parse = |args: &[&str]| {
+            Cli::try_parse_from(
+                ["perf-gauge", "-n", "1", "--hdr"]
+                    .iter()
+                    .chain(args)
+                    .chain(&["http", "http://localhost"]),
+            )
+        };
+        let cli = parse(&["--hdr_digits", "5", "--hdr_max", "10m"]).unwrap();
+        assert_eq!(Some(5), cli.hdr_digits);
+        assert_eq!(Some(Duration::from_secs(600)), cli.hdr_max);
+
+        for args in [
+            ["--hdr_digits", "0"],
+            ["--hdr_digits", "6"],
+            ["--hdr_max", "0s"],
+            ["--hdr_max", "500ns"],
+            ["--hdr_max", "1000years"],
+            ["--hdr_max", "1x"],
+        ] {
+            assert!(parse(&args).is_err(), "{:?}", args);
         }
+        assert!(parse(&["--hdr_max", "1us"]).is_ok());
+    }
+
+    #[test]
+    fn test_no_template() {
+        let parse = |args: &[&str]| {
+            Cli::try_parse_from(
+                ["perf-gauge", "-n", "1", "http", "http://localhost/{{id}}"]
+                    .iter()
+                    .chain(args),
+            )
+        };
+        // a literal `{{` would be an unknown placeholder otherwise
+        let cli = parse(&[
+            "--no_template",
+            "-B",
+            "base64://eyJxdWVyeSI6Int7dXNlcn19In0=",
+        ])
+        .unwrap();
+        assert!(matches!(
+            BenchmarkConfig::build_mode(&cli),
+            BenchmarkMode::Http(_)
+        ));
+
+        assert!(parse(&["--no_template", "--data", "data.csv"]).is_err());
+        assert!(parse(&["--no_template", "--scenario", "scenario.yaml"]).is_err());
+    }
+
+    #[test]
+    fn test_redact_command_line() {
+        let command_line: Vec<String> = [
+            "perf-gauge",
+            "--rate",
+            "10",
+            "http",
+            "http://localhost",
+            "-H",
+            "Authorization:Bearer token",
+            "--header",
+            "Accept:text/html",
+            "--header=cookie:session=1",
+            "-HX-Api-Key:42",
+            "-H",
+            "Proxy-Authorization:Basic a",
+        ]
+        .iter()
+        .map(|a| a.to_string())
+        .collect();
+        assert_eq!(
+            "perf-gauge --rate 10 http http://localhost -H Authorization:<redacted> \
+            --header Accept:text/html --header=cookie:<redacted> -HX-Api-Key:<redacted> \
+            -H Proxy-Authorization:<redacted>",
+            BenchmarkConfig::redact_command_line(&command_line)
+        );
     }
 }
@@ -0,0 +1,131 @@
+/// Copyright 2020 Developers of the perf-gauge project.
+///
+/// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+/// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+/// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+/// option. This file may not be copied, modified, or distributed
+/// except according to those terms.
+use crate::metrics::{BenchRunMetrics, BenchRunReport, ExternalMetricsServiceReporter};
+use log::info;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+/// Appends a row per batch to a CSV file, so results of several sessions can be collected
+/// in the same file. The header is written if the file is empty. Latencies are in microseconds.
+pub struct CsvReporter {
+    path: String,
+    test_case_name: Option<String>,
+    file: Mutex<File>,
+}
+
+impl CsvReporter {
+    pub fn new(path: &str, test_case_name: Option<String>) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            path: path.to_string(),
+            test_case_name,
+            file: Mutex::new(file),
+        })
+    }
+}
+
+impl ExternalMetricsServiceReporter for CsvReporter {
+    fn report(&self, metrics: &BenchRunMetrics) -> io::Result<()> {
+        let report = BenchRunReport::new(self.test_case_name.clone(), metrics);
+        let combined = &report.combined;
+
+        let mut header = vec![
+            "timestamp".to_string(),
+            "test_case_name".to_string(),
+            "concurrency".to_string(),
+            "duration_secs".to_string(),
+            "total_requests".to_string(),
+            "success_rate".to_string(),
+            "rate_per_second".to_string(),
+            "total_bytes".to_string(),
+            "bitrate_mbps".to_string(),
+        ];
+        let mut row = vec![
+            humantime::format_rfc3339_seconds(SystemTime::now()).to_string(),
+            combined.test_case_name.clone().unwrap_or_default(),
+            report.concurrency.to_string(),
+            format!("{:.3}", combined.duration.as_secs_f64()),
+            combined.total_requests.to_string(),
+            format!("{:.3}", combined.success_rate),
+            format!("{:.3}", combined.rate_per_second),
+            combined.total_bytes.to_string(),
+            format!("{:.3}", combined.bitrate_mbps),
+        ];
+        for (label, value) in combined.latency_summary.iter() {
+            header.push(format!("latency_{}_us", label.to_lowercase()));
+            row.push(value.to_string());
+        }
+
+        info!("Appending results to {}", self.path);
+        let mut file = self.file.lock().expect("Poisoned CSV report");
+        let is_empty = file.metadata()?.len() == 0;
+        let mut writer = csv::Writer::from_writer(&mut *file);
+        if is_empty {
+            writer.write_record(&header)?;
+        }
+        writer.write_record(&row)?;
+        writer.flush()
+    }
+
+    fn reset_metrics(&self) {
+        // do nothing
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::csv_reporter::CsvReporter;
+    use crate::latency_histogram::HistogramBackend;
+    use crate::metrics::{BenchRunMetrics, ExternalMetricsServiceReporter, RequestStatsBuilder};
+    use std::time::Duration;
+
+    #[test]
+    fn test_csv_report() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("report.csv");
+        let path = path.to_str().unwrap();
+
+        let mut metrics = BenchRunMetrics::new(HistogramBackend::Simple);
+        metrics.concurrency = 4;
+        metrics.report_request(
+            RequestStatsBuilder::default()
+                .is_success(true)
+                .bytes_processed(100)
+                .status("200 OK".to_string())
+                .duration(Duration::from_micros(150))
+                .fatal_error(false)
+                .build()
+                .unwrap(),
+        );
+
+        // appends to the existing file on the next session
+        for _ in 0..2 {
+            let reporter = CsvReporter::new(path, Some("a, b".to_string())).unwrap();
+            assert!(!reporter.reports_snapshots());
+            reporter.report(&metrics).unwrap();
+        }
+
+        let mut reader = csv::Reader::from_path(path).unwrap();
+        let header = reader.headers().unwrap().clone();
+        let rows: Vec<csv::StringRecord> = reader.records().map(|r| r.unwrap()).collect();
+
+        assert_eq!(Some("timestamp"), header.get(0));
+        assert_eq!(Some("latency_p99.9_us"), header.get(13));
+        assert_eq!(2, rows.len());
+        for row in rows {
+            assert_eq!(header.len(), row.len());
+            assert_eq!(Some("a, b"), row.get(1));
+            assert_eq!(Some("4"), row.get(2));
+            assert_eq!(Some("1"), row.get(4));
+            assert_eq!(Some("100.000"), row.get(5));
+            assert_eq!(Some("150"), row.get(10));
+        }
+    }
+}
@@ -0,0 +1,346 @@
+/// Copyright 2020 Developers of the perf-gauge project.
+///
+/// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+/// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+/// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+/// option. This file may not be copied, modified, or distributed
+/// except according to those terms.
+use crate::template::Variables;
+use rand::{thread_rng, Rng};
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::ops::Range;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// The order, in which clients take records.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FeedStrategy {
+    /// All clients take records one by one from the same sequence.
+    Sequential,
+    /// Each request takes a random record. Never exhausted.
+    Random,
+    /// Each client takes records one by one from its own partition of the records.
+    Partitioned,
+}
+
+/// What happens when all records are taken.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FeedExhausted {
+    /// Clients stop sending requests.
+    Stop,
+    /// Start over from the first record.
+    Wrap,
+}
+
+/// Records of a CSV (with a header) or a JSONL file, which are bound to request templates.
+#[derive(Clone)]
+pub struct DataFeeder {
+    records: Arc<Vec<Variables>>,
+    columns: Vec<String>,
+    strategy: FeedStrategy,
+    exhausted: FeedExhausted,
+    /// The number of partitions for `FeedStrategy::Partitioned`, i.e. the concurrency of the batch.
+    partitions: usize,
+    next_partition: Arc<AtomicUsize>,
+    /// Shared by all clients of a batch for `FeedStrategy::Sequential`.
+    position: Arc<AtomicUsize>,
+}
+
+/// Per-client position in the records.
+pub struct DataCursor {
+    range: Range<usize>,
+    position: AtomicUsize,
+    current: AtomicUsize,
+}
+
+impl DataFeeder {
+    /// JSONL files are recognized by the `.jsonl` or `.ndjson` extension, otherwise it's CSV.
+    pub fn from_file(
+        path: &str,
+        strategy: FeedStrategy,
+        exhausted: FeedExhausted,
+        partitions: usize,
+    ) -> Result<DataFeeder, String> {
+        let file = File::open(path).map_err(|e| format!("Cannot open {}: {}", path, e))?;
+        let records = if path.ends_with(".jsonl") || path.ends_with(".ndjson") {
+            DataFeeder::read_jsonl(file)?
+        } else {
+            DataFeeder::read_csv(file)?
+        };
+        DataFeeder::new(records, strategy, exhausted, partitions)
+    }
+
+    pub fn new(
+        records: Vec<Variables>,
+        strategy: FeedStrategy,
+        exhausted: FeedExhausted,
+        partitions: usize,
+    ) -> Result<DataFeeder, String> {
+        if records.is_empty() {
+            return Err("No data records".to_string());
+        }
+        // otherwise, some clients would get no records and send nothing
+        if strategy == FeedStrategy::Partitioned && records.len() < partitions {
+            return Err(format!(
+                "{} data records cannot be partitioned between {} clients",
+                records.len(),
+                partitions
+            ));
+        }
+
+        let columns: BTreeSet<&String> = records.iter().flat_map(|r| r.keys()).collect();
+        let columns = columns.into_iter().cloned().collect();
+
+        Ok(DataFeeder {
+            records: Arc::new(records),
+            columns,
+            strategy,
+            exhausted,
+            partitions: partitions.max(1),
+            next_partition: Arc::new(AtomicUsize::new(0)),
+            position: Arc::new(AtomicUsize::new(0)),
+        })
+    }
+
+    fn read_csv(input: impl Read) -> Result<Vec<Variables>, String> {
+        let mut reader = csv::Reader::from_reader(input);
+        let headers = reader.headers().map_err(|e| e.to_string())?.clone();
+        reader
+            .records()
+            .map(|record| {
+                let record = record.map_err(|e| e.to_string())?;
+                Ok(headers
+                    .iter()
+                    .zip(record.iter())
+                    .map(|(k, v)| (k.to_string(), v.to_string()))
+                    .collect())
+            })
+            .collect()
+    }
+
+    /// Each line is a JSON object. Values other than strings are bound as JSON.
+    fn read_jsonl(input: impl Read) -> Result<Vec<Variables>, String> {
+        BufReader::new(input)
+            .lines()
+            .filter(|line| line.as_ref().map_or(true, |l| !l.trim().is_empty()))
+            .map(|line| {
+                let line = line.map_err(|e| e.to_string())?;
+                let object: serde_json::Map<String, serde_json::Value> =
+                    serde_json::from_str(&line).map_err(|e| e.to_string())?;
+                Ok(object
+                    .into_iter()
+                    .map(|(k, v)| match v {
+                        serde_json::Value::String(s) => (k, s),
+                        other => (k, other.to_string()),
+                    })
+                    .collect())
+            })
+            .collect()
+    }
+
+    /// Names, which can be used in templates.
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+
+    /// A copy for the clients of the next batch, which take records from the start again,
+    /// split into `partitions` for its concurrency.
+    pub fn for_batch(&self, partitions: usize) -> DataFeeder {
+        DataFeeder {
+            partitions: partitions.max(1),
+            next_partition: Arc::new(AtomicUsize::new(0)),
+            position: Arc::new(AtomicUsize::new(0)),
+            ..self.clone()
+        }
+    }
+
+    /// Partitions are assigned to clients in a round-robin fashion.
+    pub fn new_cursor(&self) -> DataCursor {
+        let len = self.records.len();
+        let range = match self.strategy {
+            FeedStrategy::Partitioned => {
+                let partition =
+                    self.next_partition.fetch_add(1, Ordering::Relaxed) % self.partitions;
+                len * partition / self.partitions..len * (partition + 1) / self.partitions
+            }
+            _ => 0..len,
+        };
+        DataCursor {
+            range,
+            position: AtomicUsize::new(0),
+            current: AtomicUsize::new(0),
+        }
+    }
+
+    /// Takes the next record for the client. Returns `false` if the records are exhausted.
+    pub fn advance(&self, cursor: &DataCursor) -> bool {
+        let len = cursor.range.len();
+        if len == 0 {
+            return false;
+        }
+
+        let position = match self.strategy {
+            FeedStrategy::Random => thread_rng().gen_range(0..len),
+            FeedStrategy::Sequential => self.position.fetch_add(1, Ordering::Relaxed),
+            FeedStrategy::Partitioned => cursor.position.fetch_add(1, Ordering::Relaxed),
+        };
+        if position >= len && self.exhausted == FeedExhausted::Stop {
+            return false;
+        }
+
+        cursor
+            .current
+            .store(cursor.range.start + position % len, Ordering::Relaxed);
+        true
+    }
+
+    /// The index of the record taken by the last `advance`.
+    pub fn current(&self, cursor: &DataCursor) -> usize {
+        cursor.current.load(Ordering::Relaxed)
+    }
+
+    pub fn record(&self, index: usize) -> &Variables {
+        &self.records[index]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data_feeder::{DataFeeder, FeedExhausted, FeedStrategy};
+    use crate::template::Variables;
+
+    fn records(n: usize) -> Vec<Variables> {
+        (0..n)
+            .map(|i| {
+                vec![("id".to_string(), i.to_string())]
+                    .into_iter()
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn take(feeder: &DataFeeder, cursor: &crate::data_feeder::DataCursor, n: usize) -> Vec<String> {
+        (0..n)
+            .map_while(|_| {
+                if feeder.advance(cursor) {
+                    Some(feeder.record(feeder.current(cursor))["id"].clone())
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_read_csv() {
+        let records =
+            DataFeeder::read_csv("user,token\nalice,t1\n\"bob, jr\",t2\n".as_bytes()).unwrap();
+        assert_eq!(2, records.len());
+        assert_eq!("alice", records[0]["user"]);
+        assert_eq!("bob, jr", records[1]["user"]);
+        assert_eq!("t2", records[1]["token"]);
+    }
+
+    #[test]
+    fn test_read_jsonl() {
+        let records = DataFeeder::read_jsonl(
+            "{\"user\": \"alice\", \"payload\": {\"a\": 1}}\n\n{\"user\": \"bob\", \"age\": 42}\n"
+                .as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(2, records.len());
+        assert_eq!("{\"a\":1}", records[0]["payload"]);
+        assert_eq!("42", records[1]["age"]);
+
+        let feeder =
+            DataFeeder::new(records, FeedStrategy::Sequential, FeedExhausted::Wrap, 1).unwrap();
+        assert_eq!(vec!["age", "payload", "user"], feeder.columns());
+
+        assert!(DataFeeder::read_jsonl("[1, 2]".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_sequential() {
+        let feeder =
+            DataFeeder::new(records(3), FeedStrategy::Sequential, FeedExhausted::Stop, 2).unwrap();
+        let (c1, c2) = (feeder.new_cursor(), feeder.new_cursor());
+        assert_eq!(vec!["0"], take(&feeder, &c1, 1));
+        assert_eq!(vec!["1", "2"], take(&feeder, &c2, 5));
+        assert!(take(&feeder, &c1, 1).is_empty());
+
+        let feeder =
+            DataFeeder::new(records(3), FeedStrategy::Sequential, FeedExhausted::Wrap, 1).unwrap();
+        let cursor = feeder.new_cursor();
+        assert_eq!(vec!["0", "1", "2", "0", "1"], take(&feeder, &cursor, 5));
+    }
+
+    #[test]
+    fn test_partitioned() {
+        let feeder = DataFeeder::new(
+            records(5),
+            FeedStrategy::Partitioned,
+            FeedExhausted::Stop,
+            2,
+        )
+        .unwrap();
+        let (c1, c2) = (feeder.new_cursor(), feeder.new_cursor());
+        assert_eq!(vec!["0", "1"], take(&feeder, &c1, 5));
+        assert_eq!(vec!["2", "3", "4"], take(&feeder, &c2, 5));
+
+        let feeder = DataFeeder::new(
+            records(4),
+            FeedStrategy::Partitioned,
+            FeedExhausted::Wrap,
+            2,
+        )
+        .unwrap();
+        let (_, c2) = (feeder.new_cursor(), feeder.new_cursor());
+        assert_eq!(vec!["2", "3", "2"], take(&feeder, &c2, 3));
+
+        for exhausted in [FeedExhausted::Stop, FeedExhausted::Wrap] {
+            assert!(DataFeeder::new(records(1), FeedStrategy::Partitioned, exhausted, 2).is_err());
+        }
+    }
+
+    #[test]
+    fn test_batches() {
+        for strategy in [FeedStrategy::Sequential, FeedStrategy::Partitioned] {
+            let feeder = DataFeeder::new(records(4), strategy, FeedExhausted::Stop, 2).unwrap();
+            // e.g. a warm-up batch and the first step
+            for _ in 0..2 {
+                let batch = feeder.for_batch(2);
+                let (c1, c2) = (batch.new_cursor(), batch.new_cursor());
+                assert_eq!(vec!["0", "1"], take(&batch, &c1, 2), "{:?}", strategy);
+                assert_eq!(vec!["2", "3"], take(&batch, &c2, 5), "{:?}", strategy);
+                assert!(take(&batch, &c1, 1).is_empty());
+            }
+        }
+
+        // e.g. the next step of the concurrency ladder
+        let feeder = DataFeeder::new(
+            records(4),
+            FeedStrategy::Partitioned,
+            FeedExhausted::Stop,
+            2,
+        )
+        .unwrap();
+        let batch = feeder.for_batch(4);
+        for id in ["0", "1", "2", "3"] {
+            assert_eq!(vec![id], take(&batch, &batch.new_cursor(), 5));
+        }
+    }
+
+    #[test]
+    fn test_random() {
+        let feeder =
+            DataFeeder::new(records(3), FeedStrategy::Random, FeedExhausted::Stop, 1).unwrap();
+        let cursor = feeder.new_cursor();
+        let taken = take(&feeder, &cursor, 100);
+        assert_eq!(100, taken.len());
+        assert!(taken
+            .iter()
+            .all(|id| ["0", "1", "2"].contains(&id.as_str())));
+    }
+}
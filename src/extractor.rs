@@ -0,0 +1,243 @@
+/// Copyright 2020 Developers of the perf-gauge project.
+///
+/// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+/// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+/// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+/// option. This file may not be copied, modified, or distributed
+/// except according to those terms.
+use core::fmt;
+use hyper::HeaderMap;
+use regex::Regex;
+
+/// Status of successful HTTP responses, which don't contain a value to extract.
+pub const EXTRACTION_FAILED_STATUS: &str = "Extraction failed";
+
+/// Extracts a value from a response into a per-connection variable,
+/// so the following requests can use it as a `{{name}}` placeholder.
+#[derive(Clone, Debug)]
+pub struct Extractor {
+    name: String,
+    source: Source,
+}
+
+#[derive(Clone, Debug)]
+enum Source {
+    JsonPath(JsonPath),
+    /// The first capture group (or the whole match) in the body.
+    Regex(Regex),
+    /// The first value of the header, optionally matched by a regex (e.g. for `Set-Cookie`).
+    Header(String, Option<Regex>),
+}
+
+/// A subset of JSONPath: `$.a.b`, `$['a'].b`, `$.items[0].id`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct JsonPath(Vec<PathSegment>);
+
+#[derive(Clone, Debug, PartialEq)]
+enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
+impl Extractor {
+    pub fn json_path(name: &str, path: &str) -> Result<Extractor, String> {
+        Ok(Extractor {
+            name: name.to_string(),
+            source: Source::JsonPath(JsonPath::parse(path)?),
+        })
+    }
+
+    pub fn regex(name: &str, pattern: &str) -> Result<Extractor, String> {
+        Ok(Extractor {
+            name: name.to_string(),
+            source: Source::Regex(Regex::new(pattern).map_err(|e| e.to_string())?),
+        })
+    }
+
+    pub fn header(name: &str, header: &str, pattern: Option<&str>) -> Result<Extractor, String> {
+        let regex = pattern
+            .map(|p| Regex::new(p).map_err(|e| e.to_string()))
+            .transpose()?;
+        Ok(Extractor {
+            name: name.to_string(),
+            source: Source::Header(header.to_ascii_lowercase(), regex),
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// `None` if the response doesn't contain the value.
+    pub fn extract(&self, headers: &HeaderMap, body: &[u8]) -> Option<String> {
+        match &self.source {
+            Source::JsonPath(path) => path.find(body),
+            Source::Regex(regex) => Extractor::find(regex, &String::from_utf8_lossy(body)),
+            Source::Header(header, regex) => headers
+                .get_all(header.as_str())
+                .iter()
+                .filter_map(|v| v.to_str().ok())
+                .find_map(|v| match regex {
+                    Some(regex) => Extractor::find(regex, v),
+                    None => Some(v.to_string()),
+                }),
+        }
+    }
+
+    fn find(regex: &Regex, input: &str) -> Option<String> {
+        let captures = regex.captures(input)?;
+        captures
+            .get(1)
+            .or_else(|| captures.get(0))
+            .map(|m| m.as_str().to_string())
+    }
+}
+
+impl JsonPath {
+    pub fn parse(path: &str) -> Result<JsonPath, String> {
+        let illegal = || format!("Unsupported JSONPath: {}", path);
+        let mut rest = path.strip_prefix('$').ok_or_else(illegal)?;
+        let mut segments = vec![];
+        while !rest.is_empty() {
+            if let Some(tail) = rest.strip_prefix('.') {
+                let end = tail.find(['.', '[']).unwrap_or(tail.len());
+                if end == 0 {
+                    return Err(illegal());
+                }
+                segments.push(PathSegment::Key(tail[..end].to_string()));
+                rest = &tail[end..];
+            } else if let Some(tail) = rest.strip_prefix('[') {
+                let end = tail.find(']').ok_or_else(illegal)?;
+                let selector = &tail[..end];
+                let quoted = selector
+                    .strip_prefix('\'')
+                    .and_then(|s| s.strip_suffix('\''))
+                    .or_else(|| selector.strip_prefix('"').and_then(|s| s.strip_suffix('"')));
+                segments.push(match quoted {
+                    Some(key) => PathSegment::Key(key.to_string()),
+                    None => PathSegment::Index(selector.parse().map_err(|_| illegal())?),
+                });
+                rest = &tail[end + 1..];
+            } else {
+                return Err(illegal());
+            }
+        }
+        Ok(JsonPath(segments))
+    }
+
+    /// The value in the JSON body. Strings are returned as is, other values as JSON.
+    pub fn find(&self, body: &[u8]) -> Option<String> {
+        let mut value: &serde_json::Value = &serde_json::from_slice(body).ok()?;
+        for segment in self.0.iter() {
+            value = match segment {
+                PathSegment::Key(key) => value.get(key)?,
+                PathSegment::Index(idx) => value.get(*idx)?,
+            };
+        }
+        match value {
+            serde_json::Value::String(s) => Some(s.to_string()),
+            other => Some(other.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for JsonPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "$")?;
+        for segment in self.0.iter() {
+            match segment {
+                PathSegment::Key(key) if key.chars().all(|c| c.is_alphanumeric() || c == '_') => {
+                    write!(f, ".{}", key)?
+                }
+                PathSegment::Key(key) => write!(f, "['{}']", key)?,
+                PathSegment::Index(idx) => write!(f, "[{}]", idx)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::extractor::{Extractor, JsonPath, PathSegment};
+    use hyper::header::{HeaderValue, SET_COOKIE};
+    use hyper::HeaderMap;
+
+    #[test]
+    fn test_parse_json_path() {
+        assert_eq!(Ok(JsonPath(vec![])), JsonPath::parse("$"));
+        let path = JsonPath::parse("$.data['items'][1][\"a.b\"]").unwrap();
+        assert_eq!(
+            JsonPath(vec![
+                PathSegment::Key("data".to_string()),
+                PathSegment::Key("items".to_string()),
+                PathSegment::Index(1),
+                PathSegment::Key("a.b".to_string()),
+            ]),
+            path
+        );
+        assert_eq!("$.data.items[1]['a.b']", path.to_string());
+        assert!(JsonPath::parse("data").is_err());
+        assert!(JsonPath::parse("$..a").is_err());
+        assert!(JsonPath::parse("$.a[*]").is_err());
+        assert!(JsonPath::parse("$.a[0").is_err());
+    }
+
+    #[test]
+    fn test_extract_json() {
+        let body = br#"{"token": "abc", "user": {"id": 42, "roles": ["admin", "dev"]}}"#;
+        let headers = HeaderMap::new();
+        let extract = |path: &str| {
+            Extractor::json_path("v", path)
+                .unwrap()
+                .extract(&headers, body)
+        };
+        assert_eq!(Some("abc".to_string()), extract("$.token"));
+        assert_eq!(Some("42".to_string()), extract("$.user.id"));
+        assert_eq!(Some("dev".to_string()), extract("$.user.roles[1]"));
+        assert_eq!(None, extract("$.user.roles[2]"));
+        assert_eq!(None, extract("$.missing"));
+        assert_eq!(
+            None,
+            Extractor::json_path("v", "$.a")
+                .unwrap()
+                .extract(&headers, b"not json")
+        );
+    }
+
+    #[test]
+    fn test_extract_regex_and_header() {
+        let mut headers = HeaderMap::new();
+        headers.append(SET_COOKIE, HeaderValue::from_static("theme=dark; Path=/"));
+        headers.append(SET_COOKIE, HeaderValue::from_static("session=s1; HttpOnly"));
+
+        let session = Extractor::header("session", "Set-Cookie", Some("session=([^;]+)")).unwrap();
+        assert_eq!("session", session.name());
+        assert_eq!(Some("s1".to_string()), session.extract(&headers, b""));
+        assert_eq!(
+            Some("theme=dark; Path=/".to_string()),
+            Extractor::header("c", "set-cookie", None)
+                .unwrap()
+                .extract(&headers, b"")
+        );
+        assert_eq!(
+            None,
+            Extractor::header("c", "x-missing", None)
+                .unwrap()
+                .extract(&headers, b"")
+        );
+
+        let csrf = Extractor::regex("csrf", r#"name="csrf" value="([^"]+)""#).unwrap();
+        assert_eq!(
+            Some("t0k".to_string()),
+            csrf.extract(&headers, br#"<input name="csrf" value="t0k">"#)
+        );
+        assert_eq!(
+            Some("42".to_string()),
+            Extractor::regex("n", "[0-9]+")
+                .unwrap()
+                .extract(&headers, b"id=42")
+        );
+        assert!(Extractor::regex("n", "(").is_err());
+    }
+}
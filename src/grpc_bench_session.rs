@@ -0,0 +1,267 @@
+use crate::bench_run::BenchmarkProtocolAdapter;
+/// Copyright 2020 Developers of the perf-gauge project.
+///
+/// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+/// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+/// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+/// option. This file may not be copied, modified, or distributed
+/// except according to those terms.
+use crate::http_bench_session::{HttpClient, HttpClientConfig, HttpRequest};
+use crate::metrics::{RequestStats, RequestStatsBuilder};
+use async_trait::async_trait;
+use core::fmt;
+use hyper::body::HttpBody;
+use hyper::{HeaderMap, StatusCode};
+use log::error;
+use std::time::Instant;
+
+const GRPC_STATUS_HEADER: &str = "grpc-status";
+
+/// gRPC calls over the HTTP/2 client of the `http` mode.
+/// The request must already be a gRPC request, see `GrpcBenchAdapter::frame_message`.
+#[derive(Builder, Deserialize, Clone)]
+pub struct GrpcBenchAdapter {
+    config: HttpClientConfig,
+    request: HttpRequest,
+}
+
+impl GrpcBenchAdapter {
+    /// Length-prefixed message: compression flag, message length (big-endian u32) and the message.
+    pub fn frame_message(message: &[u8]) -> Vec<u8> {
+        let mut framed = Vec::with_capacity(message.len() + 5);
+        framed.push(0);
+        framed.extend_from_slice(&(message.len() as u32).to_be_bytes());
+        framed.extend_from_slice(message);
+        framed
+    }
+
+    /// Headers required by the gRPC over HTTP/2 protocol.
+    pub fn protocol_headers() -> Vec<(String, Vec<String>)> {
+        vec![
+            (
+                "content-type".to_string(),
+                vec!["application/grpc".to_string()],
+            ),
+            ("te".to_string(), vec!["trailers".to_string()]),
+        ]
+    }
+
+    /// Servers may respond without a body, sending `grpc-status` in the headers.
+    fn grpc_status(headers: &HeaderMap, trailers: Option<&HeaderMap>) -> Option<u16> {
+        trailers
+            .and_then(|t| t.get(GRPC_STATUS_HEADER))
+            .or_else(|| headers.get(GRPC_STATUS_HEADER))
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse().ok())
+    }
+
+    fn grpc_status_name(code: u16) -> &'static str {
+        match code {
+            0 => "OK",
+            1 => "CANCELLED",
+            2 => "UNKNOWN",
+            3 => "INVALID_ARGUMENT",
+            4 => "DEADLINE_EXCEEDED",
+            5 => "NOT_FOUND",
+            6 => "ALREADY_EXISTS",
+            7 => "PERMISSION_DENIED",
+            8 => "RESOURCE_EXHAUSTED",
+            9 => "FAILED_PRECONDITION",
+            10 => "ABORTED",
+            11 => "OUT_OF_RANGE",
+            12 => "UNIMPLEMENTED",
+            13 => "INTERNAL",
+            14 => "UNAVAILABLE",
+            15 => "DATA_LOSS",
+            16 => "UNAUTHENTICATED",
+            _ => "UNRECOGNIZED",
+        }
+    }
+}
+
+#[async_trait]
+impl BenchmarkProtocolAdapter for GrpcBenchAdapter {
+    type Client = HttpClient;
+
+    fn build_client(&self) -> Result<Self::Client, String> {
+        Ok(self.config.build_client())
+    }
+
+    async fn send_request(&self, client: &Self::Client) -> RequestStats {
+        let start = Instant::now();
+        let request = self.request.build_request();
+        let response = client.request(request).await;
+
+        match response {
+            Ok(r) => {
+                let http_status = r.status();
+                let headers = r.headers().clone();
+
+                let mut body = r.into_body();
+                let mut total_size = 0;
+                while let Some(item) = body.data().await {
+                    if let Ok(bytes) = item {
+                        total_size += bytes.len();
+                    } else {
+                        break;
+                    }
+                }
+                let trailers = body.trailers().await.unwrap_or_default();
+
+                let (status, success, fatal_error) = if http_status != StatusCode::OK {
+                    (http_status.to_string(), false, false)
+                } else {
+                    match GrpcBenchAdapter::grpc_status(&headers, trailers.as_ref()) {
+                        Some(code) => (
+                            format!("{} {}", code, GrpcBenchAdapter::grpc_status_name(code)),
+                            code == 0,
+                            self.config.stop_on_errors.contains(&code),
+                        ),
+                        None => ("Missing grpc-status".to_string(), false, false),
+                    }
+                };
+
+                RequestStatsBuilder::default()
+                    .bytes_processed(total_size)
+                    .status(status)
+                    .is_success(success)
+                    .duration(Instant::now().duration_since(start))
+                    .fatal_error(fatal_error)
+                    .build()
+                    .expect("RequestStatsBuilder failed")
+            }
+            Err(e) => {
+                error!("Error sending request: {}", e);
+                RequestStatsBuilder::default()
+                    .bytes_processed(0)
+                    .status(e.to_string())
+                    .is_success(false)
+                    .duration(Instant::now().duration_since(start))
+                    .fatal_error(false)
+                    .build()
+                    .expect("RequestStatsBuilder failed")
+            }
+        }
+    }
+}
+
+impl fmt::Display for GrpcBenchAdapter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Config={:?}, Request={}", self.config, self.request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bench_run::BenchmarkProtocolAdapter;
+    use crate::grpc_bench_session::{GrpcBenchAdapter, GrpcBenchAdapterBuilder};
+    use crate::http_bench_session::{HttpClientConfigBuilder, HttpRequestBuilder};
+    use hyper::header::HeaderValue;
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, HeaderMap, Request, Response, Server};
+    use std::convert::Infallible;
+    use std::net::SocketAddr;
+
+    /// Replies to `/test.Service/Ok` with the framed request message and `grpc-status: 0`,
+    /// to any other method with `grpc-status: 5`.
+    async fn start_grpc_server() -> SocketAddr {
+        let make_service = make_service_fn(|_| async {
+            Ok::<_, Infallible>(service_fn(|request: Request<Body>| async move {
+                let code = if request.uri().path() == "/test.Service/Ok" {
+                    "0"
+                } else {
+                    "5"
+                };
+                assert_eq!(
+                    "application/grpc",
+                    request.headers().get("content-type").unwrap()
+                );
+                let message = hyper::body::to_bytes(request.into_body()).await.unwrap();
+
+                let (mut sender, body) = Body::channel();
+                tokio::spawn(async move {
+                    sender.send_data(message).await.unwrap();
+                    let mut trailers = HeaderMap::new();
+                    trailers.insert("grpc-status", HeaderValue::from_static(code));
+                    sender.send_trailers(trailers).await.unwrap();
+                });
+                Ok::<_, Infallible>(Response::new(body))
+            }))
+        });
+
+        let server = Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0)))
+            .http2_only(true)
+            .serve(make_service);
+        let addr = server.local_addr();
+        tokio::spawn(server);
+        addr
+    }
+
+    fn grpc_adapter(addr: SocketAddr, method: &str, message: &[u8]) -> GrpcBenchAdapter {
+        GrpcBenchAdapterBuilder::default()
+            .config(
+                HttpClientConfigBuilder::default()
+                    .http2_only(true)
+                    .conn_reuse(true)
+                    .stop_on_errors(vec![16])
+                    .build()
+                    .unwrap(),
+            )
+            .request(
+                HttpRequestBuilder::default()
+                    .url(vec![format!("http://{}{}", addr, method)])
+                    .method("POST".to_string())
+                    .headers(GrpcBenchAdapter::protocol_headers())
+                    .body(GrpcBenchAdapter::frame_message(message))
+                    .build()
+                    .unwrap(),
+            )
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_frame_message() {
+        assert_eq!(
+            vec![0, 0, 0, 0, 3, 1, 2, 3],
+            GrpcBenchAdapter::frame_message(&[1, 2, 3])
+        );
+        assert_eq!(vec![0, 0, 0, 0, 0], GrpcBenchAdapter::frame_message(&[]));
+    }
+
+    #[tokio::test]
+    async fn test_grpc_ok() {
+        let addr = start_grpc_server().await;
+        let grpc_bench = grpc_adapter(addr, "/test.Service/Ok", b"hello");
+
+        let client = grpc_bench.build_client().expect("Client is built");
+        let stats = grpc_bench.send_request(&client).await;
+
+        println!("{:?}", stats);
+        assert!(stats.is_success);
+        assert_eq!("0 OK".to_string(), stats.status);
+        assert_eq!(5 + 5, stats.bytes_processed);
+    }
+
+    #[tokio::test]
+    async fn test_grpc_error_status() {
+        let addr = start_grpc_server().await;
+        let grpc_bench = grpc_adapter(addr, "/test.Service/Missing", b"hello");
+
+        let client = grpc_bench.build_client().expect("Client is built");
+        let stats = grpc_bench.send_request(&client).await;
+
+        println!("{:?}", stats);
+        assert!(!stats.is_success);
+        assert!(!stats.fatal_error);
+        assert_eq!("5 NOT_FOUND".to_string(), stats.status);
+    }
+
+    #[test]
+    fn test_grpc_status_in_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert("grpc-status", HeaderValue::from_static("16"));
+        assert_eq!(Some(16), GrpcBenchAdapter::grpc_status(&headers, None));
+        assert_eq!(None, GrpcBenchAdapter::grpc_status(&HeaderMap::new(), None));
+    }
+}
@@ -0,0 +1,218 @@
+/// Copyright 2020 Developers of the perf-gauge project.
+///
+/// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+/// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+/// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+/// option. This file may not be copied, modified, or distributed
+/// except according to those terms.
+use crate::http_bench_session::{HttpOperation, HttpOperationBuilder, HttpRequestBuilder};
+use std::fs::File;
+use std::io::{BufReader, Read};
+
+/// Headers set by the HTTP client itself.
+const SKIPPED_HEADERS: &[&str] = &[
+    "connection",
+    "content-length",
+    "host",
+    "keep-alive",
+    "transfer-encoding",
+    "upgrade",
+];
+
+#[derive(Deserialize)]
+struct Har {
+    log: HarLog,
+}
+
+#[derive(Deserialize)]
+struct HarLog {
+    entries: Vec<HarEntry>,
+}
+
+#[derive(Deserialize)]
+struct HarEntry {
+    request: HarRequest,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct HarRequest {
+    method: String,
+    url: String,
+    #[serde(default)]
+    headers: Vec<HarHeader>,
+    post_data: Option<HarPostData>,
+}
+
+#[derive(Deserialize)]
+struct HarHeader {
+    name: String,
+    value: String,
+}
+
+#[derive(Deserialize)]
+struct HarPostData {
+    #[serde(default)]
+    text: String,
+    /// Not in the HAR spec, but some tools set `base64` for binary bodies.
+    encoding: Option<String>,
+}
+
+/// Reads requests of a HAR file as operations named `METHOD /path`.
+/// `variables` can be used in `{{name}}` placeholders of the requests (e.g. data columns),
+/// unless `no_template`.
+pub fn read_operations(
+    path: &str,
+    variables: &[String],
+    no_template: bool,
+) -> Result<Vec<HttpOperation>, String> {
+    let file = File::open(path).map_err(|e| format!("Cannot open {}: {}", path, e))?;
+    parse_operations(BufReader::new(file), variables, no_template)
+}
+
+fn parse_operations(
+    input: impl Read,
+    variables: &[String],
+    no_template: bool,
+) -> Result<Vec<HttpOperation>, String> {
+    let har: Har = serde_json::from_reader(input).map_err(|e| format!("Invalid HAR: {}", e))?;
+    if har.log.entries.is_empty() {
+        return Err("No entries in HAR".to_string());
+    }
+
+    har.log
+        .entries
+        .into_iter()
+        .map(|entry| {
+            let request = entry.request;
+            let name = format!("{} {}", request.method, url_path(&request.url));
+
+            let headers = request
+                .headers
+                .into_iter()
+                .filter(|h| {
+                    !h.name.starts_with(':')
+                        && !SKIPPED_HEADERS.contains(&h.name.to_ascii_lowercase().as_str())
+                })
+                .map(|h| (h.name, vec![h.value]))
+                .collect();
+            let body = match request.post_data {
+                Some(data) if data.encoding.as_deref() == Some("base64") => {
+                    base64::decode(&data.text).map_err(|e| format!("{}: {}", name, e))?
+                }
+                Some(data) => data.text.into_bytes(),
+                None => vec![],
+            };
+
+            let request = HttpRequestBuilder::default()
+                .url(vec![request.url])
+                .method(request.method)
+                .headers(headers)
+                .body(body)
+                .variables(variables.to_vec())
+                .no_template(no_template)
+                .build()
+                .map_err(|e| format!("{}: {}", name, e))?;
+
+            HttpOperationBuilder::default()
+                .name(name)
+                .request(request)
+                .build()
+                .map_err(|e| e.to_string())
+        })
+        .collect()
+}
+
+/// The path without the query, URLs may contain `{{...}}` placeholders.
+fn url_path(url: &str) -> &str {
+    let without_scheme = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let path = without_scheme
+        .find('/')
+        .map_or("/", |idx| &without_scheme[idx..]);
+    path.split('?').next().unwrap_or(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::har::{parse_operations, url_path};
+
+    const HAR: &str = r#"{
+      "log": {
+        "version": "1.2",
+        "entries": [
+          {
+            "request": {
+              "method": "GET",
+              "url": "http://localhost:8080/api/items?page=2",
+              "headers": [
+                {"name": ":authority", "value": "localhost:8080"},
+                {"name": "Host", "value": "localhost:8080"},
+                {"name": "Accept", "value": "application/json"}
+              ]
+            },
+            "response": {"status": 200}
+          },
+          {
+            "request": {
+              "method": "POST",
+              "url": "http://localhost:8080/api/items",
+              "headers": [{"name": "Content-Length", "value": "12"}],
+              "postData": {"mimeType": "application/json", "text": "{\"id\": 42}"}
+            }
+          },
+          {
+            "request": {
+              "method": "PUT",
+              "url": "http://localhost:8080/api/blob",
+              "postData": {"text": "AAEC", "encoding": "base64"}
+            }
+          }
+        ]
+      }
+    }"#;
+
+    #[tokio::test]
+    async fn test_parse_operations() {
+        let operations = parse_operations(HAR.as_bytes(), &[], false).unwrap();
+        let names: Vec<&str> = operations.iter().map(|o| o.name.as_str()).collect();
+        assert_eq!(
+            vec!["GET /api/items", "POST /api/items", "PUT /api/blob"],
+            names
+        );
+
+        let get = operations[0].request.build_request();
+        assert_eq!("/api/items?page=2", get.uri().path_and_query().unwrap());
+        assert_eq!(1, get.headers().len());
+        assert_eq!("application/json", get.headers()["accept"]);
+
+        let post = operations[1].request.build_request();
+        assert_eq!("POST", post.method());
+        assert!(post.headers().is_empty());
+        let body = hyper::body::to_bytes(post.into_body()).await.unwrap();
+        assert_eq!(b"{\"id\": 42}".to_vec(), body);
+
+        let put = operations[2].request.build_request();
+        let body = hyper::body::to_bytes(put.into_body()).await.unwrap();
+        assert_eq!(vec![0u8, 1, 2], body);
+    }
+
+    #[test]
+    fn test_url_path() {
+        assert_eq!("/a/b", url_path("https://host:8443/a/b?c=d"));
+        assert_eq!("/", url_path("http://host"));
+        assert_eq!("/users/{{id}}", url_path("http://host/users/{{id}}"));
+    }
+
+    #[test]
+    fn test_invalid_har() {
+        assert!(parse_operations("{}".as_bytes(), &[], false).is_err());
+        assert!(parse_operations(r#"{"log": {"entries": []}}"#.as_bytes(), &[], false).is_err());
+        assert!(parse_operations(
+            r#"{"log": {"entries": [{"request": {"method": "GET", "url": "http://a/{{x}}"}}]}}"#
+                .as_bytes(),
+            &[],
+            false
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,187 @@
+/// Copyright 2020 Developers of the perf-gauge project.
+///
+/// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+/// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+/// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+/// option. This file may not be copied, modified, or distributed
+/// except according to those terms.
+use crate::metrics::{BenchRunMetrics, BenchRunMetricsItem, ExternalMetricsServiceReporter};
+use hdrhistogram::serialization::interval_log::{IntervalLogWriter, IntervalLogWriterBuilder, Tag};
+use hdrhistogram::serialization::V2DeflateSerializer;
+use log::info;
+use std::fs::File;
+use std::io;
+use std::io::Write;
+use std::sync::Mutex;
+use std::time::{Instant, SystemTime};
+
+/// Writes latency histograms of each batch to a file in the HdrHistogram interval log format
+/// (base64-encoded compressed histograms of nanoseconds), so they can be merged and re-analysed,
+/// e.g. with `HistogramLogProcessor`. Requires the HdrHistogram backend.
+pub struct HdrLogReporter {
+    path: String,
+    start: Instant,
+    file: Mutex<File>,
+}
+
+impl HdrLogReporter {
+    pub fn new(path: &str) -> io::Result<Self> {
+        let mut file = File::create(path)?;
+        let now = SystemTime::now();
+        IntervalLogWriterBuilder::new()
+            .add_comment("perf-gauge latencies in nanoseconds")
+            .with_start_time(now)
+            .with_base_time(now)
+            .begin_log_with(&mut file, &mut V2DeflateSerializer::new())?;
+        writeln!(
+            file,
+            "\"StartTimestamp\",\"Interval_Length\",\"Interval_Max\",\"Interval_Compressed_Histogram\""
+        )?;
+        Ok(Self {
+            path: path.to_string(),
+            start: Instant::now(),
+            file: Mutex::new(file),
+        })
+    }
+
+    /// Histograms are tagged `latency` and `{phase}_latency`, prefixed by `{operation}.` for operations.
+    fn write_item(
+        &self,
+        writer: &mut IntervalLogWriter<File, V2DeflateSerializer>,
+        operation: Option<&str>,
+        metrics: &BenchRunMetricsItem,
+    ) -> io::Result<()> {
+        let start_timestamp = metrics.bench_begin.saturating_duration_since(self.start);
+        let duration = metrics.bench_begin.elapsed();
+
+        let mut latency = metrics.success_latency.clone();
+        latency.merge(&metrics.error_latency);
+        let mut histograms = vec![("latency".to_string(), &latency)];
+        for (phase, histogram) in metrics.phase_latency.iter() {
+            histograms.push((format!("{}_latency", phase), histogram));
+        }
+
+        for (name, histogram) in histograms {
+            let tag = match operation {
+                Some(operation) => HdrLogReporter::sanitize(&format!("{}.{}", operation, name)),
+                None => name,
+            };
+            if let Some(histogram) = histogram.hdr() {
+                writer
+                    .write_histogram(histogram, start_timestamp, duration, Tag::new(&tag))
+                    .map_err(|e| io::Error::other(e.to_string()))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Tags cannot contain commas and whitespace.
+    fn sanitize(tag: &str) -> String {
+        tag.replace([',', ' ', '\r', '\n'], "_")
+    }
+}
+
+impl ExternalMetricsServiceReporter for HdrLogReporter {
+    fn report(&self, metrics: &BenchRunMetrics) -> io::Result<()> {
+        info!("Writing latency histograms to {}", self.path);
+        let mut file = self.file.lock().expect("Poisoned HDR log");
+        let mut serializer = V2DeflateSerializer::new();
+        // the headers are written on creation
+        let mut writer =
+            IntervalLogWriterBuilder::new().begin_log_with(&mut *file, &mut serializer)?;
+
+        self.write_item(&mut writer, None, &metrics.combined)?;
+        let mut operations: Vec<&String> = metrics.by_operation.keys().collect();
+        operations.sort();
+        for operation in operations {
+            self.write_item(
+                &mut writer,
+                Some(operation),
+                &metrics.by_operation[operation],
+            )?;
+        }
+        Ok(())
+    }
+
+    fn reset_metrics(&self) {
+        // do nothing
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hdr_log_reporter::HdrLogReporter;
+    use crate::latency_histogram::{HistogramBackend, LatencyHistogram};
+    use crate::metrics::{BenchRunMetrics, BenchRunMetricsItem, ExternalMetricsServiceReporter};
+    use hdrhistogram::serialization::interval_log::{IntervalLogIterator, LogEntry};
+    use hdrhistogram::serialization::Deserializer;
+    use hdrhistogram::Histogram;
+    use std::fs;
+    use std::time::Duration;
+
+    fn hdr_item(values: &[Duration]) -> BenchRunMetricsItem {
+        let backend = HistogramBackend::Hdr {
+            significant_digits: 3,
+            max_value: Duration::from_secs(60),
+        };
+        let mut item = BenchRunMetricsItem::new(backend);
+        let mut connect = LatencyHistogram::new(backend);
+        for value in values {
+            item.success_latency.increment(*value);
+            connect.increment(*value / 10);
+        }
+        item.phase_latency.insert("connect", connect);
+        item
+    }
+
+    #[test]
+    fn test_hdr_log() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("latency.hlog");
+        let path = path.to_str().unwrap();
+        let reporter = HdrLogReporter::new(path).unwrap();
+
+        let mut metrics = BenchRunMetrics::new(HistogramBackend::Simple);
+        metrics.combined = hdr_item(&[Duration::from_nanos(1500), Duration::from_millis(5)]);
+        metrics.by_operation.insert(
+            "GET /a, b".to_string(),
+            hdr_item(&[Duration::from_millis(1)]),
+        );
+        reporter.report(&metrics).unwrap();
+        reporter.report(&metrics).unwrap();
+
+        let log = fs::read(path).unwrap();
+
+        let mut deserializer = Deserializer::new();
+        let intervals: Vec<(String, Histogram<u64>)> = IntervalLogIterator::new(&log)
+            .filter_map(|e| match e.expect("Valid interval log") {
+                LogEntry::Interval(h) => Some(h),
+                _ => None,
+            })
+            .map(|h| {
+                let bytes = base64::decode(h.encoded_histogram()).unwrap();
+                (
+                    h.tag().unwrap().as_str().to_string(),
+                    deserializer.deserialize(&mut bytes.as_slice()).unwrap(),
+                )
+            })
+            .collect();
+
+        let tags: Vec<&str> = intervals.iter().map(|(t, _)| t.as_str()).collect();
+        assert_eq!(
+            vec![
+                "latency",
+                "connect_latency",
+                "GET_/a__b.latency",
+                "GET_/a__b.connect_latency"
+            ],
+            tags[..4]
+        );
+        assert_eq!(8, tags.len());
+
+        let (_, latency) = &intervals[0];
+        assert_eq!(2, latency.len());
+        // nanosecond precision is preserved
+        assert_eq!(1500, latency.min());
+    }
+}
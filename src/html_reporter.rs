@@ -0,0 +1,488 @@
+/// Copyright 2020 Developers of the perf-gauge project.
+///
+/// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+/// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+/// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+/// option. This file may not be copied, modified, or distributed
+/// except according to those terms.
+use crate::latency_histogram::LatencyHistogram;
+use crate::metrics::{BenchRunMetrics, ExternalMetricsServiceReporter};
+use log::info;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::sync::Mutex;
+use std::time::{Instant, SystemTime};
+
+const WIDTH: f64 = 760.;
+const HEIGHT: f64 = 300.;
+const MARGIN_LEFT: f64 = 70.;
+const MARGIN_RIGHT: f64 = 20.;
+const MARGIN_TOP: f64 = 40.;
+const MARGIN_BOTTOM: f64 = 40.;
+const COLORS: [&str; 5] = ["#1f77b4", "#ff7f0e", "#2ca02c", "#d62728", "#9467bd"];
+const DISTRIBUTION_BINS: usize = 40;
+
+/// Writes a self-contained HTML page with charts of the results of each step (batch),
+/// the latency distribution, response codes and the configuration of the session.
+/// The page is rewritten after each step, so at the end of the session it contains all of them.
+pub struct HtmlReporter {
+    path: String,
+    test_case_name: String,
+    configuration: String,
+    started: SystemTime,
+    session: Mutex<Session>,
+}
+
+#[derive(Default)]
+struct Session {
+    steps: Vec<Step>,
+    /// Of all steps.
+    latency: Option<LatencyHistogram>,
+    response_codes: BTreeMap<String, i32>,
+}
+
+struct Step {
+    concurrency: usize,
+    duration_secs: f64,
+    total_requests: usize,
+    rate_per_second: f64,
+    success_rate: f64,
+    /// p50, p90, p99, p99.9 and max in µs.
+    latency: [u64; 5],
+}
+
+impl HtmlReporter {
+    pub fn new(
+        path: &str,
+        test_case_name: Option<String>,
+        configuration: String,
+    ) -> io::Result<Self> {
+        let reporter = Self {
+            path: path.to_string(),
+            test_case_name: test_case_name.unwrap_or_else(|| "perf-gauge".to_string()),
+            configuration,
+            started: SystemTime::now(),
+            session: Default::default(),
+        };
+        // fail fast if the file cannot be written
+        fs::write(&reporter.path, reporter.render(&Session::default()))?;
+        Ok(reporter)
+    }
+
+    fn render(&self, session: &Session) -> String {
+        let mut html = String::new();
+        let title = format!("{} results", escape(&self.test_case_name));
+        write!(
+            html,
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n\
+             <style>\n\
+             body {{ font-family: sans-serif; margin: 2em; color: #222; }}\n\
+             table {{ border-collapse: collapse; margin-bottom: 1em; }}\n\
+             th, td {{ border: 1px solid #ccc; padding: 4px 8px; text-align: right; }}\n\
+             th {{ background: #f4f4f4; }}\n\
+             pre {{ background: #f4f4f4; padding: 1em; overflow-x: auto; }}\n\
+             svg {{ display: block; margin-bottom: 1em; }}\n\
+             </style>\n</head>\n<body>\n<h1>{}</h1>\n<p>Started at {}</p>\n",
+            title,
+            title,
+            humantime::format_rfc3339_seconds(self.started)
+        )
+        .unwrap();
+
+        if session.steps.is_empty() {
+            html.push_str("<p>No results yet.</p>\n");
+        } else {
+            self.render_steps(&mut html, session);
+        }
+
+        writeln!(
+            html,
+            "<h2>Configuration</h2>\n<pre>{}</pre>\n</body>\n</html>",
+            escape(&self.configuration)
+        )
+        .unwrap();
+        html
+    }
+
+    fn render_steps(&self, html: &mut String, session: &Session) {
+        let steps = &session.steps;
+        let labels: Vec<String> = steps
+            .iter()
+            .enumerate()
+            .map(|(i, s)| format!("#{} (c={})", i + 1, s.concurrency))
+            .collect();
+        let latency_ms = |index: usize| -> Vec<f64> {
+            steps
+                .iter()
+                .map(|s| s.latency[index] as f64 / 1000.)
+                .collect()
+        };
+
+        html.push_str("<h2>Steps</h2>\n");
+        html.push_str(&line_chart(
+            "Throughput, requests per second",
+            &labels,
+            &[("rps", steps.iter().map(|s| s.rate_per_second).collect())],
+        ));
+        html.push_str(&line_chart(
+            "Success rate, %",
+            &labels,
+            &[("success", steps.iter().map(|s| s.success_rate).collect())],
+        ));
+        html.push_str(&line_chart(
+            "Latency, ms",
+            &labels,
+            &[
+                ("p50", latency_ms(0)),
+                ("p90", latency_ms(1)),
+                ("p99", latency_ms(2)),
+                ("p99.9", latency_ms(3)),
+                ("max", latency_ms(4)),
+            ],
+        ));
+
+        html.push_str(
+            "<table>\n<tr><th>Step</th><th>Concurrency</th><th>Duration, s</th><th>Requests</th>\
+             <th>Requests per second</th><th>Success rate, %</th><th>p50, ms</th><th>p90, ms</th>\
+             <th>p99, ms</th><th>p99.9, ms</th><th>Max, ms</th></tr>\n",
+        );
+        for (i, step) in steps.iter().enumerate() {
+            write!(
+                html,
+                "<tr><td>{}</td><td>{}</td><td>{:.3}</td><td>{}</td><td>{:.3}</td><td>{:.3}</td>",
+                i + 1,
+                step.concurrency,
+                step.duration_secs,
+                step.total_requests,
+                step.rate_per_second,
+                step.success_rate
+            )
+            .unwrap();
+            for latency in step.latency {
+                write!(html, "<td>{:.3}</td>", latency as f64 / 1000.).unwrap();
+            }
+            html.push_str("</tr>\n");
+        }
+        html.push_str("</table>\n");
+
+        if let Some(latency) = &session.latency {
+            html.push_str("<h2>Latency distribution</h2>\n");
+            html.push_str(&distribution_chart(latency));
+        }
+
+        html.push_str("<h2>Response codes</h2>\n<table>\n<tr><th>Code</th><th>Count</th></tr>\n");
+        for (code, count) in session.response_codes.iter() {
+            writeln!(html, "<tr><td>{}</td><td>{}</td></tr>", escape(code), count).unwrap();
+        }
+        html.push_str("</table>\n");
+    }
+}
+
+impl ExternalMetricsServiceReporter for HtmlReporter {
+    fn report(&self, metrics: &BenchRunMetrics) -> io::Result<()> {
+        let combined = &metrics.combined;
+        let mut latency = combined.success_latency.clone();
+        latency.merge(&combined.error_latency);
+        let duration_secs = Instant::now()
+            .duration_since(combined.bench_begin)
+            .as_secs_f64();
+        let step = Step {
+            concurrency: metrics.concurrency,
+            duration_secs,
+            total_requests: combined.total_requests,
+            rate_per_second: combined.total_requests as f64 / duration_secs,
+            success_rate: combined.success_rate(),
+            latency: [
+                latency.percentile(50.),
+                latency.percentile(90.),
+                latency.percentile(99.),
+                latency.percentile(99.9),
+                latency.maximum(),
+            ],
+        };
+
+        let mut session = self.session.lock().expect("Poisoned HTML report");
+        session.steps.push(step);
+        match session.latency.as_mut() {
+            None => session.latency = Some(latency),
+            Some(total) => total.merge(&latency),
+        }
+        for (code, count) in combined.summary.iter() {
+            *session.response_codes.entry(code.clone()).or_default() += count;
+        }
+
+        info!("Writing the HTML report to {}", self.path);
+        fs::write(&self.path, self.render(&session))
+    }
+
+    fn reset_metrics(&self) {
+        // do nothing
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// The upper bound of the Y axis, with some headroom.
+fn y_max(values: impl Iterator<Item = f64>) -> f64 {
+    let max = values.fold(0., f64::max);
+    if max > 0. {
+        max * 1.1
+    } else {
+        1.
+    }
+}
+
+fn svg_header(svg: &mut String, title: &str, y_max: f64) {
+    let plot_height = HEIGHT - MARGIN_TOP - MARGIN_BOTTOM;
+    write!(
+        svg,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" font-size=\"12\">\n\
+         <text x=\"{l}\" y=\"20\" font-weight=\"bold\">{t}</text>\n",
+        w = WIDTH,
+        h = HEIGHT,
+        l = MARGIN_LEFT,
+        t = escape(title)
+    )
+    .unwrap();
+    for i in 0..=4 {
+        let value = y_max * i as f64 / 4.;
+        let y = MARGIN_TOP + plot_height * (1. - i as f64 / 4.);
+        write!(
+            svg,
+            "<line x1=\"{l}\" y1=\"{y:.1}\" x2=\"{r}\" y2=\"{y:.1}\" stroke=\"#ddd\"/>\n\
+             <text x=\"{tx}\" y=\"{ty:.1}\" text-anchor=\"end\">{v}</text>\n",
+            l = MARGIN_LEFT,
+            r = WIDTH - MARGIN_RIGHT,
+            y = y,
+            tx = MARGIN_LEFT - 5.,
+            ty = y + 4.,
+            v = format_value(value)
+        )
+        .unwrap();
+    }
+}
+
+fn format_value(value: f64) -> String {
+    if value >= 100. {
+        format!("{:.0}", value)
+    } else if value >= 1. {
+        format!("{:.2}", value)
+    } else {
+        format!("{:.3}", value)
+    }
+}
+
+/// A chart of series of values per label, e.g. per step.
+fn line_chart(title: &str, labels: &[String], series: &[(&str, Vec<f64>)]) -> String {
+    let mut svg = String::new();
+    let y_max = y_max(series.iter().flat_map(|(_, values)| values.iter().copied()));
+    svg_header(&mut svg, title, y_max);
+
+    let plot_width = WIDTH - MARGIN_LEFT - MARGIN_RIGHT;
+    let plot_height = HEIGHT - MARGIN_TOP - MARGIN_BOTTOM;
+    let x = |i: usize| MARGIN_LEFT + plot_width * (i as f64 + 0.5) / labels.len() as f64;
+    let y = |value: f64| MARGIN_TOP + plot_height * (1. - value / y_max);
+
+    // don't let labels overlap
+    let label_step = labels.len().div_ceil(10);
+    for (i, label) in labels.iter().enumerate().step_by(label_step) {
+        writeln!(
+            svg,
+            "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"middle\">{}</text>",
+            x(i),
+            HEIGHT - MARGIN_BOTTOM + 18.,
+            escape(label)
+        )
+        .unwrap();
+    }
+
+    for (index, (name, values)) in series.iter().enumerate() {
+        let color = COLORS[index % COLORS.len()];
+        let points: Vec<String> = values
+            .iter()
+            .enumerate()
+            .map(|(i, v)| format!("{:.1},{:.1}", x(i), y(*v)))
+            .collect();
+        writeln!(
+            svg,
+            "<polyline fill=\"none\" stroke=\"{}\" stroke-width=\"2\" points=\"{}\"/>",
+            color,
+            points.join(" ")
+        )
+        .unwrap();
+        for (i, value) in values.iter().enumerate() {
+            writeln!(
+                svg,
+                "<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"3\" fill=\"{}\"><title>{} {}: {}</title></circle>",
+                x(i),
+                y(*value),
+                color,
+                escape(&labels[i]),
+                escape(name),
+                format_value(*value)
+            )
+            .unwrap();
+        }
+        // legend
+        let legend_x = WIDTH - MARGIN_RIGHT - 70. * (series.len() - index) as f64;
+        write!(
+            svg,
+            "<rect x=\"{:.1}\" y=\"26\" width=\"10\" height=\"10\" fill=\"{}\"/>\n\
+             <text x=\"{:.1}\" y=\"35\">{}</text>\n",
+            legend_x,
+            color,
+            legend_x + 14.,
+            escape(name)
+        )
+        .unwrap();
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// Share of requests per latency range, with logarithmic ranges.
+fn distribution_chart(latency: &LatencyHistogram) -> String {
+    let buckets = latency.buckets();
+    let total: u64 = buckets.iter().map(|(_, count)| count).sum();
+    if total == 0 {
+        return "<p>No requests.</p>\n".to_string();
+    }
+    // values are in µs, zero for sub-microsecond latencies of the default histogram
+    let min = buckets.first().map_or(1., |(v, _)| v.max(0.001)).ln();
+    let max = buckets.last().map_or(1., |(v, _)| v.max(0.001)).ln();
+    let bins = if max > min { DISTRIBUTION_BINS } else { 1 };
+    let bin_width = (max - min) / bins as f64;
+    let mut counts = vec![0; bins];
+    for (value, count) in buckets {
+        let bin = if bins == 1 {
+            0
+        } else {
+            (((value.max(0.001).ln() - min) / bin_width) as usize).min(bins - 1)
+        };
+        counts[bin] += count;
+    }
+    let shares: Vec<f64> = counts
+        .iter()
+        .map(|c| *c as f64 * 100. / total as f64)
+        .collect();
+    // the upper bound of each range, in ms
+    let upper_bounds: Vec<f64> = (1..=bins)
+        .map(|i| (min + bin_width * i as f64).exp() / 1000.)
+        .collect();
+
+    let mut svg = String::new();
+    let y_max = y_max(shares.iter().copied());
+    svg_header(&mut svg, "Requests, % by latency, ms", y_max);
+    let plot_width = WIDTH - MARGIN_LEFT - MARGIN_RIGHT;
+    let plot_height = HEIGHT - MARGIN_TOP - MARGIN_BOTTOM;
+    let bar_width = plot_width / bins as f64;
+    for (i, share) in shares.iter().enumerate() {
+        let lower_bound = if i == 0 {
+            min.exp() / 1000.
+        } else {
+            upper_bounds[i - 1]
+        };
+        let height = plot_height * share / y_max;
+        writeln!(
+            svg,
+            "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" fill=\"{}\">\
+             <title>{}-{} ms: {:.3}%</title></rect>",
+            MARGIN_LEFT + bar_width * i as f64,
+            MARGIN_TOP + plot_height - height,
+            (bar_width - 1.).max(1.),
+            height,
+            COLORS[0],
+            format_value(lower_bound),
+            format_value(upper_bounds[i]),
+            share
+        )
+        .unwrap();
+    }
+    for i in (0..bins).step_by(bins.div_ceil(8)) {
+        writeln!(
+            svg,
+            "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"middle\">{}</text>",
+            MARGIN_LEFT + bar_width * (i as f64 + 1.),
+            HEIGHT - MARGIN_BOTTOM + 18.,
+            format_value(upper_bounds[i])
+        )
+        .unwrap();
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::html_reporter::HtmlReporter;
+    use crate::latency_histogram::HistogramBackend;
+    use crate::metrics::{BenchRunMetrics, ExternalMetricsServiceReporter, RequestStatsBuilder};
+    use std::fs;
+    use std::time::Duration;
+
+    fn batch(concurrency: usize, latencies: &[(u64, &str)]) -> BenchRunMetrics {
+        let mut metrics = BenchRunMetrics::new(HistogramBackend::Simple);
+        metrics.concurrency = concurrency;
+        for (latency, status) in latencies {
+            metrics.report_request(
+                RequestStatsBuilder::default()
+                    .is_success(status.starts_with('2'))
+                    .bytes_processed(0)
+                    .status(status.to_string())
+                    .duration(Duration::from_micros(*latency))
+                    .fatal_error(false)
+                    .build()
+                    .unwrap(),
+            );
+        }
+        metrics
+    }
+
+    #[test]
+    fn test_html_report() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("report.html");
+        let path = path.to_str().unwrap();
+        let reporter =
+            HtmlReporter::new(path, None, "--rate 10 --header <Accept>".to_string()).unwrap();
+        assert!(fs::read_to_string(path).unwrap().contains("No results yet"));
+
+        reporter
+            .report(&batch(1, &[(1000, "200 OK"), (3000, "200 OK")]))
+            .unwrap();
+        reporter
+            .report(&batch(
+                2,
+                &[
+                    (10, "200 OK"),
+                    (10, "200 OK"),
+                    (10, "500 Internal Server Error"),
+                    (2000, "500 Internal Server Error"),
+                ],
+            ))
+            .unwrap();
+
+        let html = fs::read_to_string(path).unwrap();
+
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(!html.contains("No results yet"));
+        // throughput, success rate, latency and distribution charts
+        assert_eq!(4, html.matches("<svg").count());
+        assert!(!reporter.reports_snapshots());
+        assert!(html.contains("#2 (c=2)"));
+        assert!(!html.contains("#3 (c="));
+        assert!(html.contains("<tr><td>200 OK</td><td>4</td></tr>"));
+        assert!(html.contains("<tr><td>500 Internal Server Error</td><td>2</td></tr>"));
+        // the second step: the success rate is 50%, p50 is 10µs, the max is 2ms
+        assert!(html.contains("<td>50.000</td><td>0.010</td>"));
+        assert!(html.contains("<td>2.00"));
+        assert!(html.contains("--rate 10 --header &lt;Accept&gt;"));
+    }
+}
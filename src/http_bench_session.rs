@@ -1,4 +1,6 @@
+use crate::assertion::ResponseAssertions;
 use crate::bench_run::BenchmarkProtocolAdapter;
+use crate::data_feeder::{DataCursor, DataFeeder};
 /// Copyright 2020 Developers of the perf-gauge project.
 ///
 /// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
@@ -6,30 +8,51 @@ use crate::bench_run::BenchmarkProtocolAdapter;
 /// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
 /// option. This file may not be copied, modified, or distributed
 /// except according to those terms.
+use crate::extractor::{Extractor, EXTRACTION_FAILED_STATUS};
+use crate::http_timing::{
+    ConnectTimings, TcpTimer, TimedConnector, TimedHttpConnector, TimedResolver, BODY_PHASE,
+    TTFB_PHASE,
+};
 use crate::metrics::{RequestStats, RequestStatsBuilder};
+#[cfg(feature = "scripting")]
+use crate::script::{RequestScript, ScriptedRequest, REJECTED_STATUS};
+use crate::template;
+use crate::template::{Template, Variables};
 use async_trait::async_trait;
 #[cfg(feature = "tls-boring")]
 use boring::ssl::{SslConnector, SslMethod};
 use core::fmt;
+use futures_util::future::BoxFuture;
 use futures_util::StreamExt;
-use hyper::client::HttpConnector;
-use hyper::header::{HeaderName, HeaderValue};
+use hyper::client::{HttpConnector, ResponseFuture};
+use hyper::header::{HeaderName, HeaderValue, HOST};
+use hyper::http::request::Builder;
+use hyper::http::response::Parts;
 use hyper::{Body, Method, Request};
 #[cfg(feature = "tls-boring")]
 use hyper_boring::HttpsConnector;
 #[cfg(feature = "tls-native")]
 use hyper_tls::HttpsConnector;
+#[cfg(unix)]
+use hyperlocal::UnixConnector;
 use log::error;
 use rand::{thread_rng, Rng};
 use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::time::Duration;
 use std::time::Instant;
 #[cfg(feature = "tls-native")]
 use tokio_native_tls::TlsConnector;
 
+/// Targets in `unix:///path/to.sock` or `unix:///path/to.sock:/request/path` form.
+const UNIX_URL_PREFIX: &str = "unix://";
+/// Unix domain socket requests are sent with this `Host` header, unless it's set explicitly.
+const UNIX_DEFAULT_HOST: &str = "localhost";
+
 #[derive(Builder, Deserialize, Clone, Debug)]
 pub struct HttpClientConfig {
     #[builder(default)]
+    #[cfg_attr(not(feature = "tls-native"), allow(dead_code))]
     ignore_cert: bool,
     #[builder(default)]
     conn_reuse: bool,
@@ -49,42 +72,178 @@ pub struct HttpRequest {
     headers: Vec<(String, Vec<String>)>,
     #[builder(default)]
     body: Vec<u8>,
+    /// Expand `{{...}}` placeholders in the URL and header values for each request.
+    /// By default, it's enabled if there are any placeholders, see `HttpRequestBuilder::no_template`.
+    /// Only checked on building.
+    #[allow(dead_code)]
+    #[builder(default = "self.has_placeholders()")]
+    #[serde(default)]
+    templated: bool,
+    /// Expand `{{...}}` placeholders in the body for each request.
+    /// By default, it's enabled if the body is text with placeholders. Only checked on building.
+    #[allow(dead_code)]
+    #[builder(default = "self.has_body_placeholders()")]
+    #[serde(default)]
+    templated_body: bool,
+    /// Names of variables, which can be used in `{{name}}` placeholders (e.g. data columns).
+    /// Only checked on building.
+    #[allow(dead_code)]
+    #[builder(default)]
+    #[serde(default)]
+    variables: Vec<String>,
+    #[builder(
+        setter(skip),
+        default = "self.parse_templates().expect(\"Templates must be valid at this point\")"
+    )]
+    #[serde(skip)]
+    templates: RequestTemplates,
+}
+
+/// Templates parsed on building, parts without templates are sent as is.
+#[derive(Clone, Default)]
+struct RequestTemplates {
+    /// Of each URL, if `templated`.
+    urls: Vec<Template>,
+    /// Of each header value, if `templated`.
+    headers: Vec<Vec<Template>>,
+    /// If `templated_body`.
+    body: Option<Template>,
 }
 
+/// A named request, e.g. an entry of a HAR file. The name is reported as the operation name.
 #[derive(Builder, Deserialize, Clone)]
+pub struct HttpOperation {
+    pub(crate) name: String,
+    /// Relative frequency for `OperationOrder::Weighted`.
+    #[builder(default = "1")]
+    pub(crate) weight: u32,
+    pub(crate) request: HttpRequest,
+    /// Values extracted from successful responses into per-connection variables.
+    #[builder(default)]
+    #[serde(skip)]
+    pub(crate) extractors: Vec<Extractor>,
+    /// Replace the assertions of the adapter, if set.
+    #[builder(default)]
+    #[serde(skip)]
+    pub(crate) assertions: Option<ResponseAssertions>,
+}
+
+/// How the next operation is chosen.
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub enum OperationOrder {
+    /// One by one in the given order, separately for each connection.
+    #[default]
+    Sequential,
+    /// Randomly, according to the weights.
+    Weighted,
+}
+
+#[derive(Builder, Deserialize, Clone)]
+#[builder(build_fn(validate = "Self::validate"))]
 pub struct HttpBenchAdapter {
     config: HttpClientConfig,
-    request: HttpRequest,
+    /// Sent unless there are named operations.
+    #[builder(default, setter(strip_option))]
+    #[serde(default)]
+    request: Option<HttpRequest>,
+    #[builder(default)]
+    #[serde(default)]
+    operations: Vec<HttpOperation>,
+    #[builder(default)]
+    #[serde(default)]
+    operation_order: OperationOrder,
+    #[cfg(feature = "scripting")]
+    #[builder(default)]
+    #[serde(skip)]
+    script: Option<RequestScript>,
+    /// Records bound to `{{column}}` placeholders, one per request.
+    #[builder(default)]
+    #[serde(skip)]
+    data: Option<DataFeeder>,
+    #[builder(default)]
+    #[serde(skip)]
+    assertions: ResponseAssertions,
 }
 
 #[cfg(feature = "tls")]
-type ProtocolConnector = HttpsConnector<HttpConnector>;
+pub(crate) type ProtocolConnector = TimedConnector<HttpsConnector<TimedHttpConnector>>;
 #[cfg(not(feature = "tls"))]
-type ProtocolConnector = HttpConnector;
+pub(crate) type ProtocolConnector = TimedConnector<TimedHttpConnector>;
+
+/// Sends requests either over TCP or Unix domain sockets, depending on the target.
+pub struct HttpClient {
+    tcp: hyper::Client<ProtocolConnector>,
+    #[cfg(unix)]
+    unix: hyper::Client<UnixConnector>,
+    /// Per-connection state of the request script.
+    #[cfg(feature = "scripting")]
+    script_state: std::sync::Mutex<rhai::Dynamic>,
+    data_cursor: Option<DataCursor>,
+    /// Position of the connection in `OperationOrder::Sequential` operations.
+    operation_position: AtomicUsize,
+    /// Values extracted from responses of this connection.
+    extracted: std::sync::Mutex<Variables>,
+}
+
+impl HttpClient {
+    pub(crate) fn request(&self, request: Request<Body>) -> ResponseFuture {
+        #[cfg(unix)]
+        if request.uri().scheme_str() == Some("unix") {
+            return self.unix.request(request);
+        }
+        self.tcp.request(request)
+    }
+}
+
+impl HttpClientConfig {
+    pub(crate) fn build_client(&self) -> HttpClient {
+        HttpClient {
+            tcp: self.client_builder().build(self.build_connector()),
+            #[cfg(unix)]
+            unix: self.client_builder().build(UnixConnector),
+            #[cfg(feature = "scripting")]
+            script_state: std::sync::Mutex::new(RequestScript::new_state()),
+            data_cursor: None,
+            operation_position: AtomicUsize::new(0),
+            extracted: std::sync::Mutex::new(Variables::new()),
+        }
+    }
+
+    fn client_builder(&self) -> hyper::client::Builder {
+        let mut builder = hyper::Client::builder();
+        builder
+            .http2_only(self.http2_only)
+            .pool_max_idle_per_host(if !self.conn_reuse { 0 } else { usize::MAX });
+        builder
+    }
 
-impl HttpBenchAdapter {
     #[cfg(not(feature = "tls"))]
     fn build_connector(&self) -> ProtocolConnector {
-        self.build_http_connector()
+        TimedConnector::new(self.build_http_connector())
     }
 
     #[cfg(feature = "tls-native")]
     fn build_connector(&self) -> ProtocolConnector {
-        HttpsConnector::from((self.build_http_connector(), self.build_tls_connector()))
+        TimedConnector::new(HttpsConnector::from((
+            self.build_http_connector(),
+            self.build_tls_connector(),
+        )))
     }
 
     #[cfg(feature = "tls-boring")]
     fn build_connector(&self) -> ProtocolConnector {
         let builder =
             SslConnector::builder(SslMethod::tls()).expect("Cannot build BoringSSL builder");
-        hyper_boring::HttpsConnector::with_connector(self.build_http_connector(), builder)
-            .expect("Cannot build Boring HttpsConnector")
+        TimedConnector::new(
+            hyper_boring::HttpsConnector::with_connector(self.build_http_connector(), builder)
+                .expect("Cannot build Boring HttpsConnector"),
+        )
     }
 
     #[cfg(feature = "tls-native")]
     fn build_tls_connector(&self) -> TlsConnector {
         let mut native_tls_builder = native_tls::TlsConnector::builder();
-        if self.config.ignore_cert {
+        if self.ignore_cert {
             native_tls_builder.danger_accept_invalid_certs(true);
         }
 
@@ -95,123 +254,589 @@ impl HttpBenchAdapter {
         )
     }
 
-    fn build_http_connector(&self) -> HttpConnector {
-        let mut connector = HttpConnector::new();
+    fn build_http_connector(&self) -> TimedHttpConnector {
+        let mut connector = HttpConnector::new_with_resolver(TimedResolver::new());
         connector.set_connect_timeout(Some(Duration::from_secs(10)));
         connector.set_nodelay(true);
         #[cfg(feature = "tls")]
         connector.enforce_http(false);
-        connector
+        TcpTimer::new(connector)
     }
 }
 
 #[async_trait]
 impl BenchmarkProtocolAdapter for HttpBenchAdapter {
-    type Client = hyper::Client<ProtocolConnector>;
+    type Client = HttpClient;
 
     fn build_client(&self) -> Result<Self::Client, String> {
-        Ok(hyper::Client::builder()
-            .http2_only(self.config.http2_only)
-            .pool_max_idle_per_host(if !self.config.conn_reuse {
-                0
-            } else {
-                usize::MAX
-            })
-            .build(self.build_connector()))
+        let mut client = self.config.build_client();
+        client.data_cursor = self.data.as_ref().map(DataFeeder::new_cursor);
+        Ok(client)
+    }
+
+    fn has_more_work(&self, client: &Self::Client) -> bool {
+        match (&self.data, &client.data_cursor) {
+            (Some(data), Some(cursor)) => data.advance(cursor),
+            _ => true,
+        }
     }
 
     async fn send_request(&self, client: &Self::Client) -> RequestStats {
+        self.send_with_record(client, self.current_record(client))
+            .await
+    }
+
+    fn issue_request<'a>(&'a self, client: &'a Self::Client) -> BoxFuture<'a, RequestStats> {
+        Box::pin(self.send_with_record(client, self.current_record(client)))
+    }
+}
+
+impl HttpBenchAdapter {
+    /// The data record taken by the last `has_more_work` of the client.
+    fn current_record(&self, client: &HttpClient) -> Option<usize> {
+        match (&self.data, &client.data_cursor) {
+            (Some(data), Some(cursor)) => Some(data.current(cursor)),
+            _ => None,
+        }
+    }
+
+    async fn send_with_record(&self, client: &HttpClient, record: Option<usize>) -> RequestStats {
         let start = Instant::now();
-        let request = self.request.build_request();
+        let (operation, request) = self.next_request(client);
+        let operation_name = operation.map(|o| o.name.clone());
+        let extractors = operation.map_or(&[][..], |o| &o.extractors);
+        let assertions = operation
+            .and_then(|o| o.assertions.as_ref())
+            .unwrap_or(&self.assertions);
+        let request = match self.build_request(client, request, record) {
+            Ok(request) => request,
+            Err(e) => {
+                error!("Error building request: {}", e);
+                return HttpBenchAdapter::failed_request(e, start, operation_name);
+            }
+        };
+        let sent = Instant::now();
         let response = client.request(request).await;
 
         match response {
             Ok(r) => {
-                let status = r.status().to_string();
-                let success = r.status().is_success();
+                let headers_received = Instant::now();
+                // only the first response over a connection reports its setup
+                let mut phases = r
+                    .extensions()
+                    .get::<ConnectTimings>()
+                    .map_or_else(Vec::new, ConnectTimings::take);
+                let setup: Duration = phases.iter().map(|(_, d)| *d).sum();
+                phases.push((
+                    TTFB_PHASE,
+                    headers_received.duration_since(sent).saturating_sub(setup),
+                ));
 
-                let fatal_error =
-                    !success && self.config.stop_on_errors.contains(&r.status().as_u16());
+                let (parts, mut stream) = r.into_parts();
+                let collect_body =
+                    self.checks_response() || assertions.needs_body() || !extractors.is_empty();
 
-                let mut stream = r.into_body();
                 let mut total_size = 0;
+                let mut body = vec![];
                 while let Some(item) = stream.next().await {
                     if let Ok(bytes) = item {
                         total_size += bytes.len();
+                        if collect_body {
+                            body.extend_from_slice(&bytes);
+                        }
                     } else {
                         break;
                     }
                 }
+                phases.push((BODY_PHASE, Instant::now().duration_since(headers_received)));
+
+                let (mut status, mut success) =
+                    self.check_response(client, assertions, &parts, &body);
+                if success {
+                    if let Err(reason) = assertions.check(&parts.headers, &body) {
+                        status = reason;
+                        success = false;
+                    }
+                }
+                if success && !HttpBenchAdapter::extract(client, extractors, &parts, &body) {
+                    status = EXTRACTION_FAILED_STATUS.to_string();
+                    success = false;
+                }
+                let fatal_error =
+                    !success && self.config.stop_on_errors.contains(&parts.status.as_u16());
+
                 RequestStatsBuilder::default()
                     .bytes_processed(total_size)
                     .status(status)
                     .is_success(success)
                     .duration(Instant::now().duration_since(start))
                     .fatal_error(fatal_error)
+                    .operation_name(operation_name)
+                    .phases(phases)
                     .build()
                     .expect("RequestStatsBuilder failed")
             }
             Err(e) => {
                 error!("Error sending request: {}", e);
-                let status = e.to_string();
-                RequestStatsBuilder::default()
-                    .bytes_processed(0)
-                    .status(status)
-                    .is_success(false)
-                    .duration(Instant::now().duration_since(start))
-                    .fatal_error(false)
-                    .build()
-                    .expect("RequestStatsBuilder failed")
+                HttpBenchAdapter::failed_request(e.to_string(), start, operation_name)
             }
         }
     }
+
+    /// A copy for the clients of the next batch, see `DataFeeder::for_batch`.
+    pub(crate) fn for_batch(&self, concurrency: usize) -> HttpBenchAdapter {
+        HttpBenchAdapter {
+            data: self.data.as_ref().map(|data| data.for_batch(concurrency)),
+            ..self.clone()
+        }
+    }
+
+    /// A scenario: operations are sent one by one in the given order and pass extracted values
+    /// to the next ones.
+    pub(crate) fn is_scenario(&self) -> bool {
+        self.operation_order == OperationOrder::Sequential
+            && self.operations.iter().any(|o| !o.extractors.is_empty())
+    }
+
+    /// The request to send next and its operation, if it's a named operation.
+    fn next_request(&self, client: &HttpClient) -> (Option<&HttpOperation>, &HttpRequest) {
+        if self.operations.is_empty() {
+            return (
+                None,
+                self.request
+                    .as_ref()
+                    .expect("Request must be set at this point"),
+            );
+        }
+
+        let idx = match self.operation_order {
+            OperationOrder::Sequential => {
+                client.operation_position.fetch_add(1, Ordering::Relaxed) % self.operations.len()
+            }
+            OperationOrder::Weighted => {
+                let total: u64 = self.operations.iter().map(|o| o.weight as u64).sum();
+                let mut point = thread_rng().gen_range(0..total);
+                self.operations
+                    .iter()
+                    .position(|o| {
+                        if point < o.weight as u64 {
+                            true
+                        } else {
+                            point -= o.weight as u64;
+                            false
+                        }
+                    })
+                    .expect("Point must be within the total weight")
+            }
+        };
+        let operation = &self.operations[idx];
+        (Some(operation), &operation.request)
+    }
+
+    /// Values extracted by the connection take precedence over the data record.
+    fn variables<'a>(
+        &'a self,
+        extracted: &'a Variables,
+        record: Option<usize>,
+    ) -> Vec<&'a Variables> {
+        match (&self.data, record) {
+            (Some(data), Some(index)) => vec![extracted, data.record(index)],
+            _ => vec![extracted],
+        }
+    }
+
+    /// Stores extracted values in the connection variables. Returns `false` if any value is missing.
+    fn extract(client: &HttpClient, extractors: &[Extractor], parts: &Parts, body: &[u8]) -> bool {
+        if extractors.is_empty() {
+            return true;
+        }
+
+        let mut extracted = client
+            .extracted
+            .lock()
+            .expect("Extracted variables lock is poisoned");
+        let mut extracted_all = true;
+        for extractor in extractors {
+            match extractor.extract(&parts.headers, body) {
+                Some(value) => {
+                    extracted.insert(extractor.name().to_string(), value);
+                }
+                None => extracted_all = false,
+            }
+        }
+        extracted_all
+    }
+
+    #[cfg(not(feature = "scripting"))]
+    fn build_request(
+        &self,
+        client: &HttpClient,
+        request: &HttpRequest,
+        record: Option<usize>,
+    ) -> Result<Request<Body>, String> {
+        let extracted = client
+            .extracted
+            .lock()
+            .expect("Extracted variables lock is poisoned");
+        request.build_request_with_variables(&self.variables(&extracted, record))
+    }
+
+    #[cfg(feature = "scripting")]
+    fn build_request(
+        &self,
+        client: &HttpClient,
+        request: &HttpRequest,
+        record: Option<usize>,
+    ) -> Result<Request<Body>, String> {
+        let extracted = client
+            .extracted
+            .lock()
+            .expect("Extracted variables lock is poisoned");
+        let variables = self.variables(&extracted, record);
+        match self.script {
+            Some(ref script) => {
+                let scripted = script.request(
+                    &mut client
+                        .script_state
+                        .lock()
+                        .expect("Script state lock is poisoned"),
+                )?;
+                request.build_scripted_request(scripted, &variables)
+            }
+            None => request.build_request_with_variables(&variables),
+        }
+    }
+
+    /// If the response body must be kept to check the response.
+    #[cfg(not(feature = "scripting"))]
+    fn checks_response(&self) -> bool {
+        false
+    }
+
+    #[cfg(feature = "scripting")]
+    fn checks_response(&self) -> bool {
+        self.script.as_ref().is_some_and(|s| s.has_response_fn())
+    }
+
+    /// Returns the status and if the request succeeded.
+    #[cfg(not(feature = "scripting"))]
+    fn check_response(
+        &self,
+        _: &HttpClient,
+        assertions: &ResponseAssertions,
+        parts: &Parts,
+        _: &[u8],
+    ) -> (String, bool) {
+        (
+            parts.status.to_string(),
+            assertions.is_expected_status(parts.status),
+        )
+    }
+
+    #[cfg(feature = "scripting")]
+    fn check_response(
+        &self,
+        client: &HttpClient,
+        assertions: &ResponseAssertions,
+        parts: &Parts,
+        body: &[u8],
+    ) -> (String, bool) {
+        let expected_status = assertions.is_expected_status(parts.status);
+        let script = match self.script {
+            Some(ref script) if script.has_response_fn() => script,
+            _ => return (parts.status.to_string(), expected_status),
+        };
+
+        let headers = parts
+            .headers
+            .iter()
+            .map(|(k, v)| {
+                (
+                    k.to_string(),
+                    String::from_utf8_lossy(v.as_bytes()).to_string(),
+                )
+            })
+            .collect();
+        let result = script.response(
+            &mut client
+                .script_state
+                .lock()
+                .expect("Script state lock is poisoned"),
+            parts.status.as_u16(),
+            headers,
+            body,
+        );
+
+        match result {
+            Ok(true) => (parts.status.to_string(), true),
+            Ok(false) if expected_status => (REJECTED_STATUS.to_string(), false),
+            Ok(false) => (parts.status.to_string(), false),
+            Err(e) => {
+                error!("{}", e);
+                (e, false)
+            }
+        }
+    }
+
+    fn failed_request(
+        status: String,
+        start: Instant,
+        operation_name: Option<String>,
+    ) -> RequestStats {
+        RequestStatsBuilder::default()
+            .bytes_processed(0)
+            .status(status)
+            .is_success(false)
+            .duration(Instant::now().duration_since(start))
+            .fatal_error(false)
+            .operation_name(operation_name)
+            .build()
+            .expect("RequestStatsBuilder failed")
+    }
 }
 
 impl HttpRequest {
-    fn build_request(&self) -> Request<Body> {
+    pub(crate) fn build_request(&self) -> Request<Body> {
+        self.build_request_with_variables(&[])
+            .map_err(|e| println!("{}", e))
+            .expect("Error building Request")
+    }
+
+    /// `{{name}}` placeholders are bound to the given variables.
+    pub(crate) fn build_request_with_variables(
+        &self,
+        variables: &[&Variables],
+    ) -> Result<Request<Body>, String> {
         let method =
             Method::from_str(&self.method.clone()).expect("Method must be valid at this point");
 
-        let uri = &self.url[thread_rng().gen_range(0..self.url.len())];
-        let mut request_builder = Request::builder().method(method).uri(uri.clone());
+        let uri = self.random_url(variables);
+        let request_builder = self.request_builder(method, &uri, variables);
 
-        if !self.headers.is_empty() {
-            for (key, value) in self.headers.iter() {
-                request_builder = request_builder.header(
-                    HeaderName::from_str(key).expect("Header name must be valid at this point"),
-                    HeaderValue::from_str(&value[thread_rng().gen_range(0..value.len())])
-                        .expect("Header value must be valid at this point"),
+        let body = self.render_body(variables);
+
+        request_builder.body(body).map_err(|e| {
+            format!(
+                "Cannot create url {}, headers: {:?}. Error: {}",
+                uri, self.headers, e
+            )
+        })
+    }
+
+    /// Parts of the request, which aren't set by the script, are taken from the command line.
+    /// Headers of the script replace the same ones from the command line.
+    #[cfg(feature = "scripting")]
+    pub(crate) fn build_scripted_request(
+        &self,
+        scripted: ScriptedRequest,
+        variables: &[&Variables],
+    ) -> Result<Request<Body>, String> {
+        let method = match scripted.method {
+            Some(method) => Method::from_str(&method).map_err(|e| e.to_string())?,
+            None => Method::from_str(&self.method).expect("Method must be valid at this point"),
+        };
+        let uri = match scripted.url {
+            Some(url) => url,
+            None if self.url.is_empty() => {
+                return Err("Script must set `url` if there are no targets".to_string())
+            }
+            None => self.random_url(variables),
+        };
+        let mut request_builder = self.request_builder(method, &uri, variables);
+
+        if let Some(headers) = request_builder.headers_mut() {
+            for (key, value) in scripted.headers {
+                headers.insert(
+                    HeaderName::from_str(&key).map_err(|e| e.to_string())?,
+                    HeaderValue::from_str(&value).map_err(|e| e.to_string())?,
                 );
             }
         }
 
-        if !self.body.is_empty() {
-            request_builder
-                .body(Body::from(self.body.clone()))
-                .expect("Error building Request")
-        } else {
-            request_builder
-                .body(Body::empty())
-                .map_err(|e| {
-                    println!(
-                        "Cannot create url {}, headers: {:?}. Error: {}",
-                        uri, self.headers, e
-                    );
-                })
-                .expect("Error building Request")
+        let body = match scripted.body {
+            Some(body) => Body::from(body),
+            None => self.render_body(variables),
+        };
+        request_builder.body(body).map_err(|e| e.to_string())
+    }
+
+    fn render_body(&self, variables: &[&Variables]) -> Body {
+        match &self.templates.body {
+            _ if self.body.is_empty() => Body::empty(),
+            Some(template) => Body::from(template.render(variables)),
+            None => Body::from(self.body.clone()),
+        }
+    }
+
+    fn random_url(&self, variables: &[&Variables]) -> String {
+        let index = thread_rng().gen_range(0..self.url.len());
+        match self.templates.urls.get(index) {
+            Some(template) => template.render_str(variables),
+            None => self.url[index].clone(),
         }
     }
+
+    fn request_builder(&self, method: Method, uri: &str, variables: &[&Variables]) -> Builder {
+        let mut request_builder = Request::builder().method(method);
+
+        request_builder = match uri.strip_prefix(UNIX_URL_PREFIX) {
+            Some(unix_target) => {
+                let request_builder = request_builder.uri(HttpRequest::unix_uri(unix_target));
+                if self
+                    .headers
+                    .iter()
+                    .any(|(k, _)| k.eq_ignore_ascii_case("host"))
+                {
+                    request_builder
+                } else {
+                    request_builder.header(HOST, UNIX_DEFAULT_HOST)
+                }
+            }
+            None => request_builder.uri(uri),
+        };
+
+        if !self.headers.is_empty() {
+            for (i, (key, values)) in self.headers.iter().enumerate() {
+                let key =
+                    HeaderName::from_str(key).expect("Header name must be valid at this point");
+                let index = thread_rng().gen_range(0..values.len());
+                request_builder = match self.templates.headers.get(i).map(|t| &t[index]) {
+                    // rendered values are checked on building the request
+                    Some(template) => request_builder.header(key, template.render(variables)),
+                    None => request_builder.header(
+                        key,
+                        HeaderValue::from_str(&values[index])
+                            .expect("Header value must be valid at this point"),
+                    ),
+                };
+            }
+        }
+
+        request_builder
+    }
+}
+
+impl HttpRequest {
+    /// Splits `/path/to.sock:/request/path` into the socket and the request path (`/` by default).
+    #[cfg(unix)]
+    fn unix_uri(unix_target: &str) -> hyper::Uri {
+        let (socket, path) = match unix_target.find(":/") {
+            Some(idx) => (&unix_target[..idx], &unix_target[idx + 1..]),
+            None => (unix_target, "/"),
+        };
+        hyperlocal::Uri::new(socket, path).into()
+    }
+
+    #[cfg(not(unix))]
+    fn unix_uri(_: &str) -> hyper::Uri {
+        unreachable!("Unix domain sockets are rejected on validation")
+    }
 }
 
 impl HttpRequestBuilder {
     /// Validate request is going to be built from the given settings
     fn validate(&self) -> Result<(), String> {
+        if cfg!(not(unix)) {
+            if let Some(ref urls) = self.url {
+                if urls.iter().any(|u| u.starts_with(UNIX_URL_PREFIX)) {
+                    return Err(
+                        "Unix domain sockets are not supported on this platform".to_string()
+                    );
+                }
+            }
+        }
+
+        self.parse_templates()?;
+
         if let Some(ref m) = self.method {
             Method::from_str(m).map_err(|e| e.to_string()).map(|_| ())
         } else {
             Ok(())
         }
     }
+
+    /// Sends `{{...}}` in the URL, header values and body as is, e.g. for Mustache templates
+    /// or GraphQL queries.
+    pub fn no_template(&mut self, no_template: bool) -> &mut Self {
+        if no_template {
+            self.templated(false).templated_body(false);
+        }
+        self
+    }
+
+    fn parse_templates(&self) -> Result<RequestTemplates, String> {
+        let variables = self.variables.clone().unwrap_or_default();
+        let parse = |part: &str| Template::parse(part.as_bytes(), &variables);
+        let mut templates = RequestTemplates::default();
+        if self.templated.unwrap_or_else(|| self.has_placeholders()) {
+            templates.urls = self
+                .url
+                .iter()
+                .flatten()
+                .map(|u| parse(u))
+                .collect::<Result<_, _>>()
+                .map_err(|e| format!("Invalid template: {}", e))?;
+            templates.headers = self
+                .headers
+                .iter()
+                .flatten()
+                .map(|(_, values)| values.iter().map(|v| parse(v)).collect())
+                .collect::<Result<_, _>>()
+                .map_err(|e| format!("Invalid template: {}", e))?;
+        }
+        if self
+            .templated_body
+            .unwrap_or_else(|| self.has_body_placeholders())
+        {
+            if let Some(ref body) = self.body {
+                templates.body = Some(
+                    Template::parse(body, &variables)
+                        .map_err(|e| format!("Invalid body template: {}", e))?,
+                );
+            }
+        }
+        Ok(templates)
+    }
+
+    fn has_placeholders(&self) -> bool {
+        self.template_parts().any(template::has_placeholders)
+    }
+
+    /// Binary bodies (e.g. `random://`) may contain `{{` by chance.
+    fn has_body_placeholders(&self) -> bool {
+        self.body
+            .as_ref()
+            .is_some_and(|b| std::str::from_utf8(b).is_ok() && template::has_placeholders(b))
+    }
+
+    /// URLs and header values.
+    fn template_parts(&self) -> impl Iterator<Item = &[u8]> {
+        let urls = self.url.iter().flatten().map(|u| u.as_bytes());
+        let header_values = self
+            .headers
+            .iter()
+            .flatten()
+            .flat_map(|(_, values)| values.iter().map(|v| v.as_bytes()));
+        urls.chain(header_values)
+    }
+}
+
+impl HttpBenchAdapterBuilder {
+    /// Either a single request or named operations must be set.
+    fn validate(&self) -> Result<(), String> {
+        let has_request = self.request.as_ref().is_some_and(Option::is_some);
+        let has_operations = self.operations.as_ref().is_some_and(|o| !o.is_empty());
+        if has_request == has_operations {
+            return Err("Either a request or operations must be set".to_string());
+        }
+        if has_operations
+            && self.operation_order == Some(OperationOrder::Weighted)
+            && self.operations.iter().flatten().all(|o| o.weight == 0)
+        {
+            return Err("At least one operation must have a positive weight".to_string());
+        }
+        Ok(())
+    }
 }
 
 impl fmt::Display for HttpRequest {
@@ -220,7 +845,7 @@ impl fmt::Display for HttpRequest {
             f,
             "Requests={}, first request={}, method={}, headers={:?}, body size={}",
             self.url.len(),
-            self.url[0],
+            self.url.first().map_or("", String::as_str),
             self.method,
             self.headers,
             self.body.len()
@@ -230,19 +855,36 @@ impl fmt::Display for HttpRequest {
 
 impl fmt::Display for HttpBenchAdapter {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        writeln!(f, "Config={:?}, Request={}", self.config, self.request)
+        match self.request {
+            Some(ref request) => writeln!(f, "Config={:?}, Request={}", self.config, request),
+            None => writeln!(
+                f,
+                "Config={:?}, Operations={}, order={:?}",
+                self.config,
+                self.operations.len(),
+                self.operation_order
+            ),
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::bench_run::BenchmarkProtocolAdapter;
+    use crate::assertion::ResponseAssertions;
+    use crate::bench_run::{BenchRun, BenchmarkProtocolAdapter};
+    use crate::data_feeder::{DataFeeder, FeedExhausted, FeedStrategy};
+    use crate::extractor::{Extractor, EXTRACTION_FAILED_STATUS};
     use crate::http_bench_session::{
-        HttpBenchAdapter, HttpBenchAdapterBuilder, HttpClientConfigBuilder, HttpRequestBuilder,
+        HttpBenchAdapter, HttpBenchAdapterBuilder, HttpClientConfigBuilder, HttpOperationBuilder,
+        HttpRequestBuilder, OperationOrder,
     };
+    use crate::http_timing::{BODY_PHASE, CONNECT_PHASE, DNS_PHASE, TTFB_PHASE};
+    use crate::metrics::RequestStats;
+    use crate::rate_limiter::{ArrivalProcess, RateLimiter};
     use mockito::mock;
-    use mockito::Matcher::Exact;
+    use mockito::Matcher::{Exact, Regex};
     use std::time::Duration;
+    use tokio::sync::mpsc;
     use tokio::time::timeout;
 
     #[tokio::test]
@@ -439,6 +1081,636 @@ mod tests {
         assert_eq!("500 Internal Server Error".to_string(), stats.status);
     }
 
+    #[tokio::test]
+    async fn test_phases() {
+        let _m = mock("GET", "/phases")
+            .with_status(200)
+            .with_body("world")
+            .expect(2)
+            .create();
+
+        let url = mockito::server_url().to_string();
+        let phases =
+            |stats: &RequestStats| stats.phases.iter().map(|(p, _)| *p).collect::<Vec<_>>();
+        // IP addresses are not resolved
+        for (host, expected) in [
+            (
+                "localhost",
+                vec![DNS_PHASE, CONNECT_PHASE, TTFB_PHASE, BODY_PHASE],
+            ),
+            ("127.0.0.1", vec![CONNECT_PHASE, TTFB_PHASE, BODY_PHASE]),
+        ] {
+            let http_bench = HttpBenchAdapterBuilder::default()
+                .request(
+                    HttpRequestBuilder::default()
+                        .url(vec![format!("{}/phases", url.replace("127.0.0.1", host))])
+                        .build()
+                        .unwrap(),
+                )
+                .config(HttpClientConfigBuilder::default().build().unwrap())
+                .build()
+                .unwrap();
+
+            let client = http_bench.build_client().expect("Client is built");
+            let stats = http_bench.send_request(&client).await;
+
+            assert!(stats.is_success, "{:?}", stats);
+            assert_eq!(expected, phases(&stats));
+            let total: Duration = stats.phases.iter().map(|(_, d)| *d).sum();
+            assert!(total <= stats.duration);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_templated_request() {
+        let _m = mock(
+            "POST",
+            Regex(r"^/item/[0-9]{1,2}\?key=[0-9a-f-]{36}$".to_string()),
+        )
+        .match_header("x-request-id", Regex("^[a-zA-Z0-9]{8}$".to_string()))
+        .match_body(Regex(r"^\{\x22seq\x22:[0-9]+\}$".to_string()))
+        .with_status(200)
+        .with_body("ok")
+        .expect(3)
+        .create();
+
+        let url = mockito::server_url().to_string();
+        let request = HttpRequestBuilder::default()
+            .url(vec![format!(
+                "{}/item/{{{{random_int(1,99)}}}}?key={{{{uuid}}}}",
+                url
+            )])
+            .method("POST".to_string())
+            .headers(vec![(
+                "x-request-id".to_string(),
+                vec!["{{random_string(8)}}".to_string()],
+            )])
+            .body(b"{\"seq\":{{seq}}}".to_vec())
+            .build()
+            .unwrap();
+        assert!(request.templated);
+        assert!(request.templated_body);
+        // parsed on building
+        assert_eq!(1, request.templates.urls.len());
+        assert_eq!(1, request.templates.headers[0].len());
+        assert!(request.templates.body.is_some());
+
+        let http_bench = HttpBenchAdapterBuilder::default()
+            .request(request)
+            .config(HttpClientConfigBuilder::default().build().unwrap())
+            .build()
+            .unwrap();
+
+        let client = http_bench.build_client().expect("Client is built");
+        for _ in 0..3 {
+            let stats = http_bench.send_request(&client).await;
+            println!("{:?}", stats);
+            assert_eq!("200 OK".to_string(), stats.status);
+        }
+
+        _m.assert();
+
+        assert!(HttpRequestBuilder::default()
+            .url(vec![format!("{}/{{{{unknown}}}}", url)])
+            .build()
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_operations() {
+        let _m1 = mock("GET", "/login").with_status(200).expect(2).create();
+        let _m2 = mock("POST", "/items").with_status(201).expect(2).create();
+
+        let url = mockito::server_url().to_string();
+        let operation = |name: &str, method: &str, path: &str, weight: u32| {
+            HttpOperationBuilder::default()
+                .name(name.to_string())
+                .weight(weight)
+                .request(
+                    HttpRequestBuilder::default()
+                        .url(vec![format!("{}{}", url, path)])
+                        .method(method.to_string())
+                        .build()
+                        .unwrap(),
+                )
+                .build()
+                .unwrap()
+        };
+
+        let http_bench = HttpBenchAdapterBuilder::default()
+            .operations(vec![
+                operation("login", "GET", "/login", 1),
+                operation("create", "POST", "/items", 1),
+            ])
+            .config(HttpClientConfigBuilder::default().build().unwrap())
+            .build()
+            .unwrap();
+
+        let client = http_bench.build_client().expect("Client is built");
+        let mut names = vec![];
+        for _ in 0..4 {
+            let stats = http_bench.send_request(&client).await;
+            assert!(stats.is_success);
+            names.push(stats.operation_name.unwrap());
+        }
+        assert_eq!(vec!["login", "create", "login", "create"], names);
+        _m1.assert();
+        _m2.assert();
+
+        let http_bench = HttpBenchAdapterBuilder::default()
+            .operations(vec![
+                operation("never", "GET", "/never", 0),
+                operation("login", "GET", "/login", 3),
+            ])
+            .operation_order(OperationOrder::Weighted)
+            .config(HttpClientConfigBuilder::default().build().unwrap())
+            .build()
+            .unwrap();
+        let client = http_bench.build_client().expect("Client is built");
+        for _ in 0..10 {
+            let (operation, _) = http_bench.next_request(&client);
+            assert_eq!(Some("login"), operation.map(|o| o.name.as_str()));
+        }
+
+        assert!(HttpBenchAdapterBuilder::default()
+            .config(HttpClientConfigBuilder::default().build().unwrap())
+            .build()
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_scenario_with_extraction() {
+        let _m1 = mock("POST", "/login")
+            .with_status(200)
+            .with_header("set-cookie", "session=s1; HttpOnly")
+            .with_body(r#"{"token": "abc"}"#)
+            .expect(1)
+            .create();
+        let _m2 = mock("GET", "/profile")
+            .match_header("authorization", "Bearer abc")
+            .match_header("cookie", "session=s1")
+            .with_status(200)
+            .expect(1)
+            .create();
+        let _m3 = mock("POST", "/refresh")
+            .with_status(200)
+            .with_body("{}")
+            .expect(1)
+            .create();
+
+        let url = mockito::server_url().to_string();
+        let operation =
+            |name: &str, method: &str, headers: Vec<(&str, &str)>, extractors: Vec<Extractor>| {
+                HttpOperationBuilder::default()
+                    .name(name.to_string())
+                    .extractors(extractors)
+                    .request(
+                        HttpRequestBuilder::default()
+                            .url(vec![format!("{}/{}", url, name)])
+                            .method(method.to_string())
+                            .headers(
+                                headers
+                                    .into_iter()
+                                    .map(|(k, v)| (k.to_string(), vec![v.to_string()]))
+                                    .collect(),
+                            )
+                            .variables(vec!["token".to_string(), "session".to_string()])
+                            .build()
+                            .unwrap(),
+                    )
+                    .build()
+                    .unwrap()
+            };
+
+        let http_bench = HttpBenchAdapterBuilder::default()
+            .operations(vec![
+                operation(
+                    "login",
+                    "POST",
+                    vec![],
+                    vec![
+                        Extractor::json_path("token", "$.token").unwrap(),
+                        Extractor::header("session", "set-cookie", Some("session=([^;]+)"))
+                            .unwrap(),
+                    ],
+                ),
+                operation(
+                    "profile",
+                    "GET",
+                    vec![
+                        ("authorization", "Bearer {{token}}"),
+                        ("cookie", "session={{session}}"),
+                    ],
+                    vec![],
+                ),
+                operation(
+                    "refresh",
+                    "POST",
+                    vec![],
+                    vec![Extractor::json_path("token", "$.token").unwrap()],
+                ),
+            ])
+            .config(HttpClientConfigBuilder::default().build().unwrap())
+            .build()
+            .unwrap();
+
+        let client = http_bench.build_client().expect("Client is built");
+        let statuses: Vec<String> = vec![
+            http_bench.send_request(&client).await.status,
+            http_bench.send_request(&client).await.status,
+            http_bench.send_request(&client).await.status,
+        ];
+        assert_eq!(
+            vec![
+                "200 OK".to_string(),
+                "200 OK".to_string(),
+                EXTRACTION_FAILED_STATUS.to_string()
+            ],
+            statuses
+        );
+
+        _m1.assert();
+        _m2.assert();
+        _m3.assert();
+    }
+
+    #[tokio::test]
+    async fn test_response_assertions() {
+        let _m1 = mock("GET", "/ok")
+            .with_status(200)
+            .with_header("x-request-id", "1")
+            .with_body(r#"{"status": "ok"}"#)
+            .create();
+        let _m2 = mock("GET", "/error-page")
+            .with_status(200)
+            .with_body("<html>Bad gateway</html>")
+            .create();
+        let _m3 = mock("GET", "/missing").with_status(404).create();
+
+        let mut assertions = ResponseAssertions::default();
+        assertions
+            .expect_status(vec![200])
+            .header_present("x-request-id");
+        assertions.json_equals("$.status=ok").unwrap();
+
+        let url = mockito::server_url().to_string();
+        let send = |path: &str| {
+            let http_bench = HttpBenchAdapterBuilder::default()
+                .request(
+                    HttpRequestBuilder::default()
+                        .url(vec![format!("{}{}", url, path)])
+                        .build()
+                        .unwrap(),
+                )
+                .config(HttpClientConfigBuilder::default().build().unwrap())
+                .assertions(assertions.clone())
+                .build()
+                .unwrap();
+            async move {
+                let client = http_bench.build_client().expect("Client is built");
+                http_bench.send_request(&client).await
+            }
+        };
+
+        let stats = send("/ok").await;
+        assert!(stats.is_success);
+        assert_eq!("200 OK", stats.status);
+
+        let stats = send("/error-page").await;
+        assert!(!stats.is_success);
+        assert_eq!("Assertion failed: header x-request-id", stats.status);
+
+        let stats = send("/missing").await;
+        assert!(!stats.is_success);
+        assert_eq!("404 Not Found", stats.status);
+    }
+
+    #[tokio::test]
+    async fn test_data_bound_request() {
+        let _m1 = mock("GET", "/users/alice")
+            .match_header("authorization", "Bearer t1")
+            .with_status(200)
+            .expect(1)
+            .create();
+        let _m2 = mock("GET", "/users/bob")
+            .match_header("authorization", "Bearer t2")
+            .with_status(200)
+            .expect(1)
+            .create();
+
+        let records = vec![("alice", "t1"), ("bob", "t2")]
+            .into_iter()
+            .map(|(user, token)| {
+                vec![
+                    ("user".to_string(), user.to_string()),
+                    ("token".to_string(), token.to_string()),
+                ]
+                .into_iter()
+                .collect()
+            })
+            .collect();
+        let data =
+            DataFeeder::new(records, FeedStrategy::Sequential, FeedExhausted::Stop, 1).unwrap();
+
+        let url = mockito::server_url().to_string();
+        let http_bench = HttpBenchAdapterBuilder::default()
+            .request(
+                HttpRequestBuilder::default()
+                    .url(vec![format!("{}/users/{{{{user}}}}", url)])
+                    .headers(vec![(
+                        "authorization".to_string(),
+                        vec!["Bearer {{token}}".to_string()],
+                    )])
+                    .variables(data.columns().to_vec())
+                    .build()
+                    .unwrap(),
+            )
+            .config(HttpClientConfigBuilder::default().build().unwrap())
+            .data(Some(data))
+            .build()
+            .unwrap();
+
+        let client = http_bench.build_client().expect("Client is built");
+        for _ in 0..2 {
+            assert!(http_bench.has_more_work(&client));
+            let stats = http_bench.send_request(&client).await;
+            assert_eq!("200 OK".to_string(), stats.status);
+        }
+        assert!(!http_bench.has_more_work(&client));
+
+        // the next batch starts over
+        let next_batch = http_bench.for_batch(1);
+        let client = next_batch.build_client().expect("Client is built");
+        assert!(next_batch.has_more_work(&client));
+
+        _m1.assert();
+        _m2.assert();
+    }
+
+    #[tokio::test]
+    async fn test_literal_placeholders() {
+        let body = r#"{"query":"{ user(id: 1) {{name}} }"}"#;
+        let _m = mock("POST", "/literal/{{id}}")
+            .match_body(Exact(body.to_string()))
+            .with_status(200)
+            .expect(1)
+            .create();
+
+        let url = format!("{}/literal/{{{{id}}}}", mockito::server_url());
+        let build = |no_template| {
+            HttpRequestBuilder::default()
+                .url(vec![url.clone()])
+                .method("POST".to_string())
+                .body(body.as_bytes().to_vec())
+                .no_template(no_template)
+                .build()
+        };
+        assert!(build(false).is_err());
+
+        let request = build(true).unwrap();
+        assert!(!request.templated);
+        assert!(!request.templated_body);
+
+        let http_bench = HttpBenchAdapterBuilder::default()
+            .request(request)
+            .config(HttpClientConfigBuilder::default().build().unwrap())
+            .build()
+            .unwrap();
+        let client = http_bench.build_client().expect("Client is built");
+        let stats = http_bench.send_request(&client).await;
+        assert_eq!("200 OK".to_string(), stats.status);
+
+        _m.assert();
+    }
+
+    #[tokio::test]
+    async fn test_open_model_data_records() {
+        let mocks: Vec<_> = (0..5)
+            .map(|i| {
+                mock("GET", format!("/open/items/{}", i).as_str())
+                    .with_status(200)
+                    .expect(1)
+                    .create()
+            })
+            .collect();
+
+        let records = (0..5)
+            .map(|i| {
+                vec![("id".to_string(), i.to_string())]
+                    .into_iter()
+                    .collect()
+            })
+            .collect();
+        let data =
+            DataFeeder::new(records, FeedStrategy::Sequential, FeedExhausted::Stop, 1).unwrap();
+
+        let url = mockito::server_url().to_string();
+        let http_bench = HttpBenchAdapterBuilder::default()
+            .request(
+                HttpRequestBuilder::default()
+                    .url(vec![format!("{}/open/items/{{{{id}}}}", url)])
+                    .variables(data.columns().to_vec())
+                    .build()
+                    .unwrap(),
+            )
+            .config(HttpClientConfigBuilder::default().build().unwrap())
+            .data(Some(data))
+            .build()
+            .unwrap();
+
+        let run = BenchRun::from_request_limit(
+            0,
+            100,
+            RateLimiter::build_rate_limiter(0., ArrivalProcess::Constant),
+            None,
+        )
+        .with_max_in_flight(4);
+        let (sender, mut receiver) = mpsc::channel(100);
+        run.send_load(&http_bench, sender).await.unwrap();
+        let mut statuses = vec![];
+        while let Some(stats) = receiver.recv().await {
+            statuses.push(stats.status);
+        }
+        assert_eq!(vec!["200 OK".to_string(); 5], statuses);
+
+        for m in mocks {
+            m.assert();
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_unix_socket_request() {
+        use hyper::service::{make_service_fn, service_fn};
+        use hyper::{Body, Request, Response, Server};
+        use std::convert::Infallible;
+        use tokio::net::UnixListener;
+
+        let socket = std::env::temp_dir().join(format!("perf-gauge-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&socket);
+        let listener = UnixListener::bind(&socket).unwrap();
+
+        let make_service = make_service_fn(|_| async {
+            Ok::<_, Infallible>(service_fn(|request: Request<Body>| async move {
+                let reply = format!(
+                    "{} {}",
+                    request.headers().get("host").unwrap().to_str().unwrap(),
+                    request.uri().path()
+                );
+                Ok::<_, Infallible>(Response::new(Body::from(reply)))
+            }))
+        });
+        let incoming = hyper::server::accept::poll_fn(move |cx| {
+            listener
+                .poll_accept(cx)
+                .map(|r| Some(r.map(|(stream, _)| stream)))
+        });
+        tokio::spawn(Server::builder(incoming).serve(make_service));
+
+        let test = vec![
+            (
+                format!("unix://{}", socket.display()),
+                vec![],
+                "localhost /",
+            ),
+            (
+                format!("unix://{}:/api/v1", socket.display()),
+                vec![("Host".to_string(), vec!["my-service".to_string()])],
+                "my-service /api/v1",
+            ),
+        ];
+
+        for (url, headers, expected_reply) in test {
+            let http_bench = HttpBenchAdapterBuilder::default()
+                .request(
+                    HttpRequestBuilder::default()
+                        .url(vec![url])
+                        .headers(headers)
+                        .build()
+                        .unwrap(),
+                )
+                .config(HttpClientConfigBuilder::default().build().unwrap())
+                .build()
+                .unwrap();
+
+            let client = http_bench.build_client().expect("Client is built");
+            let stats = http_bench.send_request(&client).await;
+
+            println!("{:?}", stats);
+            assert_eq!("200 OK".to_string(), stats.status);
+            assert_eq!(expected_reply.len(), stats.bytes_processed);
+        }
+
+        let _ = std::fs::remove_file(&socket);
+    }
+
+    #[cfg(feature = "scripting")]
+    #[tokio::test]
+    async fn test_scripted_request_templated_body() {
+        use crate::script::RequestScript;
+
+        let _m = mock("POST", "/scripted/body")
+            .match_body(Exact("{\"id\":\"alice\"}".to_string()))
+            .with_status(200)
+            .expect(1)
+            .create();
+
+        let url = mockito::server_url().to_string();
+        let script = format!(
+            r#"fn request() {{ #{{ method: "POST", url: "{}/scripted/body" }} }}"#,
+            url
+        );
+
+        let data = DataFeeder::new(
+            vec![vec![("user".to_string(), "alice".to_string())]
+                .into_iter()
+                .collect()],
+            FeedStrategy::Sequential,
+            FeedExhausted::Stop,
+            1,
+        )
+        .unwrap();
+        let http_bench = HttpBenchAdapterBuilder::default()
+            .request(
+                HttpRequestBuilder::default()
+                    .url(vec![])
+                    .body(b"{\"id\":\"{{user}}\"}".to_vec())
+                    .variables(data.columns().to_vec())
+                    .build()
+                    .unwrap(),
+            )
+            .config(HttpClientConfigBuilder::default().build().unwrap())
+            .script(Some(RequestScript::compile(&script).unwrap()))
+            .data(Some(data))
+            .build()
+            .unwrap();
+
+        let client = http_bench.build_client().expect("Client is built");
+        assert!(http_bench.has_more_work(&client));
+        let stats = http_bench.send_request(&client).await;
+        assert_eq!("200 OK".to_string(), stats.status);
+
+        _m.assert();
+    }
+
+    #[cfg(feature = "scripting")]
+    #[tokio::test]
+    async fn test_scripted_request() {
+        use crate::script::{RequestScript, REJECTED_STATUS};
+
+        let _m = mock("POST", "/scripted/1")
+            .match_header("x-header", "value1")
+            .match_header("x-seq", "1")
+            .match_body(Exact("abcd".to_string()))
+            .with_status(200)
+            .with_body("accepted")
+            .create();
+        let _m2 = mock("POST", "/scripted/2")
+            .match_header("x-seq", "2")
+            .with_status(200)
+            .with_body("rejected")
+            .create();
+
+        let url = mockito::server_url().to_string();
+        let script = format!(
+            r#"
+            fn request() {{
+                if this.seq == () {{ this.seq = 0; }}
+                this.seq += 1;
+                #{{ method: "POST", url: `{}/scripted/${{this.seq}}`, headers: #{{ "x-seq": this.seq }} }}
+            }}
+            fn response(status, headers, body) {{ body == "accepted" }}
+            "#,
+            url
+        );
+
+        let http_bench = HttpBenchAdapterBuilder::default()
+            .request(
+                HttpRequestBuilder::default()
+                    .url(vec![])
+                    .headers(vec![("x-header".to_string(), vec!["value1".to_string()])])
+                    .body("abcd".as_bytes().to_vec())
+                    .build()
+                    .unwrap(),
+            )
+            .config(HttpClientConfigBuilder::default().build().unwrap())
+            .script(Some(RequestScript::compile(&script).unwrap()))
+            .build()
+            .unwrap();
+
+        let client = http_bench.build_client().expect("Client is built");
+        let stats = http_bench.send_request(&client).await;
+        println!("{:?}", stats);
+        assert!(stats.is_success);
+        assert_eq!("200 OK".to_string(), stats.status);
+
+        let stats = http_bench.send_request(&client).await;
+        println!("{:?}", stats);
+        assert!(!stats.is_success);
+        assert_eq!(REJECTED_STATUS.to_string(), stats.status);
+        assert_eq!(8, stats.bytes_processed);
+    }
+
     #[tokio::test]
     async fn test_only_http2() {
         let body = "world";
@@ -0,0 +1,387 @@
+/// Copyright 2020 Developers of the perf-gauge project.
+///
+/// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+/// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+/// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+/// option. This file may not be copied, modified, or distributed
+/// except according to those terms.
+use crate::assertion::ResponseAssertions;
+use crate::extractor::Extractor;
+use crate::http_bench_session::{HttpOperation, HttpOperationBuilder, HttpRequestBuilder};
+use serde::{Deserialize, Deserializer};
+use std::collections::BTreeMap;
+use std::fs;
+use std::fs::File;
+use std::io::{BufReader, Read};
+
+/// A named request in an operations file, e.g.
+/// ```yaml
+/// - name: search
+///   weight: 70
+///   url: http://localhost:8080/search?q={{random_string(3)}}
+///   headers:
+///     Accept: application/json
+/// ```
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct OperationSpec {
+    name: String,
+    #[serde(default = "OperationSpec::default_weight")]
+    weight: u32,
+    #[serde(default = "OperationSpec::default_method")]
+    method: String,
+    url: String,
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    body: Option<String>,
+    body_file: Option<String>,
+    /// Variables to extract from successful responses, by name.
+    #[serde(default)]
+    extract: BTreeMap<String, ExtractSpec>,
+    /// Replaces the command line assertions for the operation.
+    expect: Option<ExpectSpec>,
+}
+
+/// Response assertions, the same as `--expect_*` command line options.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ExpectSpec {
+    #[serde(default)]
+    status: Vec<u16>,
+    /// Like the other lists, but a single value is accepted too, e.g. `body_contains: ok`.
+    #[serde(default, deserialize_with = "deserialize_one_or_many")]
+    body_contains: Vec<String>,
+    #[serde(default, deserialize_with = "deserialize_one_or_many")]
+    body_regex: Vec<String>,
+    /// `PATH=VALUE` expressions.
+    #[serde(default)]
+    json: Vec<String>,
+    #[serde(default)]
+    header: Vec<String>,
+    min_body_size: Option<usize>,
+    max_body_size: Option<usize>,
+    sha256: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum OneOrMany {
+    One(String),
+    Many(Vec<String>),
+}
+
+fn deserialize_one_or_many<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<String>, D::Error> {
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(value) => vec![value],
+        OneOrMany::Many(values) => values,
+    })
+}
+
+impl ExpectSpec {
+    fn build(&self) -> Result<ResponseAssertions, String> {
+        let mut assertions = ResponseAssertions::default();
+        assertions.expect_status(self.status.clone());
+        for text in self.body_contains.iter() {
+            assertions.body_contains(text);
+        }
+        for regex in self.body_regex.iter() {
+            assertions.body_matches(regex)?;
+        }
+        for expression in self.json.iter() {
+            assertions.json_equals(expression)?;
+        }
+        for header in self.header.iter() {
+            assertions.header_present(header);
+        }
+        if let Some(size) = self.min_body_size {
+            assertions.min_body_size(size);
+        }
+        if let Some(size) = self.max_body_size {
+            assertions.max_body_size(size);
+        }
+        if let Some(ref hex) = self.sha256 {
+            assertions.sha256(hex)?;
+        }
+        Ok(assertions)
+    }
+}
+
+/// Either `json_path` or `regex` in the body, or `header` (optionally matched by `regex`).
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ExtractSpec {
+    json_path: Option<String>,
+    regex: Option<String>,
+    header: Option<String>,
+}
+
+impl ExtractSpec {
+    fn build(&self, name: &str) -> Result<Extractor, String> {
+        match (&self.json_path, &self.regex, &self.header) {
+            (None, regex, Some(header)) => Extractor::header(name, header, regex.as_deref()),
+            (Some(path), None, None) => Extractor::json_path(name, path),
+            (None, Some(regex), None) => Extractor::regex(name, regex),
+            _ => Err(format!(
+                "`{}` must have either `json_path`, `regex` or `header`",
+                name
+            )),
+        }
+    }
+}
+
+impl OperationSpec {
+    fn default_weight() -> u32 {
+        1
+    }
+
+    fn default_method() -> String {
+        "GET".to_string()
+    }
+}
+
+/// Reads a YAML (or JSON) list of named requests.
+/// Extracted values and `variables` can be used in `{{name}}` placeholders of the requests (e.g. data columns),
+/// unless `no_template`.
+pub fn read_operations(
+    path: &str,
+    variables: &[String],
+    no_template: bool,
+) -> Result<Vec<HttpOperation>, String> {
+    let file = File::open(path).map_err(|e| format!("Cannot open {}: {}", path, e))?;
+    parse_operations(BufReader::new(file), variables, no_template)
+}
+
+fn parse_operations(
+    input: impl Read,
+    variables: &[String],
+    no_template: bool,
+) -> Result<Vec<HttpOperation>, String> {
+    let specs: Vec<OperationSpec> =
+        serde_yaml::from_reader(input).map_err(|e| format!("Invalid operations: {}", e))?;
+    if specs.is_empty() {
+        return Err("No operations".to_string());
+    }
+
+    let mut variables = variables.to_vec();
+    variables.extend(specs.iter().flat_map(|s| s.extract.keys().cloned()));
+
+    specs
+        .into_iter()
+        .map(|spec| {
+            let name = spec.name;
+            let body = match (spec.body, spec.body_file) {
+                (Some(_), Some(_)) => {
+                    return Err(format!("{}: either `body` or `body_file`", name))
+                }
+                (Some(body), None) => body.into_bytes(),
+                (None, Some(file)) => {
+                    fs::read(&file).map_err(|e| format!("{}: cannot read {}: {}", name, file, e))?
+                }
+                (None, None) => vec![],
+            };
+
+            let request = HttpRequestBuilder::default()
+                .url(vec![spec.url])
+                .method(spec.method)
+                .headers(
+                    spec.headers
+                        .into_iter()
+                        .map(|(name, value)| (name, vec![value]))
+                        .collect(),
+                )
+                .body(body)
+                .variables(variables.clone())
+                .no_template(no_template)
+                .build()
+                .map_err(|e| format!("{}: {}", name, e))?;
+            let extractors = spec
+                .extract
+                .iter()
+                .map(|(variable, extract)| extract.build(variable))
+                .collect::<Result<_, _>>()
+                .map_err(|e| format!("{}: {}", name, e))?;
+            let assertions = spec
+                .expect
+                .as_ref()
+                .map(ExpectSpec::build)
+                .transpose()
+                .map_err(|e| format!("{}: {}", name, e))?;
+
+            HttpOperationBuilder::default()
+                .name(name)
+                .weight(spec.weight)
+                .request(request)
+                .extractors(extractors)
+                .assertions(assertions)
+                .build()
+                .map_err(|e| e.to_string())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::http_operations::parse_operations;
+    use hyper::HeaderMap;
+
+    #[tokio::test]
+    async fn test_parse_operations() {
+        let operations = parse_operations(
+            r#"
+- name: login
+  weight: 5
+  method: POST
+  url: http://localhost:8080/login
+  headers:
+    Content-Type: application/json
+  body: '{"user": "{{user}}"}'
+- name: search
+  weight: 70
+  url: http://localhost:8080/search?q={{random_string(3)}}
+- name: health
+  url: http://localhost:8080/health
+"#
+            .as_bytes(),
+            &["user".to_string()],
+            false,
+        )
+        .unwrap();
+
+        let summary: Vec<(&str, u32)> = operations
+            .iter()
+            .map(|o| (o.name.as_str(), o.weight))
+            .collect();
+        assert_eq!(vec![("login", 5), ("search", 70), ("health", 1)], summary);
+
+        let login = operations[0].request.build_request();
+        assert_eq!("POST", login.method());
+        assert_eq!("application/json", login.headers()["content-type"]);
+        let body = hyper::body::to_bytes(login.into_body()).await.unwrap();
+        assert_eq!(b"{\"user\": \"\"}".to_vec(), body);
+
+        let search = operations[1].request.build_request();
+        assert_eq!("GET", search.method());
+        assert_eq!(3, search.uri().query().unwrap().len() - "q=".len());
+    }
+
+    #[test]
+    fn test_parse_scenario() {
+        let operations = parse_operations(
+            r#"
+- name: login
+  method: POST
+  url: http://localhost:8080/login
+  extract:
+    token: {json_path: $.token}
+    session: {header: Set-Cookie, regex: 'session=([^;]+)'}
+  expect:
+    status: [200]
+    header: [Set-Cookie]
+- name: profile
+  url: http://localhost:8080/profile
+  headers:
+    Authorization: Bearer {{token}}
+    Cookie: session={{session}}
+"#
+            .as_bytes(),
+            &[],
+            false,
+        )
+        .unwrap();
+
+        let names: Vec<&str> = operations[0].extractors.iter().map(|e| e.name()).collect();
+        assert_eq!(vec!["session", "token"], names);
+        assert!(operations[1].extractors.is_empty());
+        assert!(operations[0].assertions.is_some());
+        assert!(operations[1].assertions.is_none());
+
+        let invalid = |extract: &str| {
+            parse_operations(
+                format!(
+                    "- {{name: a, url: 'http://a/', extract: {{v: {}}}}}",
+                    extract
+                )
+                .as_bytes(),
+                &[],
+                false,
+            )
+            .is_err()
+        };
+        assert!(invalid("{}"));
+        assert!(invalid("{json_path: $.a, regex: a}"));
+        assert!(invalid("{json_path: a}"));
+        assert!(invalid("{regex: '('}"));
+        assert!(!invalid("{regex: '[0-9]+'}"));
+
+        assert!(parse_operations(
+            "- {name: a, url: 'http://a/', expect: {json: ['$.a']}}".as_bytes(),
+            &[],
+            false
+        )
+        .is_err());
+        assert!(parse_operations(
+            "- {name: a, url: 'http://a/', expect: {status: [200], typo: 1}}".as_bytes(),
+            &[],
+            false
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_parse_expect() {
+        let operations = parse_operations(
+            r#"
+- name: single
+  url: http://localhost:8080/
+  expect: {body_contains: ok, body_regex: '[0-9]+'}
+- name: list
+  url: http://localhost:8080/
+  expect: {body_contains: [ok, done], body_regex: ['[0-9]+', total]}
+"#
+            .as_bytes(),
+            &[],
+            false,
+        )
+        .unwrap();
+
+        let check = |i: usize, body: &[u8]| {
+            operations[i]
+                .assertions
+                .as_ref()
+                .unwrap()
+                .check(&HeaderMap::new(), body)
+                .is_ok()
+        };
+        assert!(check(0, b"ok 1"));
+        assert!(!check(0, b"ok"));
+        assert!(!check(1, b"ok 1"));
+        assert!(check(1, b"ok done, total 1"));
+    }
+
+    #[test]
+    fn test_invalid_operations() {
+        assert!(parse_operations("[]".as_bytes(), &[], false).is_err());
+        // unknown variable
+        assert!(parse_operations(
+            "- {name: a, url: 'http://a/{{user}}'}".as_bytes(),
+            &[],
+            false
+        )
+        .is_err());
+        // typo in a field name
+        assert!(parse_operations(
+            "- {name: a, url: 'http://a/', weigth: 2}".as_bytes(),
+            &[],
+            false
+        )
+        .is_err());
+        assert!(parse_operations(
+            "- {name: a, url: 'http://a/', body: x, body_file: y}".as_bytes(),
+            &[],
+            false
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,269 @@
+/// Copyright 2020 Developers of the perf-gauge project.
+///
+/// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+/// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+/// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+/// option. This file may not be copied, modified, or distributed
+/// except according to those terms.
+use hyper::client::connect::dns::{GaiAddrs, GaiResolver, Name};
+use hyper::client::connect::{Connected, Connection};
+use hyper::client::HttpConnector;
+use hyper::http::uri::Scheme;
+use hyper::service::Service;
+use hyper::Uri;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+/// Name resolution of a new connection.
+pub const DNS_PHASE: &str = "dns";
+/// TCP handshake of a new connection.
+pub const CONNECT_PHASE: &str = "connect";
+/// TLS handshake of a new connection.
+pub const TLS_PHASE: &str = "tls";
+/// From sending the request until the response headers, excluding the connection setup.
+pub const TTFB_PHASE: &str = "ttfb";
+/// Downloading the response body.
+pub const BODY_PHASE: &str = "body";
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+type BoxFuture<T, E> = Pin<Box<dyn Future<Output = Result<T, E>> + Send>>;
+
+/// Plain TCP connector, which records when the name is resolved and the connection is established.
+pub type TimedHttpConnector = TcpTimer<HttpConnector<TimedResolver>>;
+
+tokio::task_local! {
+    /// Progress of the connection being established by `TimedConnector`.
+    static PROGRESS: Arc<Mutex<Progress>>;
+}
+
+#[derive(Default)]
+struct Progress {
+    resolved: Option<Instant>,
+    connected: Option<Instant>,
+}
+
+/// Durations of the connection setup, reported with the first response over the connection.
+#[derive(Clone, Debug)]
+pub struct ConnectTimings {
+    phases: Vec<(&'static str, Duration)>,
+    taken: Arc<AtomicBool>,
+}
+
+/// Resolves names with `getaddrinfo`.
+#[derive(Clone)]
+pub struct TimedResolver(GaiResolver);
+
+/// Records when the inner connector establishes the connection, i.e. before the TLS handshake.
+#[derive(Clone)]
+pub struct TcpTimer<C>(C);
+
+/// Measures the DNS, connect and TLS phases of new connections and attaches them
+/// to the connection as `ConnectTimings`.
+#[derive(Clone)]
+pub struct TimedConnector<C>(C);
+
+pub struct TimedStream<T> {
+    inner: T,
+    timings: ConnectTimings,
+}
+
+impl ConnectTimings {
+    /// The phases, unless they were taken by a previous response over the same connection.
+    pub fn take(&self) -> Vec<(&'static str, Duration)> {
+        if self.taken.swap(true, Ordering::Relaxed) {
+            vec![]
+        } else {
+            self.phases.clone()
+        }
+    }
+}
+
+fn record(f: impl FnOnce(&mut Progress)) {
+    // connections established outside of `TimedConnector` aren't timed
+    let _ = PROGRESS.try_with(|progress| f(&mut progress.lock().expect("Poisoned progress")));
+}
+
+impl TimedResolver {
+    pub fn new() -> Self {
+        Self(GaiResolver::new())
+    }
+}
+
+impl<C> TcpTimer<C> {
+    pub fn new(inner: C) -> Self {
+        Self(inner)
+    }
+}
+
+impl<C> TimedConnector<C> {
+    pub fn new(inner: C) -> Self {
+        Self(inner)
+    }
+}
+
+impl Service<Name> for TimedResolver {
+    type Response = GaiAddrs;
+    type Error = io::Error;
+    type Future = BoxFuture<GaiAddrs, io::Error>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.0.poll_ready(cx)
+    }
+
+    fn call(&mut self, name: Name) -> Self::Future {
+        let resolving = self.0.call(name);
+        Box::pin(async move {
+            let addrs = resolving.await?;
+            record(|p| p.resolved = Some(Instant::now()));
+            Ok(addrs)
+        })
+    }
+}
+
+impl<C> Service<Uri> for TcpTimer<C>
+where
+    C: Service<Uri>,
+    C::Response: Send + 'static,
+    C::Error: Send + 'static,
+    C::Future: Send + 'static,
+{
+    type Response = C::Response;
+    type Error = C::Error;
+    type Future = BoxFuture<C::Response, C::Error>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.0.poll_ready(cx)
+    }
+
+    fn call(&mut self, uri: Uri) -> Self::Future {
+        let connecting = self.0.call(uri);
+        Box::pin(async move {
+            let stream = connecting.await?;
+            record(|p| p.connected = Some(Instant::now()));
+            Ok(stream)
+        })
+    }
+}
+
+impl<C> Service<Uri> for TimedConnector<C>
+where
+    C: Service<Uri>,
+    C::Response: Send + 'static,
+    C::Error: Into<BoxError>,
+    C::Future: Send + 'static,
+{
+    type Response = TimedStream<C::Response>;
+    type Error = BoxError;
+    type Future = BoxFuture<Self::Response, BoxError>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.0.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, uri: Uri) -> Self::Future {
+        let is_tls = uri.scheme() == Some(&Scheme::HTTPS);
+        let progress = Arc::new(Mutex::new(Progress::default()));
+        let start = Instant::now();
+        let connecting = PROGRESS.scope(progress.clone(), self.0.call(uri));
+        Box::pin(async move {
+            let inner = connecting.await.map_err(Into::into)?;
+            let done = Instant::now();
+            let progress = progress.lock().expect("Poisoned progress");
+            let connected = progress.connected.unwrap_or(done);
+
+            let mut phases = vec![];
+            // IP addresses are not resolved
+            if let Some(resolved) = progress.resolved {
+                phases.push((DNS_PHASE, resolved.duration_since(start)));
+            }
+            phases.push((
+                CONNECT_PHASE,
+                connected.duration_since(progress.resolved.unwrap_or(start)),
+            ));
+            if is_tls {
+                phases.push((TLS_PHASE, done.duration_since(connected)));
+            }
+
+            Ok(TimedStream {
+                inner,
+                timings: ConnectTimings {
+                    phases,
+                    taken: Arc::new(AtomicBool::new(false)),
+                },
+            })
+        })
+    }
+}
+
+impl<T: Connection> Connection for TimedStream<T> {
+    fn connected(&self) -> Connected {
+        self.inner.connected().extra(self.timings.clone())
+    }
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for TimedStream<T> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for TimedStream<T> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::http_timing::{ConnectTimings, CONNECT_PHASE};
+    use std::sync::atomic::AtomicBool;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[test]
+    fn test_taken_once() {
+        let timings = ConnectTimings {
+            phases: vec![(CONNECT_PHASE, Duration::from_millis(1))],
+            taken: Arc::new(AtomicBool::new(false)),
+        };
+        // the same connection info is attached to each response over the connection
+        let copy = timings.clone();
+        assert_eq!(vec![(CONNECT_PHASE, Duration::from_millis(1))], copy.take());
+        assert!(timings.take().is_empty());
+        assert!(copy.take().is_empty());
+    }
+}
@@ -0,0 +1,172 @@
+/// Copyright 2020 Developers of the perf-gauge project.
+///
+/// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+/// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+/// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+/// option. This file may not be copied, modified, or distributed
+/// except according to those terms.
+use crate::metrics::{
+    BenchRunMetrics, BenchRunMetricsItem, BenchRunReport, BenchRunReportItem,
+    ExternalMetricsServiceReporter,
+};
+use log::info;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Write};
+use std::sync::Mutex;
+
+/// Writes the results of all batches so far to a JSON file, as an array with an element per batch.
+/// The file is rewritten after each batch, so it's valid even if the session is interrupted.
+pub struct JsonReporter {
+    path: String,
+    test_case_name: Option<String>,
+    batches: Mutex<Vec<JsonReport>>,
+}
+
+#[derive(Serialize)]
+struct JsonReport {
+    concurrency: usize,
+    combined: JsonReportItem,
+    by_operation: BTreeMap<String, JsonReportItem>,
+}
+
+#[derive(Serialize)]
+struct JsonReportItem {
+    #[serde(flatten)]
+    summary: BenchRunReportItem,
+    /// `[latency in µs, count]` of recorded values, ascending.
+    latency_histogram: Vec<(f64, u64)>,
+    phase_latency_histogram: BTreeMap<&'static str, Vec<(f64, u64)>>,
+}
+
+impl JsonReporter {
+    pub fn new(path: &str, test_case_name: Option<String>) -> io::Result<Self> {
+        let reporter = Self {
+            path: path.to_string(),
+            test_case_name,
+            batches: Mutex::new(vec![]),
+        };
+        // fail fast if the file cannot be written
+        reporter.write(&[])?;
+        Ok(reporter)
+    }
+
+    fn write(&self, batches: &[JsonReport]) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(&self.path)?);
+        serde_json::to_writer_pretty(&mut writer, batches)?;
+        writeln!(writer)?;
+        writer.flush()
+    }
+
+    fn build_item(summary: BenchRunReportItem, metrics: &BenchRunMetricsItem) -> JsonReportItem {
+        let mut latency = metrics.success_latency.clone();
+        latency.merge(&metrics.error_latency);
+        JsonReportItem {
+            summary,
+            latency_histogram: latency.buckets(),
+            phase_latency_histogram: metrics
+                .phase_latency
+                .iter()
+                .map(|(phase, histogram)| (*phase, histogram.buckets()))
+                .collect(),
+        }
+    }
+}
+
+impl ExternalMetricsServiceReporter for JsonReporter {
+    fn report(&self, metrics: &BenchRunMetrics) -> io::Result<()> {
+        let BenchRunReport {
+            concurrency,
+            combined,
+            by_operation,
+            ..
+        } = BenchRunReport::new(self.test_case_name.clone(), metrics);
+
+        let report = JsonReport {
+            concurrency,
+            combined: JsonReporter::build_item(combined, &metrics.combined),
+            by_operation: by_operation
+                .into_iter()
+                .map(|(operation, summary)| {
+                    let item = JsonReporter::build_item(summary, &metrics.by_operation[&operation]);
+                    (operation, item)
+                })
+                .collect(),
+        };
+
+        info!("Writing the JSON report to {}", self.path);
+        let mut batches = self.batches.lock().expect("Poisoned JSON report");
+        batches.push(report);
+        self.write(&batches)
+    }
+
+    fn reset_metrics(&self) {
+        // do nothing
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::json_reporter::JsonReporter;
+    use crate::latency_histogram::HistogramBackend;
+    use crate::metrics::{BenchRunMetrics, ExternalMetricsServiceReporter, RequestStatsBuilder};
+    use serde_json::Value;
+    use std::fs;
+    use std::time::Duration;
+
+    #[test]
+    fn test_json_report() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("report.json");
+        let path = path.to_str().unwrap();
+        let reporter = JsonReporter::new(path, Some("test".to_string())).unwrap();
+        assert_eq!("[]\n", fs::read_to_string(path).unwrap());
+
+        let mut metrics = BenchRunMetrics::new(HistogramBackend::Simple);
+        metrics.concurrency = 2;
+        for (i, status) in ["200 OK", "200 OK", "500 Internal Server Error"]
+            .iter()
+            .enumerate()
+        {
+            metrics.report_request(
+                RequestStatsBuilder::default()
+                    .is_success(i < 2)
+                    .bytes_processed(100)
+                    .status(status.to_string())
+                    .duration(Duration::from_micros(100 * (i as u64 + 1)))
+                    .operation_name(Some("GET /".to_string()))
+                    .fatal_error(false)
+                    .phases(vec![("ttfb", Duration::from_micros(50))])
+                    .build()
+                    .unwrap(),
+            );
+        }
+        reporter.report(&metrics).unwrap();
+        assert!(!reporter.reports_snapshots());
+
+        let report: Value = serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap();
+
+        let batches = report.as_array().unwrap();
+        assert_eq!(1, batches.len());
+        let batch = &batches[0];
+        assert_eq!(2, batch["concurrency"]);
+
+        let combined = &batch["combined"];
+        assert_eq!("test", combined["test_case_name"]);
+        assert_eq!(3, combined["total_requests"]);
+        assert_eq!(
+            serde_json::json!([[100., 1], [200., 1], [300., 1]]),
+            combined["latency_histogram"]
+        );
+        assert_eq!(
+            serde_json::json!([[50., 3]]),
+            combined["phase_latency_histogram"]["ttfb"]
+        );
+        assert_eq!(
+            serde_json::json!([["200 OK", 2], ["500 Internal Server Error", 1]]),
+            combined["response_code_summary"]
+        );
+        assert_eq!(3, batch["by_operation"]["GET /"]["total_requests"]);
+    }
+}
@@ -0,0 +1,112 @@
+/// Copyright 2020 Developers of the perf-gauge project.
+///
+/// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+/// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+/// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+/// option. This file may not be copied, modified, or distributed
+/// except according to those terms.
+use rand::distributions::Alphanumeric;
+use rand::{thread_rng, Rng};
+
+/// Random keys and values for GET/SET commands of key-value stores.
+#[derive(Builder, Deserialize, Clone, Debug)]
+pub struct KeyValueWorkload {
+    #[builder(default = "\"key:\".to_string()")]
+    key_prefix: String,
+    #[builder(default = "1000")]
+    keyspace: usize,
+    /// Value sizes are uniformly distributed in the given range (inclusive).
+    #[builder(default = "(64, 64)")]
+    value_size: (usize, usize),
+    /// Share of SET commands, the rest are GET.
+    #[builder(default)]
+    set_ratio: f64,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum KeyValueCommand {
+    Get(String),
+    Set(String, String),
+}
+
+impl KeyValueWorkload {
+    pub fn next_command(&self) -> KeyValueCommand {
+        if thread_rng().gen_bool(self.set_ratio.clamp(0., 1.)) {
+            KeyValueCommand::Set(self.random_key(), self.random_value())
+        } else {
+            KeyValueCommand::Get(self.random_key())
+        }
+    }
+
+    fn random_key(&self) -> String {
+        format!(
+            "{}{}",
+            self.key_prefix,
+            thread_rng().gen_range(0..self.keyspace.max(1))
+        )
+    }
+
+    fn random_value(&self) -> String {
+        let (min, max) = self.value_size;
+        let mut rng = thread_rng();
+        let size = rng.gen_range(min..=max.max(min));
+        (0..size)
+            .map(|_| rng.sample(Alphanumeric) as char)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::kv_workload::{KeyValueCommand, KeyValueWorkloadBuilder};
+
+    #[test]
+    fn test_get_only() {
+        let workload = KeyValueWorkloadBuilder::default()
+            .key_prefix("k".to_string())
+            .keyspace(10)
+            .build()
+            .unwrap();
+
+        for _ in 0..100 {
+            match workload.next_command() {
+                KeyValueCommand::Get(key) => {
+                    let id: usize = key.strip_prefix('k').unwrap().parse().unwrap();
+                    assert!(id < 10);
+                }
+                KeyValueCommand::Set(_, _) => panic!("Unexpected SET"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_set_value_size() {
+        let workload = KeyValueWorkloadBuilder::default()
+            .value_size((16, 32))
+            .set_ratio(1.)
+            .build()
+            .unwrap();
+
+        for _ in 0..100 {
+            match workload.next_command() {
+                KeyValueCommand::Set(_, value) => {
+                    assert!(value.len() >= 16 && value.len() <= 32, "{}", value.len());
+                }
+                KeyValueCommand::Get(_) => panic!("Unexpected GET"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_mixed() {
+        let workload = KeyValueWorkloadBuilder::default()
+            .set_ratio(0.5)
+            .build()
+            .unwrap();
+
+        let sets = (0..1000)
+            .filter(|_| matches!(workload.next_command(), KeyValueCommand::Set(_, _)))
+            .count();
+        assert!(sets > 400 && sets < 600, "{}", sets);
+    }
+}
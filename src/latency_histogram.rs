@@ -0,0 +1,209 @@
+/// Copyright 2020 Developers of the perf-gauge project.
+///
+/// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+/// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+/// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+/// option. This file may not be copied, modified, or distributed
+/// except according to those terms.
+use hdrhistogram::Histogram as HdrHistogram;
+use histogram::Histogram as SimpleHistogram;
+use std::time::Duration;
+
+const NANOS_PER_MICRO: f64 = 1_000.;
+
+/// How latencies are stored.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum HistogramBackend {
+    /// Microsecond buckets of the `histogram` crate.
+    #[default]
+    Simple,
+    /// HdrHistogram of nanoseconds. Values above `max_value` are recorded as `max_value`.
+    Hdr {
+        significant_digits: u8,
+        max_value: Duration,
+    },
+}
+
+/// Latency histogram. Statistics are in microseconds, `0` if there are no values.
+#[derive(Clone)]
+pub enum LatencyHistogram {
+    Simple(SimpleHistogram),
+    Hdr(HdrHistogram<u64>),
+}
+
+impl LatencyHistogram {
+    pub fn new(backend: HistogramBackend) -> Self {
+        match backend {
+            HistogramBackend::Simple => Self::Simple(SimpleHistogram::new()),
+            HistogramBackend::Hdr {
+                significant_digits,
+                max_value,
+            } => Self::Hdr(
+                HdrHistogram::new_with_bounds(1, max_value.as_nanos() as u64, significant_digits)
+                    .expect("Illegal HDR histogram parameters"),
+            ),
+        }
+    }
+
+    pub fn increment(&mut self, value: Duration) {
+        match self {
+            Self::Simple(h) => h.increment(value.as_micros() as u64).unwrap_or_default(),
+            Self::Hdr(h) => h.saturating_record(value.as_nanos() as u64),
+        }
+    }
+
+    pub fn merge(&mut self, other: &LatencyHistogram) {
+        match (self, other) {
+            (Self::Simple(h), Self::Simple(other)) => h.merge(other),
+            (Self::Hdr(h), Self::Hdr(other)) => h
+                .add(other)
+                .expect("Histograms of the same backend have the same bounds"),
+            _ => panic!("Bug: merging histograms of different backends"),
+        }
+    }
+
+    pub fn percentile(&self, percentile: f64) -> u64 {
+        match self {
+            Self::Simple(h) => h.percentile(percentile).unwrap_or_default(),
+            Self::Hdr(h) => Self::to_micros(h.value_at_percentile(percentile)),
+        }
+    }
+
+    pub fn minimum(&self) -> u64 {
+        match self {
+            Self::Simple(h) => h.minimum().unwrap_or_default(),
+            Self::Hdr(h) => Self::to_micros(h.min()),
+        }
+    }
+
+    pub fn maximum(&self) -> u64 {
+        match self {
+            Self::Simple(h) => h.maximum().unwrap_or_default(),
+            Self::Hdr(h) => Self::to_micros(h.max()),
+        }
+    }
+
+    pub fn mean(&self) -> u64 {
+        match self {
+            Self::Simple(h) => h.mean().unwrap_or_default(),
+            Self::Hdr(h) => (h.mean() / NANOS_PER_MICRO).round() as u64,
+        }
+    }
+
+    pub fn stddev(&self) -> u64 {
+        match self {
+            Self::Simple(h) => h.stddev().unwrap_or_default(),
+            Self::Hdr(h) => (h.stdev() / NANOS_PER_MICRO).round() as u64,
+        }
+    }
+
+    /// Recorded values in microseconds (fractional for HdrHistogram) and their counts, ascending.
+    pub fn buckets(&self) -> Vec<(f64, u64)> {
+        match self {
+            Self::Simple(h) => h
+                .into_iter()
+                .filter(|b| b.count() > 0)
+                .map(|b| (b.value() as f64, b.count()))
+                .collect(),
+            Self::Hdr(h) => h
+                .iter_recorded()
+                .map(|v| {
+                    (
+                        v.value_iterated_to() as f64 / NANOS_PER_MICRO,
+                        v.count_at_value(),
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    /// The raw histogram, e.g. for exporting. `None` unless it's the HdrHistogram backend.
+    pub fn hdr(&self) -> Option<&HdrHistogram<u64>> {
+        match self {
+            Self::Simple(_) => None,
+            Self::Hdr(h) => Some(h),
+        }
+    }
+
+    fn to_micros(nanos: u64) -> u64 {
+        (nanos as f64 / NANOS_PER_MICRO).round() as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::latency_histogram::{HistogramBackend, LatencyHistogram};
+    use std::time::Duration;
+
+    fn hdr(significant_digits: u8, max_value: Duration) -> LatencyHistogram {
+        LatencyHistogram::new(HistogramBackend::Hdr {
+            significant_digits,
+            max_value,
+        })
+    }
+
+    #[test]
+    fn test_backends() {
+        for mut histogram in [
+            LatencyHistogram::new(HistogramBackend::Simple),
+            hdr(3, Duration::from_secs(60)),
+        ] {
+            assert_eq!(0, histogram.percentile(50.));
+            assert_eq!(0, histogram.maximum());
+            for i in 1..=1000 {
+                histogram.increment(Duration::from_micros(i));
+            }
+            let mut other = histogram.clone();
+            other.increment(Duration::from_millis(10));
+            histogram.merge(&other);
+
+            assert_eq!(1, histogram.minimum());
+            // the precision is 3 significant digits for both
+            for (expected, actual) in [
+                (501, histogram.percentile(50.)),
+                (991, histogram.percentile(99.)),
+                (10_000, histogram.maximum()),
+                (505, histogram.mean()),
+            ] {
+                assert!(
+                    (actual as f64 / expected as f64 - 1.).abs() <= 0.002,
+                    "{} vs {}",
+                    expected,
+                    actual
+                );
+            }
+            assert_eq!(
+                2001,
+                histogram.buckets().iter().map(|(_, c)| c).sum::<u64>()
+            );
+        }
+    }
+
+    #[test]
+    fn test_hdr_precision() {
+        let mut histogram = hdr(3, Duration::from_secs(3600));
+        histogram.increment(Duration::from_nanos(1_500));
+        histogram.increment(Duration::from_nanos(2_500));
+        // too long for the default `histogram` configuration
+        histogram.increment(Duration::from_secs(1800));
+
+        // sub-microsecond precision
+        let buckets = histogram.buckets();
+        assert_eq!(1.5, buckets[0].0);
+        assert!((buckets[1].0 - 2.5).abs() <= 0.002, "{:?}", buckets);
+        assert_eq!(2, histogram.minimum());
+        // within 3 significant digits
+        let max = histogram.maximum() as f64;
+        assert!((max / 1_800_000_000. - 1.).abs() < 0.001, "{}", max);
+
+        // values above the max are clamped
+        let mut histogram = hdr(2, Duration::from_secs(1));
+        histogram.increment(Duration::from_secs(10));
+        assert_eq!(1, histogram.buckets().len());
+        assert!(histogram.maximum() <= 1_010_000);
+        assert!(histogram.hdr().is_some());
+        assert!(LatencyHistogram::new(HistogramBackend::Simple)
+            .hdr()
+            .is_none());
+    }
+}
@@ -0,0 +1,432 @@
+/// Copyright 2020 Developers of the perf-gauge project.
+///
+/// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+/// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+/// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+/// option. This file may not be copied, modified, or distributed
+/// except according to those terms.
+use serde::{Deserialize, Deserializer};
+use std::f64::consts::PI;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::str::FromStr;
+use std::time::Duration;
+
+const DEFAULT_STEPS: usize = 10;
+
+/// A sequence of steps with a constant rate, each of them is a separate batch with its own report.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LoadProfile {
+    steps: Vec<ProfileStep>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ProfileStep {
+    pub duration: Duration,
+    /// Requests per second, `0` means no limit.
+    pub rate: f64,
+}
+
+/// Either a `{duration, rate}` step or a shape, which is split into steps.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SegmentSpec {
+    Step(StepSpec),
+    Shape(ShapeSpec),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StepSpec {
+    #[serde(deserialize_with = "deserialize_duration")]
+    duration: Duration,
+    rate: f64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+enum ShapeSpec {
+    Hold {
+        #[serde(deserialize_with = "deserialize_duration")]
+        duration: Duration,
+        rate: f64,
+    },
+    Ramp {
+        #[serde(deserialize_with = "deserialize_duration")]
+        duration: Duration,
+        from: f64,
+        to: f64,
+        #[serde(default = "default_steps")]
+        steps: usize,
+    },
+    ExpRamp {
+        #[serde(deserialize_with = "deserialize_duration")]
+        duration: Duration,
+        from: f64,
+        to: f64,
+        #[serde(default = "default_steps")]
+        steps: usize,
+    },
+    Sine {
+        #[serde(deserialize_with = "deserialize_duration")]
+        duration: Duration,
+        min: f64,
+        max: f64,
+        #[serde(deserialize_with = "deserialize_duration")]
+        period: Duration,
+        #[serde(default = "default_steps")]
+        steps: usize,
+    },
+    Spike {
+        #[serde(deserialize_with = "deserialize_duration")]
+        duration: Duration,
+        base: f64,
+        peak: f64,
+        #[serde(deserialize_with = "deserialize_duration")]
+        spike: Duration,
+    },
+}
+
+fn default_steps() -> usize {
+    DEFAULT_STEPS
+}
+
+fn parse_duration(duration: &str) -> Result<Duration, String> {
+    humantime::Duration::from_str(duration.trim())
+        .map(|d| d.into())
+        .map_err(|e| format!("Illegal duration `{}`: {}", duration, e))
+}
+
+fn deserialize_duration<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+    parse_duration(&String::deserialize(deserializer)?).map_err(serde::de::Error::custom)
+}
+
+impl LoadProfile {
+    pub fn new(steps: Vec<ProfileStep>) -> Result<LoadProfile, String> {
+        if steps.is_empty() {
+            return Err("Empty load profile".to_string());
+        }
+        if let Some(step) = steps
+            .iter()
+            .find(|s| s.duration.is_zero() || s.rate.is_nan() || s.rate < 0.)
+        {
+            return Err(format!("Illegal step of the load profile: {:?}", step));
+        }
+        Ok(LoadProfile { steps })
+    }
+
+    /// `.csv` files are read as `duration,rate` lines, anything else as YAML.
+    pub fn from_file(path: &str) -> Result<LoadProfile, String> {
+        let file = File::open(path).map_err(|e| format!("Cannot open {}: {}", path, e))?;
+        let reader = BufReader::new(file);
+        if path.to_ascii_lowercase().ends_with(".csv") {
+            LoadProfile::parse_csv(reader)
+        } else {
+            LoadProfile::parse_yaml(reader)
+        }
+    }
+
+    fn parse_yaml(input: impl Read) -> Result<LoadProfile, String> {
+        let segments: Vec<SegmentSpec> =
+            serde_yaml::from_reader(input).map_err(|e| format!("Invalid load profile: {}", e))?;
+        let mut steps = vec![];
+        for segment in segments {
+            steps.extend(match segment {
+                SegmentSpec::Step(StepSpec { duration, rate }) => hold(duration, rate),
+                SegmentSpec::Shape(shape) => shape.steps()?,
+            });
+        }
+        LoadProfile::new(steps)
+    }
+
+    /// An optional `duration,rate` header, `#` comments.
+    fn parse_csv(input: impl Read) -> Result<LoadProfile, String> {
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .trim(csv::Trim::All)
+            .comment(Some(b'#'))
+            .from_reader(input);
+        let mut steps = vec![];
+        for (idx, record) in reader.records().enumerate() {
+            let record = record.map_err(|e| format!("Invalid load profile: {}", e))?;
+            if idx == 0 && record.get(0) == Some("duration") {
+                continue;
+            }
+            match (record.get(0), record.get(1), record.len()) {
+                (Some(duration), Some(rate), 2) => steps.push(ProfileStep {
+                    duration: parse_duration(duration)?,
+                    rate: rate
+                        .parse()
+                        .map_err(|_| format!("Illegal rate `{}`", rate))?,
+                }),
+                _ => {
+                    return Err(format!(
+                        "Expected `duration,rate`, got {:?}",
+                        record.iter().collect::<Vec<_>>()
+                    ))
+                }
+            }
+        }
+        LoadProfile::new(steps)
+    }
+
+    pub fn steps(&self) -> &[ProfileStep] {
+        &self.steps
+    }
+}
+
+impl ShapeSpec {
+    fn steps(&self) -> Result<Vec<ProfileStep>, String> {
+        match *self {
+            ShapeSpec::Hold { duration, rate } => Ok(hold(duration, rate)),
+            ShapeSpec::Ramp {
+                duration,
+                from,
+                to,
+                steps,
+            } => {
+                require_positive("Ramps", &[from, to])?;
+                Ok(linear_ramp(duration, from, to, steps))
+            }
+            ShapeSpec::ExpRamp {
+                duration,
+                from,
+                to,
+                steps,
+            } => {
+                require_positive("Exponential ramps", &[from, to])?;
+                Ok(exp_ramp(duration, from, to, steps))
+            }
+            ShapeSpec::Sine {
+                duration,
+                min,
+                max,
+                period,
+                steps,
+            } => {
+                require_positive("Sine waves", &[min, max])?;
+                if period.is_zero() {
+                    return Err("The period of a sine wave must be positive".to_string());
+                }
+                Ok(sine(duration, min, max, period, steps))
+            }
+            ShapeSpec::Spike {
+                duration,
+                base,
+                peak,
+                spike: spike_duration,
+            } => {
+                require_positive("Spikes", &[base, peak])?;
+                if spike_duration > duration {
+                    return Err("A spike must be shorter than its segment".to_string());
+                }
+                Ok(spike(duration, base, peak, spike_duration))
+            }
+        }
+    }
+}
+
+/// Shapes compute intermediate rates, so `0` (no limit) cannot be a part of them.
+fn require_positive(shape: &str, rates: &[f64]) -> Result<(), String> {
+    if rates.iter().all(|r| *r > 0.) {
+        Ok(())
+    } else {
+        Err(format!("{} require positive rates: {:?}", shape, rates))
+    }
+}
+
+pub fn hold(duration: Duration, rate: f64) -> Vec<ProfileStep> {
+    vec![ProfileStep { duration, rate }]
+}
+
+/// Splits `duration` into `steps` equal steps, the rate is `rate_at(fraction of steps)`.
+fn shape(duration: Duration, steps: usize, rate_at: impl Fn(f64) -> f64) -> Vec<ProfileStep> {
+    let steps = steps.max(1);
+    let step_duration = duration / steps as u32;
+    (0..steps)
+        .map(|i| ProfileStep {
+            duration: step_duration,
+            rate: rate_at(i as f64 / (steps - 1).max(1) as f64),
+        })
+        .collect()
+}
+
+/// `from`, ..., `to` with a constant increment.
+pub fn linear_ramp(duration: Duration, from: f64, to: f64, steps: usize) -> Vec<ProfileStep> {
+    shape(duration, steps, |x| from + (to - from) * x)
+}
+
+/// `from`, ..., `to` with a constant multiplier.
+pub fn exp_ramp(duration: Duration, from: f64, to: f64, steps: usize) -> Vec<ProfileStep> {
+    shape(duration, steps, |x| from * (to / from).powf(x))
+}
+
+/// Oscillates between `min` and `max`, starting in the middle, e.g. a diurnal curve.
+pub fn sine(
+    duration: Duration,
+    min: f64,
+    max: f64,
+    period: Duration,
+    steps: usize,
+) -> Vec<ProfileStep> {
+    let steps = steps.max(1);
+    let step_duration = duration / steps as u32;
+    (0..steps)
+        .map(|i| {
+            // the rate in the middle of the step
+            let t = step_duration.as_secs_f64() * (i as f64 + 0.5);
+            let phase = 2. * PI * t / period.as_secs_f64();
+            ProfileStep {
+                duration: step_duration,
+                rate: (min + max) / 2. + (max - min) / 2. * phase.sin(),
+            }
+        })
+        .collect()
+}
+
+/// `peak` for `spike_duration` in the middle of the `base` rate.
+pub fn spike(
+    duration: Duration,
+    base: f64,
+    peak: f64,
+    spike_duration: Duration,
+) -> Vec<ProfileStep> {
+    let before = (duration - spike_duration) / 2;
+    let after = duration - spike_duration - before;
+    [(before, base), (spike_duration, peak), (after, base)]
+        .iter()
+        .filter(|(duration, _)| !duration.is_zero())
+        .map(|(duration, rate)| ProfileStep {
+            duration: *duration,
+            rate: *rate,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::load_profile::{exp_ramp, linear_ramp, sine, spike, LoadProfile, ProfileStep};
+    use std::time::Duration;
+
+    fn rates(steps: &[ProfileStep]) -> Vec<f64> {
+        steps.iter().map(|s| s.rate.round()).collect()
+    }
+
+    #[test]
+    fn test_shapes() {
+        let ramp = linear_ramp(Duration::from_secs(50), 100., 500., 5);
+        assert_eq!(vec![100., 200., 300., 400., 500.], rates(&ramp));
+        assert!(ramp.iter().all(|s| s.duration == Duration::from_secs(10)));
+
+        let ramp = exp_ramp(Duration::from_secs(40), 10., 10000., 4);
+        assert_eq!(vec![10., 100., 1000., 10000.], rates(&ramp));
+
+        let wave = sine(
+            Duration::from_secs(60),
+            100.,
+            300.,
+            Duration::from_secs(60),
+            4,
+        );
+        assert_eq!(vec![271., 271., 129., 129.], rates(&wave));
+
+        let steps = spike(
+            Duration::from_secs(60),
+            100.,
+            1000.,
+            Duration::from_secs(10),
+        );
+        assert_eq!(
+            vec![
+                ProfileStep {
+                    duration: Duration::from_secs(25),
+                    rate: 100.
+                },
+                ProfileStep {
+                    duration: Duration::from_secs(10),
+                    rate: 1000.
+                },
+                ProfileStep {
+                    duration: Duration::from_secs(25),
+                    rate: 100.
+                },
+            ],
+            steps
+        );
+        assert_eq!(
+            1,
+            spike(Duration::from_secs(5), 1., 2., Duration::from_secs(5)).len()
+        );
+    }
+
+    #[test]
+    fn test_parse_yaml() {
+        let profile = LoadProfile::parse_yaml(
+            r#"
+- {duration: 1m, rate: 100}
+- ramp: {duration: 30s, from: 100, to: 400, steps: 4}
+- exp_ramp: {duration: 20s, from: 10, to: 1000, steps: 3}
+- sine: {duration: 1h, min: 100, max: 300, period: 1h, steps: 24}
+- spike: {duration: 1m, base: 100, peak: 5000, spike: 10s}
+- hold: {duration: 5m, rate: 0}
+"#
+            .as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(1 + 4 + 3 + 24 + 3 + 1, profile.steps().len());
+        assert_eq!(
+            ProfileStep {
+                duration: Duration::from_secs(60),
+                rate: 100.
+            },
+            profile.steps()[0]
+        );
+        assert_eq!(Duration::from_millis(7500), profile.steps()[1].duration);
+        assert_eq!(0., profile.steps().last().unwrap().rate);
+
+        let invalid = |yaml: &str| LoadProfile::parse_yaml(yaml.as_bytes()).is_err();
+        assert!(invalid("[]"));
+        assert!(invalid("- {duration: 1x, rate: 100}"));
+        assert!(invalid("- {duration: 0s, rate: 100}"));
+        assert!(invalid("- {duration: 1m, rate: -1}"));
+        assert!(invalid("- {duration: 1m, rate: 100, steps: 2}"));
+        assert!(invalid("- exp_ramp: {duration: 1m, from: 0, to: 100}"));
+        assert!(invalid("- ramp: {duration: 1m, from: 0, to: 100}"));
+        assert!(invalid("- ramp: {duration: 1m, from: 100, to: -1}"));
+        assert!(invalid(
+            "- sine: {duration: 1m, min: 0, max: 100, period: 1m}"
+        ));
+        assert!(invalid(
+            "- spike: {duration: 1m, base: 0, peak: 2, spike: 1s}"
+        ));
+        assert!(invalid(
+            "- spike: {duration: 1m, base: 1, peak: 2, spike: 2m}"
+        ));
+        assert!(invalid("- zigzag: {duration: 1m}"));
+    }
+
+    #[test]
+    fn test_parse_csv() {
+        let profile = LoadProfile::parse_csv(
+            "duration, rate\n# night\n30m, 100\n1h 30m, 2500.5\n".as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(
+            vec![
+                ProfileStep {
+                    duration: Duration::from_secs(1800),
+                    rate: 100.
+                },
+                ProfileStep {
+                    duration: Duration::from_secs(5400),
+                    rate: 2500.5
+                },
+            ],
+            profile.steps()
+        );
+        assert!(LoadProfile::parse_csv("".as_bytes()).is_err());
+        assert!(LoadProfile::parse_csv("1m\n".as_bytes()).is_err());
+        assert!(LoadProfile::parse_csv("1m,x\n".as_bytes()).is_err());
+        assert!(LoadProfile::parse_csv("1m,1,2\n".as_bytes()).is_err());
+    }
+}
@@ -10,14 +10,37 @@ extern crate serde_derive;
 #[macro_use]
 extern crate derive_builder;
 
+mod assertion;
 mod bench_run;
 mod bench_session;
 mod configuration;
+mod csv_reporter;
+mod data_feeder;
+mod extractor;
+mod grpc_bench_session;
+mod har;
+mod hdr_log_reporter;
+mod html_reporter;
 mod http_bench_session;
+mod http_operations;
+mod http_timing;
+mod json_reporter;
+mod kv_workload;
+mod latency_histogram;
+mod load_profile;
+mod memcached_bench_session;
 mod metrics;
 #[cfg(feature = "report-to-prometheus")]
 mod prometheus_reporter;
 mod rate_limiter;
+mod rate_search;
+mod redis_bench_session;
+#[cfg(feature = "scripting")]
+mod script;
+mod tcp_bench_session;
+mod template;
+mod udp_bench_session;
+mod ws_bench_session;
 
 use crate::configuration::BenchmarkConfig;
 use crate::metrics::{BenchRunMetrics, ExternalMetricsServiceReporter};
@@ -40,9 +63,8 @@ async fn main() -> io::Result<()> {
         process::exit(0x1);
     }));
 
-    let mut benchmark_config = BenchmarkConfig::from_command_line().map_err(|e| {
+    let mut benchmark_config = BenchmarkConfig::from_command_line().inspect_err(|_| {
         println!("Failed to process parameters. Exiting.");
-        e
     })?;
 
     init_logger();
@@ -51,31 +73,54 @@ async fn main() -> io::Result<()> {
 
     let (reporter_task, batch_metric_sender) =
         create_async_metrics_channel(&benchmark_config.reporters, benchmark_config.continuous);
-    let bench_session = benchmark_config.new_bench_session();
+    let mut bench_session = benchmark_config.new_bench_session();
 
-    for batch in bench_session {
+    while let Some(batch) = bench_session.next() {
         info!("Running next batch {}", batch);
-        let metrics = BenchRunMetrics::new();
-        let batch_run_result = batch.run(metrics).await;
+        let warmup = batch.is_warmup();
+        let metrics = BenchRunMetrics::new(benchmark_config.histogram_backend);
+        let batch_run_result = batch
+            .with_snapshot_sender(batch_metric_sender.clone())
+            .run(metrics)
+            .await;
         match batch_run_result {
+            Ok(_) if warmup => {
+                info!("Warm-up is complete, its results are discarded");
+            }
             Ok(stats) => {
+                bench_session.report(&stats);
                 batch_metric_sender.send(stats).unwrap_or_default();
             }
             Err(e) => {
                 error!("Unexpected error during batch run: {}", e);
+                if !warmup {
+                    bench_session.report_failure();
+                }
             }
         }
     }
 
-    shutdown(reporter_task, batch_metric_sender);
+    shutdown(
+        reporter_task,
+        batch_metric_sender,
+        bench_session.search_result().map(|r| r.to_string()),
+    );
 
     Ok(())
 }
 
-fn shutdown(reporter_task: JoinHandle<()>, batch_metric_sender: Sender<BenchRunMetrics>) {
+/// `summary` is printed after all reports, e.g. the result of the rate search.
+fn shutdown(
+    reporter_task: JoinHandle<()>,
+    batch_metric_sender: Sender<BenchRunMetrics>,
+    summary: Option<String>,
+) {
     // we need to drop it explicitly, to signal completion to the `mpsc` thread.
     drop(batch_metric_sender);
     reporter_task.join().unwrap_or_default();
+    if let Some(summary) = summary {
+        println!("{}", summary);
+    }
     println!("Done gauging performance. Exiting.");
 }
 
@@ -87,12 +132,15 @@ fn create_async_metrics_channel(
     // as at the moment of writing this code not all major metric client libraries
     // had `async` APIs.
     // We can replace it with `tokio::sync::mpsc` and `tokio::spawn` at any time
-    let (sender, receiver) = std::sync::mpsc::channel();
+    let (sender, receiver) = std::sync::mpsc::channel::<BenchRunMetrics>();
     let metric_reporters = metric_reporters.to_owned();
     let reporter_task = thread::spawn(move || {
         while let Ok(stats) = receiver.recv() {
             // broadcast to all metrics reporters
             for reporter in &metric_reporters {
+                if stats.snapshot.is_some() && !reporter.reports_snapshots() {
+                    continue;
+                }
                 if let Err(e) = reporter.report(&stats) {
                     error!("Error sending metrics: {}", e);
                 }
@@ -0,0 +1,517 @@
+use crate::bench_run::BenchmarkProtocolAdapter;
+/// Copyright 2020 Developers of the perf-gauge project.
+///
+/// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+/// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+/// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+/// option. This file may not be copied, modified, or distributed
+/// except according to those terms.
+use crate::kv_workload::{KeyValueCommand, KeyValueWorkload, KeyValueWorkloadBuilder};
+use crate::metrics::{RequestStats, RequestStatsBuilder};
+use async_trait::async_trait;
+use core::fmt;
+use log::error;
+use rand::{thread_rng, Rng};
+use std::io;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::sync::Mutex;
+use tokio::time::timeout;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Reported for GET commands finding the key. Both hits and misses are successful requests.
+pub const HIT_STATUS: &str = "HIT";
+/// Reported for GET commands not finding the key.
+pub const MISS_STATUS: &str = "MISS";
+
+const BINARY_HEADER_SIZE: usize = 24;
+const BINARY_REQUEST_MAGIC: u8 = 0x80;
+const BINARY_RESPONSE_MAGIC: u8 = 0x81;
+const BINARY_OPCODE_GET: u8 = 0x00;
+const BINARY_OPCODE_SET: u8 = 0x01;
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum MemcachedProtocol {
+    Text,
+    Binary,
+}
+
+#[derive(Builder, Deserialize, Clone)]
+pub struct MemcachedBenchAdapter {
+    target: Vec<String>,
+    #[builder(default = "MemcachedProtocol::Text")]
+    protocol: MemcachedProtocol,
+    #[builder(default = "KeyValueWorkloadBuilder::default().build().unwrap()")]
+    workload: KeyValueWorkload,
+}
+
+/// Memcached connections are always re-used, but re-established after errors.
+pub struct MemcachedClient {
+    connection: Mutex<Option<BufReader<TcpStream>>>,
+}
+
+impl MemcachedBenchAdapter {
+    async fn connect(&self) -> io::Result<TcpStream> {
+        let target = &self.target[thread_rng().gen_range(0..self.target.len())];
+        let stream = timeout(CONNECT_TIMEOUT, TcpStream::connect(target))
+            .await
+            .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "Connect timed out"))??;
+        stream.set_nodelay(true)?;
+        Ok(stream)
+    }
+
+    fn encode_text_command(command: &KeyValueCommand) -> Vec<u8> {
+        match command {
+            KeyValueCommand::Get(key) => format!("get {}\r\n", key).into_bytes(),
+            KeyValueCommand::Set(key, value) => {
+                format!("set {} 0 0 {}\r\n{}\r\n", key, value.len(), value).into_bytes()
+            }
+        }
+    }
+
+    /// Request header, followed by extras (flags and expiration for SET), key and value.
+    fn encode_binary_command(command: &KeyValueCommand) -> Vec<u8> {
+        let (opcode, key, extras, value) = match command {
+            KeyValueCommand::Get(key) => (BINARY_OPCODE_GET, key, vec![], ""),
+            KeyValueCommand::Set(key, value) => {
+                (BINARY_OPCODE_SET, key, vec![0; 8], value.as_str())
+            }
+        };
+        let body_length = extras.len() + key.len() + value.len();
+
+        let mut encoded = Vec::with_capacity(BINARY_HEADER_SIZE + body_length);
+        encoded.push(BINARY_REQUEST_MAGIC);
+        encoded.push(opcode);
+        encoded.extend_from_slice(&(key.len() as u16).to_be_bytes());
+        encoded.push(extras.len() as u8);
+        // data type and vbucket id
+        encoded.extend_from_slice(&[0; 3]);
+        encoded.extend_from_slice(&(body_length as u32).to_be_bytes());
+        // opaque and CAS
+        encoded.extend_from_slice(&[0; 12]);
+        encoded.extend_from_slice(&extras);
+        encoded.extend_from_slice(key.as_bytes());
+        encoded.extend_from_slice(value.as_bytes());
+        encoded
+    }
+
+    /// Reads a complete reply. Returns the status and the number of bytes read.
+    async fn read_text_reply<R: AsyncBufRead + Unpin>(
+        reader: &mut R,
+    ) -> io::Result<(String, usize)> {
+        let mut total_size = 0;
+        let mut line = String::new();
+        let mut hit = false;
+
+        loop {
+            line.clear();
+            let size = reader.read_line(&mut line).await?;
+            if size == 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "Unexpected EOF",
+                ));
+            }
+            total_size += size;
+
+            let value = line.trim_end();
+            if let Some(header) = value.strip_prefix("VALUE ") {
+                // VALUE <key> <flags> <bytes>, the data block is followed by CRLF
+                let length = header
+                    .split(' ')
+                    .nth(2)
+                    .and_then(|v| v.parse::<u64>().ok())
+                    .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Invalid VALUE"))?
+                    + 2;
+                let read =
+                    tokio::io::copy(&mut (&mut *reader).take(length), &mut tokio::io::sink())
+                        .await?;
+                if read < length {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "Unexpected EOF",
+                    ));
+                }
+                total_size += read as usize;
+                hit = true;
+                continue;
+            }
+
+            let status = match value {
+                "END" if hit => HIT_STATUS.to_string(),
+                "END" => MISS_STATUS.to_string(),
+                // errors are followed by a message
+                _ => value.split(' ').next().unwrap_or_default().to_string(),
+            };
+            return Ok((status, total_size));
+        }
+    }
+
+    /// Reads a complete reply. Returns the status and the number of bytes read.
+    async fn read_binary_reply<R: AsyncBufRead + Unpin>(
+        reader: &mut R,
+        opcode: u8,
+    ) -> io::Result<(String, usize)> {
+        let mut header = [0; BINARY_HEADER_SIZE];
+        reader.read_exact(&mut header).await?;
+        if header[0] != BINARY_RESPONSE_MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Invalid binary response",
+            ));
+        }
+
+        let status = u16::from_be_bytes([header[6], header[7]]);
+        let body_length = u32::from_be_bytes([header[8], header[9], header[10], header[11]]) as u64;
+        let read = tokio::io::copy(
+            &mut (&mut *reader).take(body_length),
+            &mut tokio::io::sink(),
+        )
+        .await?;
+        if read < body_length {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "Unexpected EOF",
+            ));
+        }
+
+        let status = match (status, opcode) {
+            (0x0000, BINARY_OPCODE_GET) => HIT_STATUS,
+            (0x0000, _) => "STORED",
+            (0x0001, BINARY_OPCODE_GET) => MISS_STATUS,
+            (0x0001, _) => "NOT_FOUND",
+            (0x0002, _) => "EXISTS",
+            (0x0003, _) => "TOO_LARGE",
+            (0x0004, _) => "INVALID_ARGUMENTS",
+            (0x0005, _) => "NOT_STORED",
+            (0x0081, _) => "UNKNOWN_COMMAND",
+            (0x0082, _) => "OUT_OF_MEMORY",
+            _ => "SERVER_ERROR",
+        };
+        Ok((status.to_string(), BINARY_HEADER_SIZE + read as usize))
+    }
+
+    async fn exchange(
+        &self,
+        command: &KeyValueCommand,
+        stream: &mut BufReader<TcpStream>,
+    ) -> io::Result<(String, usize)> {
+        match self.protocol {
+            MemcachedProtocol::Text => {
+                stream
+                    .get_mut()
+                    .write_all(&MemcachedBenchAdapter::encode_text_command(command))
+                    .await?;
+                MemcachedBenchAdapter::read_text_reply(stream).await
+            }
+            MemcachedProtocol::Binary => {
+                let encoded = MemcachedBenchAdapter::encode_binary_command(command);
+                stream.get_mut().write_all(&encoded).await?;
+                MemcachedBenchAdapter::read_binary_reply(stream, encoded[1]).await
+            }
+        }
+    }
+
+    fn is_success(status: &str) -> bool {
+        matches!(status, HIT_STATUS | MISS_STATUS | "STORED")
+    }
+}
+
+#[async_trait]
+impl BenchmarkProtocolAdapter for MemcachedBenchAdapter {
+    type Client = MemcachedClient;
+
+    fn build_client(&self) -> Result<Self::Client, String> {
+        Ok(MemcachedClient {
+            connection: Mutex::new(None),
+        })
+    }
+
+    async fn send_request(&self, client: &Self::Client) -> RequestStats {
+        let command = self.workload.next_command();
+        let operation_name = match command {
+            KeyValueCommand::Get(_) => "GET",
+            KeyValueCommand::Set(_, _) => "SET",
+        };
+
+        let mut connection = client.connection.lock().await;
+
+        let mut phases = vec![];
+        let start = Instant::now();
+        if connection.is_none() {
+            match self.connect().await {
+                Ok(stream) => {
+                    phases.push(("connect", Instant::now().duration_since(start)));
+                    *connection = Some(BufReader::new(stream));
+                }
+                Err(e) => {
+                    error!("Error connecting: {}", e);
+                    return RequestStatsBuilder::default()
+                        .bytes_processed(0)
+                        .status(e.to_string())
+                        .is_success(false)
+                        .duration(Instant::now().duration_since(start))
+                        .operation_name(Some(operation_name.to_string()))
+                        .fatal_error(false)
+                        .build()
+                        .expect("RequestStatsBuilder failed");
+                }
+            }
+        }
+
+        let stream = connection.as_mut().expect("Connection must be established");
+        let start = Instant::now();
+        let result = self.exchange(&command, stream).await;
+        let duration = Instant::now().duration_since(start);
+
+        let (status, success, total_size) = match result {
+            Ok((status, total_size)) => {
+                let success = MemcachedBenchAdapter::is_success(&status);
+                (status, success, total_size)
+            }
+            Err(e) => {
+                error!("Error sending command: {}", e);
+                *connection = None;
+                (e.to_string(), false, 0)
+            }
+        };
+
+        RequestStatsBuilder::default()
+            .bytes_processed(total_size)
+            .status(status)
+            .is_success(success)
+            .duration(duration)
+            .operation_name(Some(operation_name.to_string()))
+            .phases(phases)
+            .fatal_error(false)
+            .build()
+            .expect("RequestStatsBuilder failed")
+    }
+}
+
+impl fmt::Display for MemcachedBenchAdapter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Targets={}, first target={}, protocol={:?}, workload={:?}",
+            self.target.len(),
+            self.target[0],
+            self.protocol,
+            self.workload
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bench_run::BenchmarkProtocolAdapter;
+    use crate::kv_workload::{KeyValueCommand, KeyValueWorkloadBuilder};
+    use crate::memcached_bench_session::{
+        MemcachedBenchAdapter, MemcachedBenchAdapterBuilder, MemcachedProtocol, HIT_STATUS,
+        MISS_STATUS,
+    };
+    use std::collections::HashMap;
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+
+    /// Minimal memcached server supporting get and set in both protocols.
+    async fn start_memcached_stub(protocol: MemcachedProtocol) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            while let Ok((socket, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let mut storage: HashMap<Vec<u8>, Vec<u8>> = HashMap::new();
+                    let mut reader = BufReader::new(socket);
+                    loop {
+                        let reply = match protocol {
+                            MemcachedProtocol::Text => {
+                                match text_command(&mut reader, &mut storage).await {
+                                    Some(reply) => reply,
+                                    None => break,
+                                }
+                            }
+                            MemcachedProtocol::Binary => {
+                                match binary_command(&mut reader, &mut storage).await {
+                                    Some(reply) => reply,
+                                    None => break,
+                                }
+                            }
+                        };
+                        reader.get_mut().write_all(&reply).await.unwrap();
+                    }
+                });
+            }
+        });
+        addr
+    }
+
+    async fn text_command(
+        reader: &mut BufReader<tokio::net::TcpStream>,
+        storage: &mut HashMap<Vec<u8>, Vec<u8>>,
+    ) -> Option<Vec<u8>> {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await.unwrap_or(0) == 0 {
+            return None;
+        }
+        let args: Vec<&str> = line.split_whitespace().collect();
+        Some(match args[0] {
+            "get" => match storage.get(args[1].as_bytes()) {
+                Some(v) => {
+                    let mut reply = format!("VALUE {} 0 {}\r\n", args[1], v.len()).into_bytes();
+                    reply.extend_from_slice(v);
+                    reply.extend_from_slice(b"\r\nEND\r\n");
+                    reply
+                }
+                None => b"END\r\n".to_vec(),
+            },
+            "set" => {
+                let length: usize = args[4].parse().unwrap();
+                let mut value = vec![0; length + 2];
+                reader.read_exact(&mut value).await.unwrap();
+                value.truncate(length);
+                storage.insert(args[1].as_bytes().to_vec(), value);
+                b"STORED\r\n".to_vec()
+            }
+            _ => b"ERROR\r\n".to_vec(),
+        })
+    }
+
+    async fn binary_command(
+        reader: &mut BufReader<tokio::net::TcpStream>,
+        storage: &mut HashMap<Vec<u8>, Vec<u8>>,
+    ) -> Option<Vec<u8>> {
+        let mut header = [0; 24];
+        reader.read_exact(&mut header).await.ok()?;
+        let key_length = u16::from_be_bytes([header[2], header[3]]) as usize;
+        let extras_length = header[4] as usize;
+        let body_length =
+            u32::from_be_bytes([header[8], header[9], header[10], header[11]]) as usize;
+        let mut body = vec![0; body_length];
+        reader.read_exact(&mut body).await.unwrap();
+        let key = body[extras_length..extras_length + key_length].to_vec();
+
+        let (status, body) = match header[1] {
+            0x00 => match storage.get(&key) {
+                Some(v) => {
+                    let mut body = vec![0; 4];
+                    body.extend_from_slice(v);
+                    (0u16, body)
+                }
+                None => (1, b"Not found".to_vec()),
+            },
+            _ => {
+                storage.insert(key, body[extras_length + key_length..].to_vec());
+                (0, vec![])
+            }
+        };
+
+        let mut reply = vec![0x81, header[1], 0, 0, 0, 0];
+        reply.extend_from_slice(&status.to_be_bytes());
+        reply.extend_from_slice(&(body.len() as u32).to_be_bytes());
+        reply.extend_from_slice(&[0; 12]);
+        reply.extend_from_slice(&body);
+        Some(reply)
+    }
+
+    fn memcached_adapter(
+        addr: String,
+        protocol: MemcachedProtocol,
+        set_ratio: f64,
+    ) -> MemcachedBenchAdapter {
+        MemcachedBenchAdapterBuilder::default()
+            .target(vec![addr])
+            .protocol(protocol)
+            .workload(
+                KeyValueWorkloadBuilder::default()
+                    .keyspace(1)
+                    .value_size((10, 10))
+                    .set_ratio(set_ratio)
+                    .build()
+                    .unwrap(),
+            )
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_encode_command() {
+        assert_eq!(
+            b"get k\r\n".to_vec(),
+            MemcachedBenchAdapter::encode_text_command(&KeyValueCommand::Get("k".to_string()))
+        );
+        assert_eq!(
+            b"set k 0 0 2\r\nab\r\n".to_vec(),
+            MemcachedBenchAdapter::encode_text_command(&KeyValueCommand::Set(
+                "k".to_string(),
+                "ab".to_string()
+            ))
+        );
+
+        let encoded = MemcachedBenchAdapter::encode_binary_command(&KeyValueCommand::Set(
+            "k".to_string(),
+            "ab".to_string(),
+        ));
+        assert_eq!(24 + 8 + 1 + 2, encoded.len());
+        assert_eq!(&[0x80, 0x01, 0, 1, 8], &encoded[..5]);
+        assert_eq!(&[0, 0, 0, 11], &encoded[8..12]);
+        assert_eq!(b"kab", &encoded[32..]);
+    }
+
+    #[tokio::test]
+    async fn test_read_text_reply() {
+        let test = vec![
+            ("VALUE k 0 5\r\nhello\r\nEND\r\n", HIT_STATUS, 25),
+            ("END\r\n", MISS_STATUS, 5),
+            ("STORED\r\n", "STORED", 8),
+            ("NOT_STORED\r\n", "NOT_STORED", 12),
+            ("SERVER_ERROR out of memory\r\n", "SERVER_ERROR", 28),
+        ];
+
+        for (input, expected_status, expected_size) in test {
+            let mut reader = BufReader::new(input.as_bytes());
+            let (status, size) = MemcachedBenchAdapter::read_text_reply(&mut reader)
+                .await
+                .expect("Valid reply");
+            assert_eq!(expected_status, status, "{}", input);
+            assert_eq!(expected_size, size, "{}", input);
+        }
+
+        let mut reader = BufReader::new("VALUE k 0 5\r\nhel".as_bytes());
+        assert!(MemcachedBenchAdapter::read_text_reply(&mut reader)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_hit_and_miss() {
+        for protocol in [MemcachedProtocol::Text, MemcachedProtocol::Binary] {
+            let addr = start_memcached_stub(protocol).await;
+
+            let get_bench = memcached_adapter(addr.clone(), protocol, 0.);
+            let client = get_bench.build_client().expect("Client is built");
+            let stats = get_bench.send_request(&client).await;
+            println!("{:?}", stats);
+            assert!(stats.is_success);
+            assert_eq!(MISS_STATUS.to_string(), stats.status);
+            assert_eq!(Some("GET".to_string()), stats.operation_name);
+            assert_eq!(1, stats.phases.len());
+
+            // the stub keeps values per connection
+            let set_bench = memcached_adapter(addr, protocol, 1.);
+            let stats = set_bench.send_request(&client).await;
+            println!("{:?}", stats);
+            assert!(stats.is_success);
+            assert_eq!("STORED".to_string(), stats.status);
+            assert_eq!(Some("SET".to_string()), stats.operation_name);
+            assert!(stats.phases.is_empty());
+
+            let stats = get_bench.send_request(&client).await;
+            println!("{:?}", stats);
+            assert!(stats.is_success);
+            assert_eq!(HIT_STATUS.to_string(), stats.status);
+        }
+    }
+}
@@ -1,8 +1,8 @@
+use crate::latency_histogram::{HistogramBackend, LatencyHistogram};
 use bytesize::ByteSize;
 use core::fmt;
-use histogram::Histogram;
 use log::{error, info};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::ops::AddAssign;
 use std::time::{Duration, Instant};
 use std::{cmp, io};
@@ -10,50 +10,75 @@ use std::{cmp, io};
 pub trait ExternalMetricsServiceReporter {
     fn report(&self, metrics: &BenchRunMetrics) -> io::Result<()>;
     fn reset_metrics(&self);
+
+    /// Whether periodic reports during a batch are sent to `report` too.
+    /// Otherwise, only the results of whole batches are, as snapshots overlap with them.
+    fn reports_snapshots(&self) -> bool {
+        false
+    }
 }
 
 pub struct DefaultConsoleReporter {
     test_case_name: Option<String>,
 }
 
+/// Periodic reports during a batch, see `--report_interval`.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub enum Snapshot {
+    /// Requests since the previous report.
+    Interval,
+    /// Requests since the beginning of the batch.
+    Cumulative,
+}
+
 #[derive(Clone)]
 pub struct BenchRunMetrics {
+    /// The number of concurrent clients of the batch.
+    pub(crate) concurrency: usize,
+    /// `None` for the results of the whole batch.
+    pub(crate) snapshot: Option<Snapshot>,
     pub(crate) combined: BenchRunMetricsItem,
     pub(crate) by_operation: HashMap<String, BenchRunMetricsItem>,
 }
 
 #[derive(Clone)]
 pub struct BenchRunMetricsItem {
+    /// Of all histograms of the item, so they can be merged.
+    backend: HistogramBackend,
     pub(crate) bench_begin: Instant,
     pub(crate) total_bytes: usize,
     pub(crate) total_requests: usize,
     pub(crate) successful_requests: usize,
     pub(crate) summary: HashMap<String, i32>,
-    pub(crate) success_latency: Histogram,
-    pub(crate) error_latency: Histogram,
+    pub(crate) success_latency: LatencyHistogram,
+    pub(crate) error_latency: LatencyHistogram,
+    pub(crate) phase_latency: BTreeMap<&'static str, LatencyHistogram>,
 }
 
 #[derive(Serialize)]
-struct BenchRunReport {
-    combined: BenchRunReportItem,
-    by_operation: HashMap<String, BenchRunReportItem>,
+pub(crate) struct BenchRunReport {
+    pub(crate) concurrency: usize,
+    pub(crate) snapshot: Option<Snapshot>,
+    pub(crate) combined: BenchRunReportItem,
+    pub(crate) by_operation: BTreeMap<String, BenchRunReportItem>,
 }
 
 /// Default reporter that prints stats to console.
 #[derive(Serialize)]
-struct BenchRunReportItem {
-    test_case_name: Option<String>,
-    duration: Duration,
-    total_bytes: usize,
-    total_requests: usize,
-    success_rate: f64,
-    rate_per_second: f64,
-    bitrate_mbps: f64,
-    response_code_summary: Vec<(String, i32)>,
-    latency_summary: Vec<(String, u64)>,
+pub(crate) struct BenchRunReportItem {
+    pub(crate) test_case_name: Option<String>,
+    pub(crate) duration: Duration,
+    pub(crate) total_bytes: usize,
+    pub(crate) total_requests: usize,
+    pub(crate) success_rate: f64,
+    pub(crate) rate_per_second: f64,
+    pub(crate) bitrate_mbps: f64,
+    pub(crate) response_code_summary: Vec<(String, i32)>,
+    pub(crate) latency_summary: Vec<(String, u64)>,
+    pub(crate) phase_latency_summary: Vec<(String, Vec<(String, u64)>)>,
 }
 
-#[derive(Builder, Debug)]
+#[derive(Builder, Debug, Clone)]
 pub struct RequestStats {
     pub is_success: bool,
     pub bytes_processed: usize,
@@ -62,37 +87,49 @@ pub struct RequestStats {
     #[builder(default = "None")]
     pub operation_name: Option<String>,
     pub fatal_error: bool,
+    /// Durations of individual phases of the request, e.g. `connect`.
+    #[builder(default)]
+    pub phases: Vec<(&'static str, Duration)>,
 }
 
 impl BenchRunMetrics {
-    pub fn new() -> Self {
+    pub fn new(backend: HistogramBackend) -> Self {
         Self {
-            combined: BenchRunMetricsItem::new(),
+            concurrency: 0,
+            snapshot: None,
+            combined: BenchRunMetricsItem::new(backend),
             by_operation: HashMap::new(),
         }
     }
 
+    pub fn backend(&self) -> HistogramBackend {
+        self.combined.backend
+    }
+
     pub fn report_request(&mut self, stats: RequestStats) {
         self.combined.report_request(&stats);
         if let Some(operation_name) = stats.operation_name.as_ref() {
+            let backend = self.combined.backend;
             self.by_operation
                 .entry(operation_name.to_owned())
-                .or_insert_with(BenchRunMetricsItem::new)
+                .or_insert_with(|| BenchRunMetricsItem::new(backend))
                 .report_request(&stats);
         }
     }
 }
 
 impl BenchRunMetricsItem {
-    pub fn new() -> Self {
+    pub fn new(backend: HistogramBackend) -> Self {
         Self {
+            backend,
             bench_begin: Instant::now(),
             total_bytes: 0,
             total_requests: 0,
             successful_requests: 0,
             summary: Default::default(),
-            success_latency: Default::default(),
-            error_latency: Default::default(),
+            success_latency: LatencyHistogram::new(backend),
+            error_latency: LatencyHistogram::new(backend),
+            phase_latency: Default::default(),
         }
     }
 
@@ -100,13 +137,16 @@ impl BenchRunMetricsItem {
         self.total_requests += 1;
         if stats.is_success {
             self.successful_requests += 1;
-            self.success_latency
-                .increment(stats.duration.as_micros() as u64)
-                .unwrap_or_default();
+            self.success_latency.increment(stats.duration);
         } else {
-            self.error_latency
-                .increment(stats.duration.as_micros() as u64)
-                .unwrap_or_default();
+            self.error_latency.increment(stats.duration);
+        }
+        let backend = self.backend;
+        for (phase, duration) in stats.phases.iter() {
+            self.phase_latency
+                .entry(phase)
+                .or_insert_with(|| LatencyHistogram::new(backend))
+                .increment(*duration);
         }
         self.total_bytes += stats.bytes_processed;
         self.summary
@@ -115,22 +155,37 @@ impl BenchRunMetricsItem {
             .add_assign(1);
     }
 
-    pub fn truncated_mean(histogram: &Histogram, threshold: f64) -> u64 {
-        let lowest = histogram.percentile(threshold).unwrap_or_default() as i64;
-        let highest = histogram.percentile(100. - threshold).unwrap_or_default() as i64;
+    /// In percents, `0` if there are no requests.
+    pub fn success_rate(&self) -> f64 {
+        if self.total_requests == 0 {
+            return 0.;
+        }
+        self.successful_requests as f64 * 100. / self.total_requests as f64
+    }
+
+    /// Of both successful and failed requests.
+    pub fn latency_percentile(&self, percentile: f64) -> Duration {
+        let mut latency = self.success_latency.clone();
+        latency.merge(&self.error_latency);
+        Duration::from_micros(latency.percentile(percentile))
+    }
+
+    pub fn truncated_mean(histogram: &LatencyHistogram, threshold: f64) -> u64 {
+        let lowest = histogram.percentile(threshold) as f64;
+        let highest = histogram.percentile(100. - threshold) as f64;
         let mut ignored_count = 0;
         let mut count = 0;
-        let mut sum = 0_u64;
-        for bucket in histogram.into_iter() {
-            if bucket.value() as i64 >= lowest && bucket.value() as i64 <= highest {
-                count += bucket.count();
-                sum += bucket.value() * bucket.count();
+        let mut sum = 0.;
+        for (value, value_count) in histogram.buckets() {
+            if value >= lowest && value <= highest {
+                count += value_count;
+                sum += value * value_count as f64;
             } else {
-                ignored_count += bucket.count();
+                ignored_count += value_count;
             }
         }
         if count > 0 {
-            let truncated_mean = sum / count;
+            let truncated_mean = (sum / count as f64) as u64;
             info!(
                 "Truncated mean {:.3}: ignored {} data points out of {}, the %={:.6}. TM={}µs",
                 threshold,
@@ -175,42 +230,44 @@ impl BenchRunReportItem {
         let mut latency = metrics.success_latency.clone();
         latency.merge(&metrics.error_latency);
 
+        BenchRunReportItem::histogram_summary(&latency)
+    }
+
+    fn phase_latency_summary(metrics: &BenchRunMetricsItem) -> Vec<(String, Vec<(String, u64)>)> {
+        metrics
+            .phase_latency
+            .iter()
+            .map(|(phase, latency)| {
+                (
+                    phase.to_string(),
+                    BenchRunReportItem::histogram_summary(latency),
+                )
+            })
+            .collect()
+    }
+
+    fn histogram_summary(latency: &LatencyHistogram) -> Vec<(String, u64)> {
         vec![
-            ("Min".to_string(), latency.minimum().unwrap_or_default()),
-            (
-                "p50".to_string(),
-                latency.percentile(50.0).unwrap_or_default(),
-            ),
-            (
-                "p90".to_string(),
-                latency.percentile(90.0).unwrap_or_default(),
-            ),
-            (
-                "p99".to_string(),
-                latency.percentile(99.0).unwrap_or_default(),
-            ),
-            (
-                "p99.9".to_string(),
-                latency.percentile(99.9).unwrap_or_default(),
-            ),
-            (
-                "p99.99".to_string(),
-                latency.percentile(99.99).unwrap_or_default(),
-            ),
-            ("Max".to_string(), latency.maximum().unwrap_or_default()),
-            ("Mean".to_string(), latency.mean().unwrap_or_default()),
-            ("StdDev".to_string(), latency.stddev().unwrap_or_default()),
+            ("Min".to_string(), latency.minimum()),
+            ("p50".to_string(), latency.percentile(50.0)),
+            ("p90".to_string(), latency.percentile(90.0)),
+            ("p99".to_string(), latency.percentile(99.0)),
+            ("p99.9".to_string(), latency.percentile(99.9)),
+            ("p99.99".to_string(), latency.percentile(99.99)),
+            ("Max".to_string(), latency.maximum()),
+            ("Mean".to_string(), latency.mean()),
+            ("StdDev".to_string(), latency.stddev()),
             (
                 "tm95".to_string(),
-                BenchRunMetricsItem::truncated_mean(&latency, 5.0),
+                BenchRunMetricsItem::truncated_mean(latency, 5.0),
             ),
             (
                 "tm99".to_string(),
-                BenchRunMetricsItem::truncated_mean(&latency, 1.0),
+                BenchRunMetricsItem::truncated_mean(latency, 1.0),
             ),
             (
                 "tm99.9".to_string(),
-                BenchRunMetricsItem::truncated_mean(&latency, 0.1),
+                BenchRunMetricsItem::truncated_mean(latency, 0.1),
             ),
         ]
     }
@@ -218,7 +275,21 @@ impl BenchRunReportItem {
 
 impl fmt::Display for BenchRunReport {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        writeln!(f, "{}", self.combined)
+        match self.snapshot {
+            Some(Snapshot::Interval) => writeln!(f, "Interval report")?,
+            Some(Snapshot::Cumulative) => writeln!(f, "Cumulative report (the step so far)")?,
+            None => {}
+        }
+        if self.concurrency > 0 {
+            writeln!(f, "Concurrency: {}", self.concurrency)?;
+        }
+        writeln!(f, "{}", self.combined)?;
+        for (operation, item) in self.by_operation.iter() {
+            writeln!(f, "{}", "-".repeat(50))?;
+            writeln!(f, "Operation: {}", operation)?;
+            writeln!(f, "{}", item)?;
+        }
+        Ok(())
     }
 }
 
@@ -257,38 +328,12 @@ impl fmt::Display for BenchRunReportItem {
         if !self.latency_summary.is_empty() {
             writeln!(f)?;
             writeln!(f, "Latency:")?;
-            let mut max_label_len = 0;
-            let mut max_value_len = 0;
-            let mut min_value = 1_000_000_000;
-            for (label, value) in self.latency_summary.iter() {
-                max_label_len = max_label_len.max(label.len());
-                max_value_len = max_value_len.max(value.to_string().len());
-                min_value = min_value.min(*value);
-            }
-            let use_ms = min_value >= 1_000;
-
-            for (label, value) in self.latency_summary.iter() {
-                let label_spacing = " ".repeat(max_label_len - label.len() + 1);
-                let value_spacing = " ".repeat(max_value_len - value.to_string().len() + 1);
-                if use_ms {
-                    writeln!(
-                        f,
-                        "{label}{label_spacing}:{value_spacing}{value:.2}ms",
-                        label = label,
-                        value = *value as f64 / 1000.0,
-                        label_spacing = label_spacing,
-                        value_spacing = value_spacing
-                    )?;
-                } else {
-                    writeln!(
-                        f,
-                        "{label}{label_spacing}:{value_spacing}{value}µs",
-                        label = label,
-                        value = value,
-                        label_spacing = label_spacing,
-                        value_spacing = value_spacing
-                    )?;
-                }
+            BenchRunReportItem::write_latency_summary(f, &self.latency_summary)?;
+
+            for (phase, latency_summary) in self.phase_latency_summary.iter() {
+                writeln!(f)?;
+                writeln!(f, "Latency ({}):", phase)?;
+                BenchRunReportItem::write_latency_summary(f, latency_summary)?;
             }
             Ok(())
         } else {
@@ -297,6 +342,48 @@ impl fmt::Display for BenchRunReportItem {
     }
 }
 
+impl BenchRunReportItem {
+    fn write_latency_summary(
+        f: &mut fmt::Formatter<'_>,
+        latency_summary: &[(String, u64)],
+    ) -> fmt::Result {
+        let mut max_label_len = 0;
+        let mut max_value_len = 0;
+        let mut min_value = 1_000_000_000;
+        for (label, value) in latency_summary.iter() {
+            max_label_len = max_label_len.max(label.len());
+            max_value_len = max_value_len.max(value.to_string().len());
+            min_value = min_value.min(*value);
+        }
+        let use_ms = min_value >= 1_000;
+
+        for (label, value) in latency_summary.iter() {
+            let label_spacing = " ".repeat(max_label_len - label.len() + 1);
+            let value_spacing = " ".repeat(max_value_len - value.to_string().len() + 1);
+            if use_ms {
+                writeln!(
+                    f,
+                    "{label}{label_spacing}:{value_spacing}{value:.2}ms",
+                    label = label,
+                    value = *value as f64 / 1000.0,
+                    label_spacing = label_spacing,
+                    value_spacing = value_spacing
+                )?;
+            } else {
+                writeln!(
+                    f,
+                    "{label}{label_spacing}:{value_spacing}{value}µs",
+                    label = label,
+                    value = value,
+                    label_spacing = label_spacing,
+                    value_spacing = value_spacing
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
 // cov:begin-ignore-line
 impl ExternalMetricsServiceReporter for DefaultConsoleReporter {
     fn report(&self, metrics: &BenchRunMetrics) -> io::Result<()> {
@@ -311,6 +398,10 @@ impl ExternalMetricsServiceReporter for DefaultConsoleReporter {
     fn reset_metrics(&self) {
         // do nothing
     }
+
+    fn reports_snapshots(&self) -> bool {
+        true
+    }
 }
 
 impl DefaultConsoleReporter {
@@ -318,19 +409,20 @@ impl DefaultConsoleReporter {
         Self { test_case_name }
     }
 
-    fn sorted_operations(metrics: &BenchRunMetrics) -> Vec<String> {
-        let sorted_operation_name: Vec<String> =
-            metrics.by_operation.keys().map(|s| s.to_owned()).collect();
-        sorted_operation_name
+    fn build_report(&self, metrics: &BenchRunMetrics) -> BenchRunReport {
+        BenchRunReport::new(self.test_case_name.clone(), metrics)
     }
+}
 
-    fn build_report(&self, metrics: &BenchRunMetrics) -> BenchRunReport {
-        let mut by_operation = HashMap::new();
-        let sorted_operation_name = DefaultConsoleReporter::sorted_operations(metrics);
+impl BenchRunReport {
+    pub(crate) fn new(test_case_name: Option<String>, metrics: &BenchRunMetrics) -> Self {
+        let mut by_operation = BTreeMap::new();
+        let sorted_operation_name = BenchRunReport::sorted_operations(metrics);
         for operation in sorted_operation_name {
             by_operation.insert(
                 operation.to_owned(),
-                self.build_item_report(
+                BenchRunReportItem::new(
+                    test_case_name.clone(),
                     metrics
                         .by_operation
                         .get(&operation)
@@ -338,23 +430,30 @@ impl DefaultConsoleReporter {
                 ),
             );
         }
-        BenchRunReport {
-            combined: self.build_item_report(&metrics.combined),
+        Self {
+            concurrency: metrics.concurrency,
+            snapshot: metrics.snapshot,
+            combined: BenchRunReportItem::new(test_case_name, &metrics.combined),
             by_operation,
         }
     }
 
-    fn build_item_report(&self, metrics: &BenchRunMetricsItem) -> BenchRunReportItem {
-        let successful_requests = metrics.successful_requests as usize;
-        let total_requests = metrics.total_requests as usize;
-        let total_bytes = metrics.total_bytes as usize;
+    fn sorted_operations(metrics: &BenchRunMetrics) -> Vec<String> {
+        let mut sorted_operation_name: Vec<String> =
+            metrics.by_operation.keys().map(|s| s.to_owned()).collect();
+        sorted_operation_name.sort();
+        sorted_operation_name
+    }
+}
+
+impl BenchRunReportItem {
+    fn new(test_case_name: Option<String>, metrics: &BenchRunMetricsItem) -> Self {
+        let successful_requests = metrics.successful_requests;
+        let total_requests = metrics.total_requests;
+        let total_bytes = metrics.total_bytes;
         let duration = Instant::now().duration_since(metrics.bench_begin);
-        BenchRunReportItem {
-            test_case_name: self
-                .test_case_name
-                .as_ref()
-                .cloned()
-                .or_else(|| Some("perf-gauge".to_string())),
+        Self {
+            test_case_name: test_case_name.or_else(|| Some("perf-gauge".to_string())),
             duration,
             total_bytes,
             total_requests,
@@ -363,6 +462,7 @@ impl DefaultConsoleReporter {
             bitrate_mbps: total_bytes as f64 / duration.as_secs_f64() * 8. / 1_000_000.,
             response_code_summary: BenchRunReportItem::summary_ordered(metrics),
             latency_summary: BenchRunReportItem::latency_summary(metrics),
+            phase_latency_summary: BenchRunReportItem::phase_latency_summary(metrics),
         }
     }
 }
@@ -371,14 +471,15 @@ impl DefaultConsoleReporter {
 #[cfg(test)]
 mod tests {
     use crate::bench_run::BenchRun;
-    use crate::metrics::{BenchRunMetrics, DefaultConsoleReporter, RequestStats};
-    use crate::rate_limiter::RateLimiter;
+    use crate::latency_histogram::HistogramBackend;
+    use crate::metrics::{BenchRunMetrics, DefaultConsoleReporter, RequestStats, Snapshot};
+    use crate::rate_limiter::{ArrivalProcess, RateLimiter};
     use std::thread::sleep;
     use std::time::Duration;
 
     #[test]
     fn test_codes() {
-        let mut metrics = BenchRunMetrics::new();
+        let mut metrics = BenchRunMetrics::new(HistogramBackend::Simple);
         let codes = vec![
             "200 OK".to_string(),
             "200 OK".to_string(),
@@ -396,6 +497,7 @@ mod tests {
                 duration: Default::default(),
                 operation_name: None,
                 fatal_error: false,
+                phases: vec![],
             });
         }
 
@@ -417,7 +519,7 @@ mod tests {
 
     #[test]
     fn test_latency() {
-        let mut metrics = BenchRunMetrics::new();
+        let mut metrics = BenchRunMetrics::new(HistogramBackend::Simple);
         for i in 0..1000 {
             metrics.report_request(RequestStats {
                 is_success: true,
@@ -426,6 +528,7 @@ mod tests {
                 duration: Duration::from_micros(i),
                 operation_name: None,
                 fatal_error: false,
+                phases: vec![],
             });
         }
 
@@ -445,7 +548,7 @@ mod tests {
 
     #[test]
     fn test_by_operation_reporting() {
-        let mut metrics = BenchRunMetrics::new();
+        let mut metrics = BenchRunMetrics::new(HistogramBackend::Simple);
         for i in 0..1000 {
             metrics.report_request(RequestStats {
                 is_success: true,
@@ -458,11 +561,12 @@ mod tests {
                     Some("OperationB".to_string())
                 },
                 fatal_error: false,
+                phases: vec![],
             });
         }
 
         let report = DefaultConsoleReporter::new(None).build_report(&metrics);
-        let mut items = report.combined.latency_summary.to_owned().into_iter();
+        let mut items = report.combined.latency_summary.iter().cloned();
 
         assert_eq!(Some(("Min".to_string(), 0)), items.next());
         assert_eq!(Some(("p50".to_string(), 500)), items.next());
@@ -481,8 +585,8 @@ mod tests {
             .get("OperationA")
             .unwrap()
             .latency_summary
-            .to_owned()
-            .into_iter();
+            .iter()
+            .cloned();
         assert_eq!(Some(("Min".to_string(), 0)), items.next());
         assert_eq!(Some(("p50".to_string(), 500)), items.next());
         assert_eq!(Some(("p90".to_string(), 900)), items.next());
@@ -498,8 +602,8 @@ mod tests {
             .get("OperationB")
             .unwrap()
             .latency_summary
-            .to_owned()
-            .into_iter();
+            .iter()
+            .cloned();
         assert_eq!(Some(("Min".to_string(), 1)), items.next());
         assert_eq!(Some(("p50".to_string(), 501)), items.next());
         assert_eq!(Some(("p90".to_string(), 901)), items.next());
@@ -511,11 +615,47 @@ mod tests {
         assert_eq!(Some(("StdDev".to_string(), 289)), items.next());
     }
 
+    #[test]
+    fn test_phase_latency() {
+        let mut metrics = BenchRunMetrics::new(HistogramBackend::Simple);
+        for i in 0..1000 {
+            metrics.report_request(RequestStats {
+                is_success: true,
+                bytes_processed: 0,
+                status: "OK".to_string(),
+                duration: Duration::from_micros(i),
+                operation_name: None,
+                fatal_error: false,
+                phases: if i % 10 == 0 {
+                    vec![("connect", Duration::from_micros(i / 10))]
+                } else {
+                    vec![]
+                },
+            });
+        }
+
+        let report = DefaultConsoleReporter::new(None).build_report(&metrics);
+
+        assert_eq!(1, report.combined.phase_latency_summary.len());
+        let (phase, latency_summary) = &report.combined.phase_latency_summary[0];
+        assert_eq!("connect", phase);
+
+        let mut items = latency_summary.iter().cloned();
+        assert_eq!(Some(("Min".to_string(), 0)), items.next());
+        assert_eq!(Some(("p50".to_string(), 50)), items.next());
+
+        assert!(report.to_string().contains("Latency (connect):"));
+    }
+
     #[test]
     fn test_has_more_work_request_limit() {
         let requests = 10;
-        let mut metrics =
-            BenchRun::from_request_limit(0, requests, RateLimiter::build_rate_limiter(0.), None);
+        let mut metrics = BenchRun::from_request_limit(
+            0,
+            requests,
+            RateLimiter::build_rate_limiter(0., ArrivalProcess::Constant),
+            None,
+        );
         for _ in 0..requests {
             assert!(metrics.has_more_work());
         }
@@ -525,8 +665,12 @@ mod tests {
     #[test]
     fn test_has_more_work_time_limit() {
         let duration = Duration::from_secs(1);
-        let mut metrics =
-            BenchRun::from_duration_limit(0, duration, RateLimiter::build_rate_limiter(0.), None);
+        let mut metrics = BenchRun::from_duration_limit(
+            0,
+            duration,
+            RateLimiter::build_rate_limiter(0., ArrivalProcess::Constant),
+            None,
+        );
         for _ in 0..1000 {
             assert!(metrics.has_more_work());
         }
@@ -536,7 +680,7 @@ mod tests {
 
     #[test]
     fn test_bench_run_report_display() {
-        let mut metrics = BenchRunMetrics::new();
+        let mut metrics = BenchRunMetrics::new(HistogramBackend::Simple);
         for i in 0..1000 {
             metrics.report_request(RequestStats {
                 is_success: true,
@@ -545,6 +689,7 @@ mod tests {
                 duration: Duration::from_micros(i),
                 operation_name: None,
                 fatal_error: false,
+                phases: vec![],
             });
         }
 
@@ -563,5 +708,10 @@ mod tests {
         assert!(as_str.contains("tm95"));
         assert!(as_str.contains("tm99"));
         assert!(as_str.contains("tm99.9"));
+        assert!(!as_str.contains("Interval report"));
+
+        metrics.snapshot = Some(Snapshot::Interval);
+        let report = DefaultConsoleReporter::new(None).build_report(&metrics);
+        assert!(report.to_string().contains("Interval report"));
     }
 }
@@ -1,23 +1,34 @@
-use crate::metrics::{BenchRunMetrics, BenchRunMetricsItem, ExternalMetricsServiceReporter};
-use histogram::Histogram;
+use crate::latency_histogram::{HistogramBackend, LatencyHistogram};
+use crate::metrics::{
+    BenchRunMetrics, BenchRunMetricsItem, ExternalMetricsServiceReporter, Snapshot,
+};
 use log::info;
 use prometheus::core::{AtomicI64, GenericGauge, GenericGaugeVec};
 use prometheus::{BasicAuthentication, HistogramOpts, Opts, Registry};
 use std::collections::HashMap;
 use std::io;
+use std::sync::Mutex;
 
 pub struct PrometheusReporter {
     test_case_name: Option<String>,
     job: String,
     address: String,
     basic_auth: Option<prometheus::BasicAuthentication>,
+    /// Kinds of periodic reports pushed so far, to reset them at the end.
+    snapshots: Mutex<Vec<Snapshot>>,
 }
 
 impl ExternalMetricsServiceReporter for PrometheusReporter {
     fn report(&self, metrics: &BenchRunMetrics) -> io::Result<()> {
-        self.report_item(None, &metrics.combined)?;
+        if let Some(snapshot) = metrics.snapshot {
+            let mut snapshots = self.snapshots.lock().expect("Poisoned snapshots");
+            if !snapshots.contains(&snapshot) {
+                snapshots.push(snapshot);
+            }
+        }
+        self.report_item(metrics.snapshot, None, &metrics.combined)?;
         for (operation, metrics_item) in metrics.by_operation.iter() {
-            self.report_item(Some(operation.to_owned()), metrics_item)?;
+            self.report_item(metrics.snapshot, Some(operation.to_owned()), metrics_item)?;
         }
         Ok(())
     }
@@ -25,7 +36,16 @@ impl ExternalMetricsServiceReporter for PrometheusReporter {
     fn reset_metrics(&self) {
         info!("Stop sending metrics to Prometheus: {}", self.address);
         // send empty metrics to reset counters
-        self.report(&BenchRunMetrics::new()).unwrap_or_default();
+        let snapshots = self.snapshots.lock().expect("Poisoned snapshots").clone();
+        for snapshot in std::iter::once(None).chain(snapshots.into_iter().map(Some)) {
+            let mut metrics = BenchRunMetrics::new(HistogramBackend::default());
+            metrics.snapshot = snapshot;
+            self.report(&metrics).unwrap_or_default();
+        }
+    }
+
+    fn reports_snapshots(&self) -> bool {
+        true
     }
 }
 
@@ -37,11 +57,13 @@ impl PrometheusReporter {
             job: job.unwrap_or("pushgateway").to_string(),
             address: addr,
             basic_auth: None,
+            snapshots: Mutex::new(vec![]),
         }
     }
 
     fn report_item(
         &self,
+        snapshot: Option<Snapshot>,
         operation_name: Option<String>,
         metrics: &BenchRunMetricsItem,
     ) -> io::Result<()> {
@@ -51,18 +73,9 @@ impl PrometheusReporter {
 
         let metric_families = registry.gather();
 
-        let mut labels_map = HashMap::new();
-        labels_map.insert(
-            "testname".to_string(),
-            self.test_case_name
-                .as_ref()
-                .cloned()
-                .unwrap_or_else(|| "perf-gauge".to_string()),
-        );
-
         prometheus::push_metrics(
             &self.job,
-            labels_map,
+            self.build_grouping_labels(snapshot),
             &self.address,
             metric_families,
             self.basic_auth.as_ref().map(|auth| BasicAuthentication {
@@ -70,13 +83,45 @@ impl PrometheusReporter {
                 password: auth.password.clone(),
             }),
         )
-        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+        .map_err(io::Error::other)
     }
 
+    /// Periodic reports are pushed to their own groups (with the `snapshot` label),
+    /// so they don't overwrite the results of whole batches.
+    fn build_grouping_labels(&self, snapshot: Option<Snapshot>) -> HashMap<String, String> {
+        let mut labels_map = HashMap::new();
+        labels_map.insert(
+            "testname".to_string(),
+            self.test_case_name
+                .as_ref()
+                .cloned()
+                .unwrap_or_else(|| "perf-gauge".to_string()),
+        );
+        if let Some(snapshot) = snapshot {
+            let label = match snapshot {
+                Snapshot::Interval => "interval",
+                Snapshot::Cumulative => "cumulative",
+            };
+            labels_map.insert("snapshot".to_string(), label.to_string());
+        }
+        labels_map
+    }
+
+    /// Operation names (e.g. `GET /items`) may contain characters, which aren't allowed in metric names.
     fn build_metric_name(operation_name: &Option<String>, name: &str) -> String {
         operation_name
             .as_ref()
-            .map(|s| format!("{}_{}", s, name))
+            .map(|s| {
+                let prefix: String = s
+                    .chars()
+                    .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+                    .collect();
+                if prefix.starts_with(|c: char| c.is_ascii_digit()) {
+                    format!("_{}_{}", prefix, name)
+                } else {
+                    format!("{}_{}", prefix, name)
+                }
+            })
             .unwrap_or_else(|| name.to_string())
     }
 
@@ -134,6 +179,18 @@ impl PrometheusReporter {
             latency,
         );
 
+        for (phase, latency) in bench_run_metrics.phase_latency.iter() {
+            PrometheusReporter::register_histogram(
+                &registry,
+                PrometheusReporter::build_metric_name(
+                    &operation_name,
+                    &format!("{}_latency", phase),
+                ),
+                &format!("Latency of the {} phase", phase),
+                latency.clone(),
+            );
+        }
+
         registry
     }
 
@@ -164,15 +221,13 @@ impl PrometheusReporter {
             .for_each(|(k, v)| codes.with_label_values(&[k]).set((*v).into()))
     }
 
-    fn register_histogram(registry: &Registry, name: String, help: &str, histogram: Histogram) {
-        let mut buckets = vec![];
-        let mut counts = vec![];
-        for bucket in histogram.into_iter() {
-            if bucket.count() > 0 {
-                buckets.push(bucket.value() as f64);
-                counts.push(bucket.count());
-            }
-        }
+    fn register_histogram(
+        registry: &Registry,
+        name: String,
+        help: &str,
+        histogram: LatencyHistogram,
+    ) {
+        let (buckets, counts): (Vec<f64>, Vec<u64>) = histogram.buckets().into_iter().unzip();
         info!(
             "Prometheus histogram {}: buckets {}, observations {}",
             name,
@@ -191,7 +246,7 @@ impl PrometheusReporter {
 
         for i in 0..buckets.len() {
             for _ in 0..counts[i] {
-                prometheus_histogram.observe(buckets[i] as f64);
+                prometheus_histogram.observe(buckets[i]);
             }
         }
 
@@ -202,33 +257,18 @@ impl PrometheusReporter {
         registry: &Registry,
         name: String,
         help: &str,
-        histogram: Histogram,
+        histogram: LatencyHistogram,
     ) {
         let percentiles = vec![
-            ("min".to_string(), histogram.minimum().unwrap_or_default()),
-            (
-                "p50".to_string(),
-                histogram.percentile(50.0).unwrap_or_default(),
-            ),
-            (
-                "p90".to_string(),
-                histogram.percentile(90.0).unwrap_or_default(),
-            ),
-            (
-                "p99".to_string(),
-                histogram.percentile(99.0).unwrap_or_default(),
-            ),
-            (
-                "p99_9".to_string(),
-                histogram.percentile(99.9).unwrap_or_default(),
-            ),
-            (
-                "p99_99".to_string(),
-                histogram.percentile(99.99).unwrap_or_default(),
-            ),
-            ("max".to_string(), histogram.maximum().unwrap_or_default()),
-            ("mean".to_string(), histogram.mean().unwrap_or_default()),
-            ("stddev".to_string(), histogram.stddev().unwrap_or_default()),
+            ("min".to_string(), histogram.minimum()),
+            ("p50".to_string(), histogram.percentile(50.0)),
+            ("p90".to_string(), histogram.percentile(90.0)),
+            ("p99".to_string(), histogram.percentile(99.0)),
+            ("p99_9".to_string(), histogram.percentile(99.9)),
+            ("p99_99".to_string(), histogram.percentile(99.99)),
+            ("max".to_string(), histogram.maximum()),
+            ("mean".to_string(), histogram.mean()),
+            ("stddev".to_string(), histogram.stddev()),
             (
                 "tm95".to_string(),
                 BenchRunMetricsItem::truncated_mean(&histogram, 5.0),
@@ -255,12 +295,12 @@ impl PrometheusReporter {
 
 #[cfg(test)]
 mod test {
+    use crate::latency_histogram::{HistogramBackend, LatencyHistogram};
     use crate::metrics::{
         BenchRunMetrics, DefaultConsoleReporter, ExternalMetricsServiceReporter, RequestStats,
-        RequestStatsBuilder,
+        RequestStatsBuilder, Snapshot,
     };
     use crate::prometheus_reporter::PrometheusReporter;
-    use histogram::Histogram;
     use mockito::mock;
     use prometheus::proto::*;
     use prometheus::Registry;
@@ -297,11 +337,11 @@ mod test {
     #[test]
     fn test_register_histogram() {
         let registry = Registry::new();
-        let mut histogram = Histogram::new();
-        histogram.increment(100).expect("infallible");
-        histogram.increment(200).expect("infallible");
-        histogram.increment(300).expect("infallible");
-        histogram.increment(300).expect("infallible");
+        let mut histogram = LatencyHistogram::new(HistogramBackend::Simple);
+        histogram.increment(Duration::from_micros(100));
+        histogram.increment(Duration::from_micros(200));
+        histogram.increment(Duration::from_micros(300));
+        histogram.increment(Duration::from_micros(300));
 
         PrometheusReporter::register_histogram(
             &registry,
@@ -351,7 +391,7 @@ mod test {
 
     #[test]
     fn test_build_registry_combined() {
-        let mut metrics = BenchRunMetrics::new();
+        let mut metrics = BenchRunMetrics::new(HistogramBackend::Simple);
         let mut total_bytes = 0;
         let mut successful_requests = 0;
         let mut total_requests = 0;
@@ -457,9 +497,25 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_build_metric_name() {
+        assert_eq!(
+            "latency",
+            PrometheusReporter::build_metric_name(&None, "latency")
+        );
+        assert_eq!(
+            "GET__api_items_latency",
+            PrometheusReporter::build_metric_name(&Some("GET /api/items".to_string()), "latency")
+        );
+        assert_eq!(
+            "_2xx_latency",
+            PrometheusReporter::build_metric_name(&Some("2xx".to_string()), "latency")
+        );
+    }
+
     #[test]
     fn test_build_registry_with_operation() {
-        let mut metrics = BenchRunMetrics::new();
+        let mut metrics = BenchRunMetrics::new(HistogramBackend::Simple);
         let mut total_bytes = 0;
         let mut successful_requests = 0;
         let mut total_requests = 0;
@@ -588,7 +644,7 @@ mod test {
             Some("prometheus_job"),
         );
 
-        let mut metrics = BenchRunMetrics::new();
+        let mut metrics = BenchRunMetrics::new(HistogramBackend::Simple);
         for i in 0..1000 {
             metrics.report_request(RequestStats {
                 is_success: true,
@@ -597,6 +653,7 @@ mod test {
                 duration: Duration::from_micros(i),
                 operation_name: None,
                 fatal_error: false,
+                phases: vec![],
             });
         }
 
@@ -604,4 +661,22 @@ mod test {
 
         assert!(sent.is_ok(), "{:?}", sent);
     }
+
+    #[test]
+    fn test_grouping_labels() {
+        let reporter = PrometheusReporter::new(Some("test".to_string()), "".to_string(), None);
+        let labels = reporter.build_grouping_labels(None);
+        assert_eq!(1, labels.len());
+        assert_eq!("test", labels["testname"]);
+
+        // periodic reports don't overwrite the results of the batch
+        for (snapshot, label) in [
+            (Snapshot::Interval, "interval"),
+            (Snapshot::Cumulative, "cumulative"),
+        ] {
+            let labels = reporter.build_grouping_labels(Some(snapshot));
+            assert_eq!("test", labels["testname"]);
+            assert_eq!(label, labels["snapshot"]);
+        }
+    }
 }
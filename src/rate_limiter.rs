@@ -5,20 +5,65 @@
 /// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
 /// option. This file may not be copied, modified, or distributed
 /// except according to those terms.
+use core::fmt;
 use leaky_bucket::{LeakyBucket, LeakyBuckets};
 use log::{debug, error};
-use std::time::Duration;
+use rand::{thread_rng, Rng};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How requests are spread in time. The average rate is the same for all of them.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ArrivalProcess {
+    /// Evenly spaced requests.
+    #[default]
+    Constant,
+    /// Exponentially distributed intervals, i.e. independent random arrivals.
+    Poisson,
+    /// Evenly spaced requests during `on` periods, followed by `off` periods of silence.
+    Bursty { on: Duration, off: Duration },
+}
 
 #[derive(Clone, Debug)]
 pub struct RateLimiter {
     leaky_bucket: Option<LeakyBucket>,
+    /// Intended send times, `None` if unlimited.
+    schedule: Option<Arc<Mutex<ArrivalSchedule>>>,
+}
+
+#[derive(Debug)]
+struct ArrivalSchedule {
+    arrival: ArrivalProcess,
+    /// The mean interval between requests.
+    interval: Duration,
+    next: Instant,
+    /// Of the next request within the on period of the on/off cycle.
+    cycle_position: Duration,
 }
 
 impl RateLimiter {
-    pub fn build_rate_limiter(rate_per_second: f64) -> RateLimiter {
+    pub fn build_rate_limiter(rate_per_second: f64, arrival: ArrivalProcess) -> RateLimiter {
         if rate_per_second == 0. {
             // unlimited
-            return RateLimiter { leaky_bucket: None };
+            return RateLimiter {
+                leaky_bucket: None,
+                schedule: None,
+            };
+        }
+
+        let schedule = Some(Arc::new(Mutex::new(ArrivalSchedule {
+            arrival,
+            interval: Duration::from_secs_f64(1. / rate_per_second),
+            next: Instant::now(),
+            cycle_position: Duration::ZERO,
+        })));
+
+        if arrival != ArrivalProcess::Constant {
+            return RateLimiter {
+                leaky_bucket: None,
+                schedule,
+            };
         }
 
         let (amount, interval) = RateLimiter::rate_to_refill_amount_and_duration(rate_per_second);
@@ -54,17 +99,28 @@ impl RateLimiter {
                     .build()
                     .expect("LeakyBucket builder failed"),
             ),
+            schedule,
         }
     }
 
+    /// The intended send time of the next request, `None` if unlimited.
+    pub fn next_arrival(&self) -> Option<Instant> {
+        self.schedule
+            .as_ref()
+            .map(|s| s.lock().expect("Poisoned schedule").next_arrival())
+    }
+
     pub async fn acquire_one(&self) -> Result<(), String> {
-        match self.leaky_bucket.as_ref() {
-            None => Ok(()),
-            Some(leaky_bucket) => leaky_bucket.acquire_one().await.map_err(|e| {
+        if let Some(leaky_bucket) = self.leaky_bucket.as_ref() {
+            return leaky_bucket.acquire_one().await.map_err(|e| {
                 error!("Error acquiring permit: {}", e);
                 e.to_string()
-            }),
+            });
+        }
+        if let Some(arrival) = self.next_arrival() {
+            tokio::time::sleep_until(arrival.into()).await;
         }
+        Ok(())
     }
 
     fn gcd(mut a: usize, mut b: usize) -> usize {
@@ -95,14 +151,92 @@ impl RateLimiter {
     }
 }
 
+impl ArrivalSchedule {
+    fn next_arrival(&mut self) -> Instant {
+        let arrival = self.next;
+        let interval = self.next_interval();
+        self.next += interval;
+        arrival
+    }
+
+    fn next_interval(&mut self) -> Duration {
+        match self.arrival {
+            ArrivalProcess::Constant => self.interval,
+            ArrivalProcess::Poisson => {
+                let uniform: f64 = thread_rng().gen();
+                self.interval.mul_f64(-(1. - uniform).ln())
+            }
+            ArrivalProcess::Bursty { on, off } => {
+                // on average, the same number of requests per cycle as with constant spacing,
+                // so the part of the interval past the on period is carried to the next cycle(s)
+                let cycle = on + off;
+                let interval = self
+                    .interval
+                    .mul_f64(on.as_secs_f64() / cycle.as_secs_f64());
+                let position = self.cycle_position + interval;
+                let cycles = (position.as_nanos() / on.as_nanos()) as u32;
+                self.cycle_position = position - on * cycles;
+                interval + off * cycles
+            }
+        }
+    }
+}
+
+/// `constant`, `poisson` or `bursty:ON/OFF`, e.g. `bursty:1s/4s`.
+impl FromStr for ArrivalProcess {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse_duration = |d: &str| -> Result<Duration, String> {
+            humantime::Duration::from_str(d.trim())
+                .map(|d| d.into())
+                .map_err(|e| format!("Illegal duration `{}`: {}", d, e))
+        };
+
+        match s.trim().split_once(':') {
+            None if s.trim() == "constant" => Ok(ArrivalProcess::Constant),
+            None if s.trim() == "poisson" => Ok(ArrivalProcess::Poisson),
+            Some(("bursty", periods)) => {
+                let (on, off) = periods
+                    .split_once('/')
+                    .ok_or_else(|| format!("Expected bursty:ON/OFF, got {}", s))?;
+                let (on, off) = (parse_duration(on)?, parse_duration(off)?);
+                if on.is_zero() {
+                    return Err(format!("The on period must be positive: {}", s));
+                }
+                Ok(ArrivalProcess::Bursty { on, off })
+            }
+            _ => Err(format!(
+                "Unknown arrival process `{}`, expected `constant`, `poisson` or `bursty:ON/OFF`",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for ArrivalProcess {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArrivalProcess::Constant => write!(f, "constant"),
+            ArrivalProcess::Poisson => write!(f, "poisson"),
+            ArrivalProcess::Bursty { on, off } => write!(
+                f,
+                "bursty:{}/{}",
+                humantime::format_duration(*on),
+                humantime::format_duration(*off)
+            ),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::rate_limiter::RateLimiter;
+    use crate::rate_limiter::{ArrivalProcess, ArrivalSchedule, RateLimiter};
     use std::time::{Duration, Instant};
 
     #[tokio::test]
     async fn test_limited_frequent() {
-        let rate_limiter = RateLimiter::build_rate_limiter(100.);
+        let rate_limiter = RateLimiter::build_rate_limiter(100., ArrivalProcess::Constant);
         let begin = Instant::now();
         for _ in 0..100 {
             rate_limiter.acquire_one().await.expect("No reason to fail");
@@ -114,7 +248,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_limited_seldom() {
-        let rate_limiter = RateLimiter::build_rate_limiter(0.5);
+        let rate_limiter = RateLimiter::build_rate_limiter(0.5, ArrivalProcess::Constant);
         let begin = Instant::now();
         for _ in 0..2 {
             rate_limiter.acquire_one().await.expect("No reason to fail");
@@ -127,7 +261,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_unlimited() {
-        let rate_limiter = RateLimiter::build_rate_limiter(0.);
+        let rate_limiter = RateLimiter::build_rate_limiter(0., ArrivalProcess::Constant);
         let begin = Instant::now();
         for _ in 0..1_000_000 {
             rate_limiter.acquire_one().await.expect("No reason to fail");
@@ -164,4 +298,97 @@ mod tests {
             );
         });
     }
+
+    #[test]
+    fn test_parse_arrival_process() {
+        assert_eq!(Ok(ArrivalProcess::Constant), "constant".parse());
+        assert_eq!(Ok(ArrivalProcess::Poisson), " poisson".parse());
+        let bursty = ArrivalProcess::Bursty {
+            on: Duration::from_secs(1),
+            off: Duration::from_millis(4500),
+        };
+        assert_eq!(Ok(bursty), "bursty:1s/4s 500ms".parse());
+        assert_eq!("bursty:1s/4s 500ms", bursty.to_string());
+
+        assert!("uniform".parse::<ArrivalProcess>().is_err());
+        assert!("bursty:1s".parse::<ArrivalProcess>().is_err());
+        assert!("bursty:0s/1s".parse::<ArrivalProcess>().is_err());
+        assert!("bursty:1x/1s".parse::<ArrivalProcess>().is_err());
+    }
+
+    fn intervals(arrival: ArrivalProcess, interval: Duration, count: usize) -> Vec<Duration> {
+        let mut schedule = ArrivalSchedule {
+            arrival,
+            interval,
+            next: Instant::now(),
+            cycle_position: Duration::ZERO,
+        };
+        let mut previous = schedule.next_arrival();
+        (0..count)
+            .map(|_| {
+                let next = schedule.next_arrival();
+                let interval = next - previous;
+                previous = next;
+                interval
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_arrival_schedule() {
+        let constant = intervals(ArrivalProcess::Constant, Duration::from_millis(10), 100);
+        assert!(constant.iter().all(|i| *i == Duration::from_millis(10)));
+
+        let poisson = intervals(ArrivalProcess::Poisson, Duration::from_millis(10), 10_000);
+        let mean = poisson.iter().sum::<Duration>() / poisson.len() as u32;
+        assert!(
+            (mean.as_secs_f64() - 0.01).abs() < 0.001,
+            "Unexpected mean: {:?}",
+            mean
+        );
+        assert!(poisson.iter().any(|i| *i < Duration::from_millis(2)));
+        assert!(poisson.iter().any(|i| *i > Duration::from_millis(30)));
+
+        // 100 requests per 1s cycle, sent in the first 200ms
+        let bursty = intervals(
+            ArrivalProcess::Bursty {
+                on: Duration::from_millis(200),
+                off: Duration::from_millis(800),
+            },
+            Duration::from_millis(10),
+            200,
+        );
+        assert_eq!(Duration::from_secs(2), bursty.iter().sum());
+        assert_eq!(
+            2,
+            bursty
+                .iter()
+                .filter(|i| **i > Duration::from_millis(2))
+                .count()
+        );
+    }
+
+    #[test]
+    fn test_bursty_rate() {
+        for (rate, on, off) in [
+            (0.1, Duration::from_secs(1), Duration::from_secs(4)),
+            (3., Duration::from_secs(1), Duration::from_millis(500)),
+            (7., Duration::from_millis(300), Duration::from_millis(700)),
+        ] {
+            let interval = Duration::from_secs_f64(1. / rate);
+            let bursty = intervals(ArrivalProcess::Bursty { on, off }, interval, 1000);
+            let elapsed: Duration = bursty.iter().sum();
+            let expected = interval * bursty.len() as u32;
+            // within a cycle of the constant rate
+            assert!(
+                (elapsed.as_secs_f64() - expected.as_secs_f64()).abs() <= (on + off).as_secs_f64(),
+                "Rate {} with {:?}/{:?}: {:?} instead of {:?}",
+                rate,
+                on,
+                off,
+                elapsed,
+                expected
+            );
+        }
+    }
 }
@@ -0,0 +1,290 @@
+/// Copyright 2020 Developers of the perf-gauge project.
+///
+/// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+/// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+/// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+/// option. This file may not be copied, modified, or distributed
+/// except according to those terms.
+use crate::metrics::BenchRunMetrics;
+use core::fmt;
+use log::info;
+use std::time::Duration;
+
+/// The search stops when the max rate is known within this fraction.
+const PRECISION: f64 = 0.05;
+
+/// Service level objective, each run at a sustainable rate must meet it.
+#[derive(Clone, Debug, Default)]
+pub struct Slo {
+    pub max_p99: Option<Duration>,
+    /// In percents, e.g. `99.9`.
+    pub min_success_rate: Option<f64>,
+}
+
+/// Finds the max rate meeting the SLO: increases the rate until the SLO is broken,
+/// then binary-searches between the last good and the first bad rate,
+/// and confirms the result with another run.
+#[derive(Clone, Debug)]
+pub struct RateSearch {
+    slo: Slo,
+    /// Added on each increase, the rate is doubled if `None`.
+    rate_increment: Option<f64>,
+    max_rate: Option<f64>,
+    current: f64,
+    /// Rates meeting the SLO, ascending.
+    passed: Vec<f64>,
+    /// The lowest rate breaking the SLO.
+    failed: Option<f64>,
+    confirming: bool,
+    result: Option<SearchResult>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SearchResult {
+    /// The rate and the p99 latency and the success rate of the confirming run.
+    MaxRate(f64, Duration, f64),
+    /// Even the lowest tried rate.
+    SloNotMet(f64),
+}
+
+impl Slo {
+    fn is_met(&self, p99: Duration, success_rate: f64) -> bool {
+        self.max_p99.is_none_or(|max| p99 <= max)
+            && self.min_success_rate.is_none_or(|min| success_rate >= min)
+    }
+}
+
+impl RateSearch {
+    pub fn new(start: f64, rate_increment: Option<f64>, max_rate: Option<f64>, slo: Slo) -> Self {
+        assert!(
+            start > 0.,
+            "Bug: the rate search requires a positive start rate"
+        );
+        Self {
+            slo,
+            rate_increment,
+            max_rate,
+            current: start,
+            passed: vec![],
+            failed: None,
+            confirming: false,
+            result: None,
+        }
+    }
+
+    /// `None` when the search is complete.
+    pub fn next_rate(&self) -> Option<f64> {
+        match self.result {
+            Some(_) => None,
+            None => Some(self.current),
+        }
+    }
+
+    pub fn result(&self) -> Option<&SearchResult> {
+        self.result.as_ref()
+    }
+
+    /// Results of the run at the current rate.
+    pub fn report(&mut self, metrics: &BenchRunMetrics) {
+        let p99 = metrics.combined.latency_percentile(99.);
+        let success_rate = metrics.combined.success_rate();
+        self.report_run(p99, success_rate);
+    }
+
+    /// A batch, which failed to run (e.g. a fatal error), breaks the SLO.
+    pub fn report_failure(&mut self) {
+        info!("Rate {:.1}: the run failed, SLO broken", self.current);
+        self.update(None);
+    }
+
+    fn report_run(&mut self, p99: Duration, success_rate: f64) {
+        let met = self.slo.is_met(p99, success_rate);
+        info!(
+            "Rate {:.1}: p99 {:?}, success rate {:.3}%, SLO {}",
+            self.current,
+            p99,
+            success_rate,
+            if met { "met" } else { "broken" }
+        );
+        self.update(Some((p99, success_rate)).filter(|_| met));
+    }
+
+    /// `passed` is the p99 and the success rate of the run, if it met the SLO.
+    fn update(&mut self, passed: Option<(Duration, f64)>) {
+        let met = passed.is_some();
+        if self.confirming {
+            self.confirming = false;
+            if let Some((p99, success_rate)) = passed {
+                self.result = Some(SearchResult::MaxRate(self.current, p99, success_rate));
+                return;
+            }
+            // not stable, so search below it
+            self.passed.pop();
+            self.failed = Some(self.current);
+        } else if met {
+            self.passed.push(self.current);
+        } else {
+            self.failed = Some(self.current);
+        }
+
+        let good = self.passed.last().copied();
+        match (good, self.failed) {
+            (Some(good), None) if self.max_rate.is_some_and(|max| good >= max) => {
+                self.confirm(good)
+            }
+            (Some(good), None) => {
+                let next = match self.rate_increment {
+                    Some(increment) => good + increment,
+                    None => good * 2.,
+                };
+                self.current = self.max_rate.map_or(next, |max| next.min(max));
+            }
+            (good, Some(bad)) => {
+                let good = good.unwrap_or(0.);
+                if bad - good > (bad * PRECISION).max(1.) {
+                    // whole rates are easier to read and to reproduce
+                    self.current = ((good + bad) / 2.).round();
+                } else if good > 0. {
+                    self.confirm(good);
+                } else {
+                    self.result = Some(SearchResult::SloNotMet(bad));
+                }
+            }
+            (None, None) => unreachable!(),
+        }
+    }
+
+    fn confirm(&mut self, rate: f64) {
+        self.current = rate;
+        self.confirming = true;
+    }
+}
+
+impl fmt::Display for SearchResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SearchResult::MaxRate(rate, p99, success_rate) => write!(
+                f,
+                "Max sustainable rate: {:.1} per second (p99: {:.2}ms, success rate: {:.3}%)",
+                rate,
+                p99.as_secs_f64() * 1000.,
+                success_rate
+            ),
+            SearchResult::SloNotMet(rate) => {
+                write!(f, "The SLO is not met even at {:.1} per second", rate)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rate_search::{RateSearch, SearchResult, Slo};
+    use std::time::Duration;
+
+    /// Runs the search against a service, which meets the SLO up to `capacity`.
+    fn search(mut search: RateSearch, capacity: f64) -> (Vec<f64>, SearchResult) {
+        let mut rates = vec![];
+        while let Some(rate) = search.next_rate() {
+            rates.push(rate);
+            let p99 = if rate <= capacity {
+                Duration::from_millis(10)
+            } else {
+                Duration::from_millis(500)
+            };
+            search.report_run(p99, 100.);
+        }
+        (rates, search.result().unwrap().clone())
+    }
+
+    fn slo() -> Slo {
+        Slo {
+            max_p99: Some(Duration::from_millis(100)),
+            min_success_rate: Some(99.),
+        }
+    }
+
+    #[test]
+    fn test_slo() {
+        let slo = slo();
+        assert!(slo.is_met(Duration::from_millis(100), 99.));
+        assert!(!slo.is_met(Duration::from_millis(101), 100.));
+        assert!(!slo.is_met(Duration::from_millis(1), 98.9));
+        assert!(Slo::default().is_met(Duration::from_secs(10), 0.));
+    }
+
+    #[test]
+    fn test_search() {
+        let (rates, result) = search(RateSearch::new(100., None, None, slo()), 1000.);
+        assert_eq!(
+            vec![100., 200., 400., 800., 1600., 1200., 1000., 1100., 1050., 1000.],
+            rates
+        );
+        assert_eq!(
+            SearchResult::MaxRate(1000., Duration::from_millis(10), 100.),
+            result
+        );
+
+        // linear increase up to the max rate
+        let (rates, result) = search(RateSearch::new(100., Some(100.), Some(250.), slo()), 1000.);
+        assert_eq!(vec![100., 200., 250., 250.], rates);
+        assert!(matches!(result, SearchResult::MaxRate(rate, _, _) if rate == 250.));
+
+        let (rates, result) = search(RateSearch::new(10., None, None, slo()), 0.1);
+        assert_eq!(vec![10., 5., 3., 2., 1.], rates);
+        assert_eq!(SearchResult::SloNotMet(1.), result);
+    }
+
+    #[test]
+    fn test_failed_confirmation() {
+        let mut search = RateSearch::new(100., None, None, slo());
+        let runs = [
+            (100., true),
+            (200., false),
+            (150., true),
+            (175., false),
+            (163., false),
+            (157., false),
+            // the confirmation fails, so 150 is not sustainable
+            (150., false),
+            (125., false),
+            (113., false),
+            (107., false),
+            (104., false),
+            (100., true),
+        ];
+        for (rate, met) in runs.iter() {
+            assert_eq!(Some(*rate), search.next_rate());
+            let p99 = Duration::from_millis(if *met { 10 } else { 500 });
+            search.report_run(p99, 100.);
+        }
+        assert_eq!(None, search.next_rate());
+        assert!(matches!(
+            search.result(),
+            Some(SearchResult::MaxRate(rate, _, _)) if *rate == 100.
+        ));
+    }
+
+    #[test]
+    fn test_failed_runs() {
+        // failed runs break any SLO
+        let mut search = RateSearch::new(100., None, None, Slo::default());
+        let mut rates = vec![];
+        while let Some(rate) = search.next_rate() {
+            rates.push(rate);
+            if rate <= 1000. {
+                search.report_run(Duration::from_secs(1), 0.);
+            } else {
+                search.report_failure();
+            }
+        }
+        assert_eq!(
+            vec![100., 200., 400., 800., 1600., 1200., 1000., 1100., 1050., 1000.],
+            rates
+        );
+        assert!(matches!(
+            search.result(),
+            Some(SearchResult::MaxRate(rate, _, _)) if *rate == 1000.
+        ));
+    }
+}
@@ -0,0 +1,420 @@
+use crate::bench_run::BenchmarkProtocolAdapter;
+/// Copyright 2020 Developers of the perf-gauge project.
+///
+/// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+/// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+/// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+/// option. This file may not be copied, modified, or distributed
+/// except according to those terms.
+use crate::kv_workload::{KeyValueCommand, KeyValueWorkload, KeyValueWorkloadBuilder};
+use crate::metrics::{RequestStats, RequestStatsBuilder};
+use async_trait::async_trait;
+use core::fmt;
+use log::error;
+use rand::{thread_rng, Rng};
+use std::io;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::sync::Mutex;
+use tokio::time::timeout;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Builder, Deserialize, Clone)]
+pub struct RedisBenchAdapter {
+    target: Vec<String>,
+    /// Arbitrary commands (a random one is sent each time). If empty, `workload` is used.
+    #[builder(default)]
+    commands: Vec<Vec<String>>,
+    #[builder(default = "KeyValueWorkloadBuilder::default().build().unwrap()")]
+    workload: KeyValueWorkload,
+}
+
+/// Redis connections are always re-used, but re-established after errors.
+pub struct RedisClient {
+    connection: Mutex<Option<BufReader<TcpStream>>>,
+}
+
+/// Type of a reply, which is reported as the status.
+#[derive(Debug, PartialEq)]
+enum RedisReply {
+    Simple(String),
+    Error(String),
+    Integer,
+    Bulk,
+    Nil,
+    Array,
+}
+
+impl RedisBenchAdapter {
+    async fn connect(&self) -> io::Result<TcpStream> {
+        let target = &self.target[thread_rng().gen_range(0..self.target.len())];
+        let stream = timeout(CONNECT_TIMEOUT, TcpStream::connect(target))
+            .await
+            .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "Connect timed out"))??;
+        stream.set_nodelay(true)?;
+        Ok(stream)
+    }
+
+    fn next_command(&self) -> Vec<String> {
+        if self.commands.is_empty() {
+            match self.workload.next_command() {
+                KeyValueCommand::Get(key) => vec!["GET".to_string(), key],
+                KeyValueCommand::Set(key, value) => vec!["SET".to_string(), key, value],
+            }
+        } else {
+            self.commands[thread_rng().gen_range(0..self.commands.len())].clone()
+        }
+    }
+
+    fn encode_command(command: &[String]) -> Vec<u8> {
+        let mut encoded = format!("*{}\r\n", command.len()).into_bytes();
+        for arg in command {
+            encoded.extend_from_slice(format!("${}\r\n", arg.len()).as_bytes());
+            encoded.extend_from_slice(arg.as_bytes());
+            encoded.extend_from_slice(b"\r\n");
+        }
+        encoded
+    }
+
+    /// Reads a complete reply, including nested arrays.
+    /// Returns the type of the top-level reply and the number of bytes read.
+    async fn read_reply<R: AsyncBufRead + Unpin>(
+        reader: &mut R,
+    ) -> io::Result<(RedisReply, usize)> {
+        let mut reply = None;
+        let mut total_size = 0;
+        let mut pending = 1;
+        let mut line = String::new();
+
+        while pending > 0 {
+            pending -= 1;
+            line.clear();
+            let size = reader.read_line(&mut line).await?;
+            if size == 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "Unexpected EOF",
+                ));
+            }
+            total_size += size;
+
+            let value = line.trim_end();
+            let (prefix, value) = value.split_at(value.len().min(1));
+            let element = match prefix {
+                "+" => RedisReply::Simple(value.to_string()),
+                "-" => RedisReply::Error(value.split(' ').next().unwrap_or_default().to_string()),
+                ":" => RedisReply::Integer,
+                "$" => match RedisBenchAdapter::parse_length(value)? {
+                    None => RedisReply::Nil,
+                    Some(length) => {
+                        // the value is followed by CRLF
+                        let length = length as u64 + 2;
+                        let read = tokio::io::copy(
+                            &mut (&mut *reader).take(length),
+                            &mut tokio::io::sink(),
+                        )
+                        .await?;
+                        if read < length {
+                            return Err(io::Error::new(
+                                io::ErrorKind::UnexpectedEof,
+                                "Unexpected EOF",
+                            ));
+                        }
+                        total_size += read as usize;
+                        RedisReply::Bulk
+                    }
+                },
+                "*" => {
+                    pending += RedisBenchAdapter::parse_length(value)?.unwrap_or_default();
+                    RedisReply::Array
+                }
+                _ => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "Invalid RESP reply",
+                    ))
+                }
+            };
+
+            if reply.is_none() {
+                reply = Some(element);
+            }
+        }
+
+        Ok((reply.expect("At least one element is read"), total_size))
+    }
+
+    /// Negative length stands for `nil`.
+    fn parse_length(value: &str) -> io::Result<Option<usize>> {
+        let length = value
+            .parse::<i64>()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        Ok(if length < 0 {
+            None
+        } else {
+            Some(length as usize)
+        })
+    }
+
+    async fn exchange(
+        command: &[String],
+        stream: &mut BufReader<TcpStream>,
+    ) -> io::Result<(RedisReply, usize)> {
+        stream
+            .get_mut()
+            .write_all(&RedisBenchAdapter::encode_command(command))
+            .await?;
+        RedisBenchAdapter::read_reply(stream).await
+    }
+}
+
+#[async_trait]
+impl BenchmarkProtocolAdapter for RedisBenchAdapter {
+    type Client = RedisClient;
+
+    fn build_client(&self) -> Result<Self::Client, String> {
+        Ok(RedisClient {
+            connection: Mutex::new(None),
+        })
+    }
+
+    async fn send_request(&self, client: &Self::Client) -> RequestStats {
+        let command = self.next_command();
+        let operation_name = command
+            .first()
+            .map(|c| c.to_uppercase())
+            .unwrap_or_default();
+
+        let mut connection = client.connection.lock().await;
+
+        let mut phases = vec![];
+        let start = Instant::now();
+        if connection.is_none() {
+            match self.connect().await {
+                Ok(stream) => {
+                    phases.push(("connect", Instant::now().duration_since(start)));
+                    *connection = Some(BufReader::new(stream));
+                }
+                Err(e) => {
+                    error!("Error connecting: {}", e);
+                    return RequestStatsBuilder::default()
+                        .bytes_processed(0)
+                        .status(e.to_string())
+                        .is_success(false)
+                        .duration(Instant::now().duration_since(start))
+                        .operation_name(Some(operation_name))
+                        .fatal_error(false)
+                        .build()
+                        .expect("RequestStatsBuilder failed");
+                }
+            }
+        }
+
+        let stream = connection.as_mut().expect("Connection must be established");
+        let start = Instant::now();
+        let result = RedisBenchAdapter::exchange(&command, stream).await;
+        let duration = Instant::now().duration_since(start);
+
+        let (status, success, total_size) = match result {
+            Ok((RedisReply::Error(error), total_size)) => {
+                (format!("-{}", error), false, total_size)
+            }
+            Ok((RedisReply::Simple(value), total_size)) => (value, true, total_size),
+            Ok((reply, total_size)) => (format!("{:?}", reply), true, total_size),
+            Err(e) => {
+                error!("Error sending command: {}", e);
+                *connection = None;
+                (e.to_string(), false, 0)
+            }
+        };
+
+        RequestStatsBuilder::default()
+            .bytes_processed(total_size)
+            .status(status)
+            .is_success(success)
+            .duration(duration)
+            .operation_name(Some(operation_name))
+            .phases(phases)
+            .fatal_error(false)
+            .build()
+            .expect("RequestStatsBuilder failed")
+    }
+}
+
+impl fmt::Display for RedisBenchAdapter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Targets={}, first target={}, commands={:?}, workload={:?}",
+            self.target.len(),
+            self.target[0],
+            self.commands,
+            self.workload
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bench_run::BenchmarkProtocolAdapter;
+    use crate::kv_workload::KeyValueWorkloadBuilder;
+    use crate::redis_bench_session::{RedisBenchAdapter, RedisBenchAdapterBuilder, RedisReply};
+    use std::collections::HashMap;
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+
+    /// Minimal RESP server supporting GET and SET.
+    async fn start_redis_stub() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            while let Ok((socket, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let mut storage: HashMap<String, String> = HashMap::new();
+                    let mut reader = BufReader::new(socket);
+                    let mut line = String::new();
+                    loop {
+                        line.clear();
+                        if reader.read_line(&mut line).await.unwrap_or(0) == 0 {
+                            break;
+                        }
+                        let args: usize = line.trim_end()[1..].parse().unwrap();
+                        let mut command = vec![];
+                        for _ in 0..args {
+                            line.clear();
+                            reader.read_line(&mut line).await.unwrap();
+                            let length: usize = line.trim_end()[1..].parse().unwrap();
+                            let mut arg = vec![0; length + 2];
+                            reader.read_exact(&mut arg).await.unwrap();
+                            command.push(String::from_utf8_lossy(&arg[..length]).to_string());
+                        }
+                        let reply = match command[0].as_str() {
+                            "SET" => {
+                                storage.insert(command[1].clone(), command[2].clone());
+                                "+OK\r\n".to_string()
+                            }
+                            "GET" => match storage.get(&command[1]) {
+                                Some(v) => format!("${}\r\n{}\r\n", v.len(), v),
+                                None => "$-1\r\n".to_string(),
+                            },
+                            _ => "-ERR unknown command\r\n".to_string(),
+                        };
+                        reader.get_mut().write_all(reply.as_bytes()).await.unwrap();
+                    }
+                });
+            }
+        });
+        addr
+    }
+
+    #[test]
+    fn test_encode_command() {
+        assert_eq!(
+            b"*2\r\n$3\r\nGET\r\n$3\r\nkey\r\n".to_vec(),
+            RedisBenchAdapter::encode_command(&["GET".to_string(), "key".to_string()])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_read_reply() {
+        let test = vec![
+            ("+OK\r\n", RedisReply::Simple("OK".to_string()), 5),
+            (
+                "-WRONGTYPE Operation against a key\r\n",
+                RedisReply::Error("WRONGTYPE".to_string()),
+                36,
+            ),
+            (":1000\r\n", RedisReply::Integer, 7),
+            ("$5\r\nhello\r\n", RedisReply::Bulk, 11),
+            ("$-1\r\n", RedisReply::Nil, 5),
+            ("*2\r\n$1\r\na\r\n*1\r\n:1\r\n", RedisReply::Array, 19),
+            ("*0\r\n", RedisReply::Array, 4),
+        ];
+
+        for (input, expected_reply, expected_size) in test {
+            let mut reader = BufReader::new(input.as_bytes());
+            let (reply, size) = RedisBenchAdapter::read_reply(&mut reader)
+                .await
+                .expect("Valid reply");
+            assert_eq!(expected_reply, reply, "{}", input);
+            assert_eq!(expected_size, size, "{}", input);
+        }
+
+        let mut reader = BufReader::new("$5\r\nhel".as_bytes());
+        assert!(RedisBenchAdapter::read_reply(&mut reader).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_get_set() {
+        let addr = start_redis_stub().await;
+
+        let set_bench = RedisBenchAdapterBuilder::default()
+            .target(vec![addr.clone()])
+            .workload(
+                KeyValueWorkloadBuilder::default()
+                    .keyspace(1)
+                    .value_size((10, 10))
+                    .set_ratio(1.)
+                    .build()
+                    .unwrap(),
+            )
+            .build()
+            .unwrap();
+
+        let client = set_bench.build_client().expect("Client is built");
+        let stats = set_bench.send_request(&client).await;
+        println!("{:?}", stats);
+        assert!(stats.is_success);
+        assert_eq!("OK".to_string(), stats.status);
+        assert_eq!(Some("SET".to_string()), stats.operation_name);
+        assert_eq!(1, stats.phases.len());
+
+        let get_bench = RedisBenchAdapterBuilder::default()
+            .target(vec![addr])
+            .workload(
+                KeyValueWorkloadBuilder::default()
+                    .keyspace(1)
+                    .build()
+                    .unwrap(),
+            )
+            .build()
+            .unwrap();
+
+        // the stub keeps values per connection
+        let stats = get_bench.send_request(&client).await;
+        println!("{:?}", stats);
+        assert!(stats.is_success);
+        assert_eq!("Bulk".to_string(), stats.status);
+        assert_eq!(Some("GET".to_string()), stats.operation_name);
+        assert_eq!(17, stats.bytes_processed);
+        // the connection is re-used
+        assert!(stats.phases.is_empty());
+
+        let client = get_bench.build_client().expect("Client is built");
+        let stats = get_bench.send_request(&client).await;
+        assert!(stats.is_success);
+        assert_eq!("Nil".to_string(), stats.status);
+    }
+
+    #[tokio::test]
+    async fn test_error_reply() {
+        let addr = start_redis_stub().await;
+
+        let redis_bench = RedisBenchAdapterBuilder::default()
+            .target(vec![addr])
+            .commands(vec![vec!["flushall".to_string()]])
+            .build()
+            .unwrap();
+
+        let client = redis_bench.build_client().expect("Client is built");
+        let stats = redis_bench.send_request(&client).await;
+
+        println!("{:?}", stats);
+        assert!(!stats.is_success);
+        assert!(!stats.fatal_error);
+        assert_eq!("-ERR".to_string(), stats.status);
+        assert_eq!(Some("FLUSHALL".to_string()), stats.operation_name);
+    }
+}
@@ -0,0 +1,232 @@
+/// Copyright 2020 Developers of the perf-gauge project.
+///
+/// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+/// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+/// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+/// option. This file may not be copied, modified, or distributed
+/// except according to those terms.
+use rand::{thread_rng, Rng};
+use rhai::{CallFnOptions, Dynamic, Engine, Map, Scope, AST};
+use std::fs;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Status of successful HTTP responses, which are rejected by `fn response`.
+pub const REJECTED_STATUS: &str = "Rejected by script";
+
+const REQUEST_FN: &str = "request";
+const RESPONSE_FN: &str = "response";
+
+/// Rhai script building requests and (optionally) checking responses.
+///
+/// `fn request()` must return a map with any of `method`, `url`, `headers` (a map) and `body` keys.
+/// Missing keys fall back to the command line values, and headers replace the same command line ones.
+/// `fn response(status, headers, body)` is optional and returns `true` if the request succeeded.
+/// Both functions can keep per-connection state in `this`, which is an object map.
+#[derive(Clone)]
+pub struct RequestScript {
+    engine: Arc<Engine>,
+    ast: Arc<AST>,
+    has_response_fn: bool,
+}
+
+/// Request parts overridden by the script.
+#[derive(Debug, Default, PartialEq)]
+pub struct ScriptedRequest {
+    pub method: Option<String>,
+    pub url: Option<String>,
+    pub headers: Vec<(String, String)>,
+    pub body: Option<Vec<u8>>,
+}
+
+impl RequestScript {
+    pub fn from_file(path: &str) -> Result<Self, String> {
+        let script =
+            fs::read_to_string(path).map_err(|e| format!("Cannot read {}: {}", path, e))?;
+        RequestScript::compile(&script)
+    }
+
+    pub fn compile(script: &str) -> Result<Self, String> {
+        let mut engine = Engine::new();
+        engine.register_fn("random_int", |min: i64, max: i64| {
+            thread_rng().gen_range(min..=max.max(min))
+        });
+        engine.register_fn("timestamp_ms", || {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_millis() as i64)
+                .unwrap_or_default()
+        });
+
+        let ast = engine.compile(script).map_err(|e| e.to_string())?;
+        let has_fn = |name: &str, params: usize| {
+            ast.iter_functions()
+                .any(|f| f.name == name && f.params.len() == params)
+        };
+        if !has_fn(REQUEST_FN, 0) {
+            return Err(format!("Script must define `fn {}()`", REQUEST_FN));
+        }
+        let has_response_fn = has_fn(RESPONSE_FN, 3);
+
+        Ok(RequestScript {
+            engine: Arc::new(engine),
+            ast: Arc::new(ast),
+            has_response_fn,
+        })
+    }
+
+    /// Initial per-connection state.
+    pub fn new_state() -> Dynamic {
+        Dynamic::from_map(Map::new())
+    }
+
+    pub fn has_response_fn(&self) -> bool {
+        self.has_response_fn
+    }
+
+    pub fn request(&self, state: &mut Dynamic) -> Result<ScriptedRequest, String> {
+        let result = self
+            .call(state, REQUEST_FN, ())?
+            .try_cast::<Map>()
+            .ok_or("`request` must return a map")?;
+
+        let string = |key: &str| -> Result<Option<String>, String> {
+            result
+                .get(key)
+                .map(|v| {
+                    v.clone()
+                        .into_string()
+                        .map_err(|t| format!("`{}` is {}", key, t))
+                })
+                .transpose()
+        };
+
+        let headers = match result.get("headers") {
+            Some(headers) => headers
+                .clone()
+                .try_cast::<Map>()
+                .ok_or("`headers` must be a map")?
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            None => vec![],
+        };
+
+        Ok(ScriptedRequest {
+            method: string("method")?,
+            url: string("url")?,
+            headers,
+            body: string("body")?.map(String::into_bytes),
+        })
+    }
+
+    pub fn response(
+        &self,
+        state: &mut Dynamic,
+        status: u16,
+        headers: Vec<(String, String)>,
+        body: &[u8],
+    ) -> Result<bool, String> {
+        let headers: Map = headers
+            .into_iter()
+            .map(|(k, v)| (k.into(), Dynamic::from(v)))
+            .collect();
+        self.call(
+            state,
+            RESPONSE_FN,
+            (
+                status as i64,
+                headers,
+                String::from_utf8_lossy(body).to_string(),
+            ),
+        )?
+        .as_bool()
+        .map_err(|t| format!("`{}` must return bool, not {}", RESPONSE_FN, t))
+    }
+
+    fn call(
+        &self,
+        state: &mut Dynamic,
+        name: &str,
+        args: impl rhai::FuncArgs,
+    ) -> Result<Dynamic, String> {
+        let options = CallFnOptions::new().eval_ast(false).bind_this_ptr(state);
+        self.engine
+            .call_fn_with_options(options, &mut Scope::new(), &self.ast, name, args)
+            .map_err(|e| format!("Script error in `{}`: {}", name, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::script::{RequestScript, ScriptedRequest};
+
+    #[test]
+    fn test_request_with_state() {
+        let script = RequestScript::compile(
+            r#"
+            fn request() {
+                if this.count == () { this.count = 0; }
+                this.count += 1;
+                #{ url: `http://localhost/item/${this.count}`, headers: #{ "x-count": this.count } }
+            }
+            "#,
+        )
+        .unwrap();
+        assert!(!script.has_response_fn());
+
+        let mut state = RequestScript::new_state();
+        for i in 1..=3 {
+            assert_eq!(
+                ScriptedRequest {
+                    url: Some(format!("http://localhost/item/{}", i)),
+                    headers: vec![("x-count".to_string(), i.to_string())],
+                    ..Default::default()
+                },
+                script.request(&mut state).unwrap()
+            );
+        }
+
+        // the state is per connection
+        let mut state = RequestScript::new_state();
+        assert_eq!(
+            Some("http://localhost/item/1".to_string()),
+            script.request(&mut state).unwrap().url
+        );
+    }
+
+    #[test]
+    fn test_response() {
+        let script = RequestScript::compile(
+            r#"
+            fn request() { #{ method: "POST", body: "abc" } }
+            fn response(status, headers, body) {
+                status == 200 && headers["content-type"] == "application/json" && body.contains("ok")
+            }
+            "#,
+        )
+        .unwrap();
+        assert!(script.has_response_fn());
+
+        let mut state = RequestScript::new_state();
+        let request = script.request(&mut state).unwrap();
+        assert_eq!(Some("POST".to_string()), request.method);
+        assert_eq!(Some(b"abc".to_vec()), request.body);
+
+        let headers = vec![("content-type".to_string(), "application/json".to_string())];
+        assert!(script
+            .response(&mut state, 200, headers.clone(), b"{\"ok\":true}")
+            .unwrap());
+        assert!(!script.response(&mut state, 200, headers, b"{}").unwrap());
+        assert!(!script.response(&mut state, 500, vec![], b"ok").unwrap());
+    }
+
+    #[test]
+    fn test_invalid_script() {
+        assert!(RequestScript::compile("fn response(a, b, c) { true }").is_err());
+        assert!(RequestScript::compile("fn request() {").is_err());
+
+        let script = RequestScript::compile("fn request() { 42 }").unwrap();
+        assert!(script.request(&mut RequestScript::new_state()).is_err());
+    }
+}
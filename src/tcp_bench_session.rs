@@ -0,0 +1,353 @@
+use crate::bench_run::BenchmarkProtocolAdapter;
+/// Copyright 2020 Developers of the perf-gauge project.
+///
+/// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+/// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+/// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+/// option. This file may not be copied, modified, or distributed
+/// except according to those terms.
+use crate::metrics::{RequestStats, RequestStatsBuilder};
+use async_trait::async_trait;
+use core::fmt;
+use log::error;
+use rand::{thread_rng, Rng};
+use std::io;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::sync::Mutex;
+use tokio::time::timeout;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How the end of a response is detected.
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub enum TcpResponseEnd {
+    /// Exactly this number of bytes is expected.
+    Size(usize),
+    /// The response ends with the given sequence of bytes.
+    Delimiter(Vec<u8>),
+    /// The server closes the connection after responding.
+    Eof,
+}
+
+#[derive(Builder, Deserialize, Clone)]
+#[builder(build_fn(validate = "Self::validate"))]
+pub struct TcpBenchAdapter {
+    target: Vec<String>,
+    #[builder(default)]
+    conn_reuse: bool,
+    #[builder(default)]
+    payload: Vec<u8>,
+    #[builder(default = "TcpResponseEnd::Eof")]
+    response_end: TcpResponseEnd,
+}
+
+/// A connection is established lazily and kept open between requests
+/// if `conn_reuse` is set.
+pub struct TcpClient {
+    connection: Mutex<Option<BufReader<TcpStream>>>,
+}
+
+impl TcpBenchAdapter {
+    async fn connect(&self) -> io::Result<TcpStream> {
+        let target = &self.target[thread_rng().gen_range(0..self.target.len())];
+        let stream = timeout(CONNECT_TIMEOUT, TcpStream::connect(target))
+            .await
+            .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "Connect timed out"))??;
+        stream.set_nodelay(true)?;
+        Ok(stream)
+    }
+
+    /// Writes the payload and reads the response. Returns the number of bytes received.
+    async fn exchange(&self, stream: &mut BufReader<TcpStream>) -> io::Result<usize> {
+        if !self.payload.is_empty() {
+            stream.get_mut().write_all(&self.payload).await?;
+        }
+
+        match &self.response_end {
+            TcpResponseEnd::Size(size) => {
+                let received = tokio::io::copy(
+                    &mut (&mut *stream).take(*size as u64),
+                    &mut tokio::io::sink(),
+                )
+                .await? as usize;
+                if received < *size {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "Unexpected EOF",
+                    ));
+                }
+                Ok(received)
+            }
+            TcpResponseEnd::Delimiter(delimiter) => {
+                let last_byte = *delimiter.last().expect("Delimiter must not be empty");
+                let mut response = vec![];
+                loop {
+                    if stream.read_until(last_byte, &mut response).await? == 0 {
+                        return Err(io::Error::new(
+                            io::ErrorKind::UnexpectedEof,
+                            "Unexpected EOF",
+                        ));
+                    }
+                    if response.ends_with(delimiter) {
+                        return Ok(response.len());
+                    }
+                }
+            }
+            TcpResponseEnd::Eof => tokio::io::copy(stream, &mut tokio::io::sink())
+                .await
+                .map(|received| received as usize),
+        }
+    }
+
+    fn failed_request(status: String, duration: Duration) -> RequestStats {
+        RequestStatsBuilder::default()
+            .bytes_processed(0)
+            .status(status)
+            .is_success(false)
+            .duration(duration)
+            .fatal_error(false)
+            .build()
+            .expect("RequestStatsBuilder failed")
+    }
+}
+
+#[async_trait]
+impl BenchmarkProtocolAdapter for TcpBenchAdapter {
+    type Client = TcpClient;
+
+    fn build_client(&self) -> Result<Self::Client, String> {
+        Ok(TcpClient {
+            connection: Mutex::new(None),
+        })
+    }
+
+    async fn send_request(&self, client: &Self::Client) -> RequestStats {
+        let mut connection = client.connection.lock().await;
+
+        let mut phases = vec![];
+        if connection.is_none() {
+            let start = Instant::now();
+            match self.connect().await {
+                Ok(stream) => {
+                    phases.push(("connect", Instant::now().duration_since(start)));
+                    *connection = Some(BufReader::new(stream));
+                }
+                Err(e) => {
+                    error!("Error connecting: {}", e);
+                    return TcpBenchAdapter::failed_request(
+                        e.to_string(),
+                        Instant::now().duration_since(start),
+                    );
+                }
+            }
+        }
+
+        let stream = connection.as_mut().expect("Connection must be established");
+        let start = Instant::now();
+        let result = self.exchange(stream).await;
+        let duration = Instant::now().duration_since(start);
+
+        if result.is_err() || !self.conn_reuse {
+            *connection = None;
+        }
+
+        match result {
+            Ok(total_size) => RequestStatsBuilder::default()
+                .bytes_processed(total_size)
+                .status("OK".to_string())
+                .is_success(true)
+                .duration(duration)
+                .phases(phases)
+                .fatal_error(false)
+                .build()
+                .expect("RequestStatsBuilder failed"),
+            Err(e) => {
+                error!("Error sending request: {}", e);
+                TcpBenchAdapter::failed_request(e.to_string(), duration)
+            }
+        }
+    }
+}
+
+impl TcpBenchAdapterBuilder {
+    /// Validate the response can be delimited for the given settings
+    fn validate(&self) -> Result<(), String> {
+        match (&self.response_end, self.conn_reuse) {
+            (Some(TcpResponseEnd::Delimiter(delimiter)), _) if delimiter.is_empty() => {
+                Err("Response delimiter must not be empty".to_string())
+            }
+            (None, Some(true)) | (Some(TcpResponseEnd::Eof), Some(true)) => Err(
+                "Either response size or delimiter is required to re-use connections".to_string(),
+            ),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for TcpBenchAdapter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Targets={}, first target={}, conn_reuse={}, payload size={}, response end={:?}",
+            self.target.len(),
+            self.target[0],
+            self.conn_reuse,
+            self.payload.len(),
+            self.response_end
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bench_run::BenchmarkProtocolAdapter;
+    use crate::tcp_bench_session::{TcpBenchAdapterBuilder, TcpResponseEnd};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Echo server, which reports the number of accepted connections.
+    async fn start_echo_server(close_after_reply: bool) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let connections = Arc::new(AtomicUsize::new(0));
+        let accepted = connections.clone();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                accepted.fetch_add(1, Ordering::Relaxed);
+                tokio::spawn(async move {
+                    let mut buf = [0; 1024];
+                    while let Ok(n) = socket.read(&mut buf).await {
+                        if n == 0 || socket.write_all(&buf[..n]).await.is_err() {
+                            break;
+                        }
+                        if close_after_reply {
+                            break;
+                        }
+                    }
+                });
+            }
+        });
+        (addr, connections)
+    }
+
+    #[tokio::test]
+    async fn test_response_size() {
+        let (addr, connections) = start_echo_server(false).await;
+
+        let tcp_bench = TcpBenchAdapterBuilder::default()
+            .target(vec![addr])
+            .payload(b"hello".to_vec())
+            .response_end(TcpResponseEnd::Size(5))
+            .build()
+            .unwrap();
+
+        let client = tcp_bench.build_client().expect("Client is built");
+        for _ in 0..3 {
+            let stats = tcp_bench.send_request(&client).await;
+            println!("{:?}", stats);
+            assert!(stats.is_success);
+            assert_eq!(5, stats.bytes_processed);
+            assert_eq!("OK".to_string(), stats.status);
+            assert_eq!(1, stats.phases.len());
+            assert_eq!("connect", stats.phases[0].0);
+        }
+
+        assert_eq!(3, connections.load(Ordering::Relaxed));
+    }
+
+    #[tokio::test]
+    async fn test_delimiter_with_conn_reuse() {
+        let (addr, connections) = start_echo_server(false).await;
+
+        let tcp_bench = TcpBenchAdapterBuilder::default()
+            .target(vec![addr])
+            .conn_reuse(true)
+            .payload(b"hello\r\n".to_vec())
+            .response_end(TcpResponseEnd::Delimiter(b"\r\n".to_vec()))
+            .build()
+            .unwrap();
+
+        let client = tcp_bench.build_client().expect("Client is built");
+        for i in 0..3 {
+            let stats = tcp_bench.send_request(&client).await;
+            println!("{:?}", stats);
+            assert!(stats.is_success);
+            assert_eq!(7, stats.bytes_processed);
+            // connection is established only once
+            assert_eq!(i == 0, !stats.phases.is_empty());
+        }
+
+        assert_eq!(1, connections.load(Ordering::Relaxed));
+    }
+
+    #[tokio::test]
+    async fn test_response_until_eof() {
+        let (addr, _) = start_echo_server(true).await;
+
+        let tcp_bench = TcpBenchAdapterBuilder::default()
+            .target(vec![addr])
+            .payload(b"hello".to_vec())
+            .build()
+            .unwrap();
+
+        let client = tcp_bench.build_client().expect("Client is built");
+        let stats = tcp_bench.send_request(&client).await;
+
+        assert!(stats.is_success);
+        assert_eq!(5, stats.bytes_processed);
+    }
+
+    #[tokio::test]
+    async fn test_unexpected_eof() {
+        let (addr, _) = start_echo_server(true).await;
+
+        let tcp_bench = TcpBenchAdapterBuilder::default()
+            .target(vec![addr])
+            .payload(b"hello".to_vec())
+            .response_end(TcpResponseEnd::Size(10))
+            .build()
+            .unwrap();
+
+        let client = tcp_bench.build_client().expect("Client is built");
+        let stats = tcp_bench.send_request(&client).await;
+
+        assert!(!stats.is_success);
+        assert_eq!("Unexpected EOF".to_string(), stats.status);
+    }
+
+    #[tokio::test]
+    async fn test_connection_refused() {
+        // bind and drop to get a port nobody listens to
+        let addr = TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .to_string();
+
+        let tcp_bench = TcpBenchAdapterBuilder::default()
+            .target(vec![addr])
+            .build()
+            .unwrap();
+
+        let client = tcp_bench.build_client().expect("Client is built");
+        let stats = tcp_bench.send_request(&client).await;
+
+        assert!(!stats.is_success);
+        assert!(!stats.fatal_error);
+        assert!(stats.phases.is_empty());
+    }
+
+    #[test]
+    fn test_conn_reuse_requires_response_end() {
+        assert!(TcpBenchAdapterBuilder::default()
+            .target(vec!["127.0.0.1:80".to_string()])
+            .conn_reuse(true)
+            .build()
+            .is_err());
+    }
+}
@@ -0,0 +1,288 @@
+/// Copyright 2020 Developers of the perf-gauge project.
+///
+/// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+/// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+/// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+/// option. This file may not be copied, modified, or distributed
+/// except according to those terms.
+use rand::distributions::Alphanumeric;
+use rand::{thread_rng, Rng};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const OPEN: &[u8] = b"{{";
+const CLOSE: &[u8] = b"}}";
+
+/// Shared by all clients, so each request gets a unique number.
+static SEQ: AtomicU64 = AtomicU64::new(0);
+
+/// Named values (e.g. columns of a data record), which are bound to `{{name}}` placeholders.
+pub type Variables = HashMap<String, String>;
+
+/// Placeholders, which are expanded for each request:
+/// `{{uuid}}`, `{{seq}}`, `{{timestamp_ms}}`, `{{random_int(MIN,MAX)}}`, `{{random_string(LEN)}}`
+/// and `{{name}}` for variables.
+#[derive(Clone, Debug, PartialEq)]
+enum Generator {
+    Uuid,
+    Seq,
+    TimestampMs,
+    RandomInt(i64, i64),
+    RandomString(usize),
+    Variable(String),
+}
+
+/// A template parsed once on start, so only values are generated for each request.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Template(Vec<Segment>);
+
+#[derive(Clone, Debug, PartialEq)]
+enum Segment {
+    Literal(Vec<u8>),
+    Placeholder(Generator),
+}
+
+impl Generator {
+    fn parse(expression: &str) -> Result<Generator, String> {
+        let expression = expression.trim();
+        let (name, args) = match expression.split_once('(') {
+            Some((name, args)) => {
+                let args = args
+                    .strip_suffix(')')
+                    .ok_or_else(|| format!("Missing `)` in {{{{{}}}}}", expression))?;
+                (name.trim(), args.split(',').map(str::trim).collect())
+            }
+            None => (expression, vec![]),
+        };
+
+        let parse_arg = |idx: usize| -> Result<i64, String> {
+            args.get(idx)
+                .and_then(|a: &&str| a.parse().ok())
+                .ok_or_else(|| format!("Illegal arguments in {{{{{}}}}}", expression))
+        };
+
+        match (name, args.len()) {
+            ("uuid", 0) => Ok(Generator::Uuid),
+            ("seq", 0) => Ok(Generator::Seq),
+            ("timestamp_ms", 0) => Ok(Generator::TimestampMs),
+            ("random_int", 2) => {
+                let (min, max) = (parse_arg(0)?, parse_arg(1)?);
+                if min > max {
+                    return Err(format!("Illegal range in {{{{{}}}}}", expression));
+                }
+                Ok(Generator::RandomInt(min, max))
+            }
+            ("random_string", 1) => Ok(Generator::RandomString(parse_arg(0)?.max(0) as usize)),
+            (name, 0) if !name.is_empty() && !expression.contains('(') => {
+                Ok(Generator::Variable(name.to_string()))
+            }
+            _ => Err(format!("Unknown placeholder {{{{{}}}}}", expression)),
+        }
+    }
+
+    /// Variables are looked up in the given order, missing ones are expanded to an empty string.
+    fn generate(&self, output: &mut Vec<u8>, variables: &[&Variables]) {
+        match self {
+            Generator::Uuid => output.extend_from_slice(uuid_v4().as_bytes()),
+            Generator::Seq => output.extend_from_slice(
+                (SEQ.fetch_add(1, Ordering::Relaxed) + 1)
+                    .to_string()
+                    .as_bytes(),
+            ),
+            Generator::TimestampMs => {
+                let timestamp = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| d.as_millis())
+                    .unwrap_or_default();
+                output.extend_from_slice(timestamp.to_string().as_bytes())
+            }
+            Generator::RandomInt(min, max) => {
+                output.extend_from_slice(thread_rng().gen_range(*min..=*max).to_string().as_bytes())
+            }
+            Generator::RandomString(len) => {
+                output.extend(thread_rng().sample_iter(Alphanumeric).take(*len))
+            }
+            Generator::Variable(name) => {
+                if let Some(value) = variables.iter().find_map(|v| v.get(name)) {
+                    output.extend_from_slice(value.as_bytes())
+                }
+            }
+        }
+    }
+}
+
+fn uuid_v4() -> String {
+    let mut bytes: [u8; 16] = thread_rng().gen();
+    // version 4 and RFC 4122 variant
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
+
+fn find(input: &[u8], pattern: &[u8]) -> Option<usize> {
+    input.windows(pattern.len()).position(|w| w == pattern)
+}
+
+pub fn has_placeholders(input: &[u8]) -> bool {
+    find(input, OPEN).is_some()
+}
+
+impl Template {
+    /// Splits the input into literals and placeholders.
+    /// All placeholders must be known and well-formed, variables must be declared.
+    pub fn parse(input: &[u8], variables: &[String]) -> Result<Self, String> {
+        let mut segments = vec![];
+        let mut rest = input;
+        while let Some(start) = find(rest, OPEN) {
+            if start > 0 {
+                segments.push(Segment::Literal(rest[..start].to_vec()));
+            }
+            let end = find(&rest[start..], CLOSE)
+                .ok_or_else(|| "Unclosed placeholder, `}}` is missing".to_string())?
+                + start;
+            let expression =
+                std::str::from_utf8(&rest[start + OPEN.len()..end]).map_err(|e| e.to_string())?;
+            let generator = Generator::parse(expression)?;
+            if let Generator::Variable(name) = &generator {
+                if !variables.contains(name) {
+                    return Err(format!("Unknown placeholder {{{{{}}}}}", name));
+                }
+            }
+            segments.push(Segment::Placeholder(generator));
+            rest = &rest[end + CLOSE.len()..];
+        }
+        if !rest.is_empty() {
+            segments.push(Segment::Literal(rest.to_vec()));
+        }
+        Ok(Self(segments))
+    }
+
+    /// Expands placeholders. Variables are looked up in the given order.
+    pub fn render(&self, variables: &[&Variables]) -> Vec<u8> {
+        let mut output = Vec::new();
+        for segment in self.0.iter() {
+            match segment {
+                Segment::Literal(literal) => output.extend_from_slice(literal),
+                Segment::Placeholder(generator) => generator.generate(&mut output, variables),
+            }
+        }
+        output
+    }
+
+    pub fn render_str(&self, variables: &[&Variables]) -> String {
+        String::from_utf8_lossy(&self.render(variables)).to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::template::{has_placeholders, Generator, Template, Variables};
+    use std::collections::HashSet;
+
+    fn render_str(input: &str, variables: &[&Variables]) -> String {
+        let names = ["user", "id", "missing"].map(String::from);
+        Template::parse(input.as_bytes(), &names)
+            .unwrap()
+            .render_str(variables)
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Ok(Generator::Uuid), Generator::parse("uuid"));
+        assert_eq!(Ok(Generator::Seq), Generator::parse(" seq "));
+        assert_eq!(Ok(Generator::TimestampMs), Generator::parse("timestamp_ms"));
+        assert_eq!(
+            Ok(Generator::RandomInt(1, 1000)),
+            Generator::parse("random_int(1, 1000)")
+        );
+        assert_eq!(
+            Ok(Generator::RandomString(16)),
+            Generator::parse("random_string(16)")
+        );
+
+        assert!(Generator::parse("random_int(10,1)").is_err());
+        assert!(Generator::parse("random_int(1)").is_err());
+        assert!(Generator::parse("random_string(a)").is_err());
+        assert!(Generator::parse("uuid(").is_err());
+        assert_eq!(
+            Ok(Generator::Variable("user".to_string())),
+            Generator::parse("user")
+        );
+        assert!(Generator::parse("unknown(1)").is_err());
+        assert!(Generator::parse("").is_err());
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(Template::parse(b"no placeholders", &[]).is_ok());
+        assert!(Template::parse(b"/item/{{random_int(1,10)}}?id={{uuid}}", &[]).is_ok());
+        assert!(Template::parse(b"/item/{{random_int(1,10)", &[]).is_err());
+        assert!(Template::parse(b"/item/{{foo}}", &[]).is_err());
+        assert!(Template::parse(b"/item/{{foo}}", &["foo".to_string()]).is_ok());
+        assert!(has_placeholders(b"a{{seq}}"));
+        assert!(!has_placeholders(b"a{seq}"));
+
+        // parsed once
+        assert_eq!(3, Template::parse(b"/item/{{seq}}/x", &[]).unwrap().0.len());
+    }
+
+    #[test]
+    fn test_render() {
+        assert_eq!("plain", render_str("plain", &[]));
+
+        let rendered = render_str("/item/{{random_int(5,5)}}/{{random_string(8)}}", &[]);
+        assert!(rendered.starts_with("/item/5/"));
+        assert_eq!(16, rendered.len());
+
+        let uuid = render_str("{{uuid}}", &[]);
+        assert_eq!(36, uuid.len());
+        assert_eq!(Some('4'), uuid.chars().nth(14));
+
+        let seq: Vec<u64> = (0..3)
+            .map(|_| render_str("{{seq}}", &[]).parse().unwrap())
+            .collect();
+        assert!(seq[0] < seq[1] && seq[1] < seq[2]);
+
+        let ids: HashSet<String> = (0..100).map(|_| render_str("{{uuid}}", &[])).collect();
+        assert_eq!(100, ids.len());
+
+        assert!(render_str("{{timestamp_ms}}", &[]).parse::<u128>().unwrap() > 0);
+    }
+
+    #[test]
+    fn test_render_variables() {
+        let record: Variables = vec![
+            ("user".to_string(), "alice".to_string()),
+            ("id".to_string(), "1".to_string()),
+        ]
+        .into_iter()
+        .collect();
+        let overrides: Variables = vec![("id".to_string(), "2".to_string())]
+            .into_iter()
+            .collect();
+
+        assert_eq!(
+            b"/users/alice/1".to_vec(),
+            Template::parse(
+                b"/users/{{user}}/{{id}}",
+                &["user".to_string(), "id".to_string()]
+            )
+            .unwrap()
+            .render(&[&record])
+        );
+        assert_eq!(
+            "/users/alice/2",
+            render_str("/users/{{ user }}/{{id}}", &[&overrides, &record])
+        );
+        assert_eq!("/users/", render_str("/users/{{missing}}", &[&record]));
+    }
+}
@@ -0,0 +1,208 @@
+use crate::bench_run::BenchmarkProtocolAdapter;
+/// Copyright 2020 Developers of the perf-gauge project.
+///
+/// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+/// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+/// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+/// option. This file may not be copied, modified, or distributed
+/// except according to those terms.
+use crate::metrics::{RequestStats, RequestStatsBuilder};
+use async_trait::async_trait;
+use core::fmt;
+use log::error;
+use rand::{thread_rng, Rng};
+use std::io;
+use std::net::ToSocketAddrs;
+use std::time::{Duration, Instant};
+use tokio::net::UdpSocket;
+use tokio::sync::Mutex;
+use tokio::time::timeout;
+
+/// Status of requests, which didn't get a reply within `packet_timeout`.
+pub const LOST_PACKET_STATUS: &str = "Lost";
+
+const MAX_DATAGRAM_SIZE: usize = 65_536;
+
+#[derive(Builder, Deserialize, Clone)]
+pub struct UdpBenchAdapter {
+    target: Vec<String>,
+    #[builder(default)]
+    payload: Vec<u8>,
+    #[builder(default = "Duration::from_secs(1)")]
+    packet_timeout: Duration,
+}
+
+pub struct UdpClient {
+    socket: UdpSocket,
+    buffer: Mutex<Vec<u8>>,
+}
+
+impl UdpBenchAdapter {
+    fn connect(&self) -> io::Result<UdpSocket> {
+        let target = &self.target[thread_rng().gen_range(0..self.target.len())];
+        let addr = target.to_socket_addrs()?.next().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::AddrNotAvailable,
+                format!("Cannot resolve {}", target),
+            )
+        })?;
+        let local_addr = if addr.is_ipv4() {
+            "0.0.0.0:0"
+        } else {
+            "[::]:0"
+        };
+        let socket = std::net::UdpSocket::bind(local_addr)?;
+        socket.connect(addr)?;
+        socket.set_nonblocking(true)?;
+        UdpSocket::from_std(socket)
+    }
+
+    /// Late replies to lost packets must not be taken for replies to the next ones.
+    fn discard_late_replies(socket: &UdpSocket, buffer: &mut [u8]) {
+        while socket.try_recv(buffer).is_ok() {}
+    }
+}
+
+#[async_trait]
+impl BenchmarkProtocolAdapter for UdpBenchAdapter {
+    type Client = UdpClient;
+
+    fn build_client(&self) -> Result<Self::Client, String> {
+        Ok(UdpClient {
+            socket: self.connect().map_err(|e| {
+                error!("Cannot create UDP socket: {}", e);
+                e.to_string()
+            })?,
+            buffer: Mutex::new(vec![0; MAX_DATAGRAM_SIZE]),
+        })
+    }
+
+    async fn send_request(&self, client: &Self::Client) -> RequestStats {
+        let mut buffer = client.buffer.lock().await;
+        UdpBenchAdapter::discard_late_replies(&client.socket, &mut buffer);
+
+        let start = Instant::now();
+        let result = match client.socket.send(&self.payload).await {
+            Ok(_) => timeout(self.packet_timeout, client.socket.recv(&mut buffer)).await,
+            Err(e) => Ok(Err(e)),
+        };
+        let duration = Instant::now().duration_since(start);
+
+        let (status, success, total_size) = match result {
+            Ok(Ok(total_size)) => ("OK".to_string(), true, total_size),
+            Ok(Err(e)) => {
+                error!("Error sending request: {}", e);
+                (e.to_string(), false, 0)
+            }
+            Err(_) => (LOST_PACKET_STATUS.to_string(), false, 0),
+        };
+
+        RequestStatsBuilder::default()
+            .bytes_processed(total_size)
+            .status(status)
+            .is_success(success)
+            .duration(duration)
+            .fatal_error(false)
+            .build()
+            .expect("RequestStatsBuilder failed")
+    }
+}
+
+impl fmt::Display for UdpBenchAdapter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Targets={}, first target={}, payload size={}, packet timeout={:?}",
+            self.target.len(),
+            self.target[0],
+            self.payload.len(),
+            self.packet_timeout
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bench_run::BenchmarkProtocolAdapter;
+    use crate::udp_bench_session::{UdpBenchAdapterBuilder, LOST_PACKET_STATUS};
+    use std::time::Duration;
+    use tokio::net::UdpSocket;
+
+    #[tokio::test]
+    async fn test_reply() {
+        let server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = server.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            let mut buf = [0; 1024];
+            while let Ok((n, peer)) = server.recv_from(&mut buf).await {
+                server.send_to(&buf[..n], peer).await.unwrap();
+            }
+        });
+
+        let udp_bench = UdpBenchAdapterBuilder::default()
+            .target(vec![addr])
+            .payload(b"hello".to_vec())
+            .build()
+            .unwrap();
+
+        let client = udp_bench.build_client().expect("Client is built");
+        for _ in 0..3 {
+            let stats = udp_bench.send_request(&client).await;
+            println!("{:?}", stats);
+            assert!(stats.is_success);
+            assert_eq!(5, stats.bytes_processed);
+            assert_eq!("OK".to_string(), stats.status);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_lost_packet() {
+        // never replies
+        let server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = server.local_addr().unwrap().to_string();
+
+        let udp_bench = UdpBenchAdapterBuilder::default()
+            .target(vec![addr])
+            .payload(b"hello".to_vec())
+            .packet_timeout(Duration::from_millis(50))
+            .build()
+            .unwrap();
+
+        let client = udp_bench.build_client().expect("Client is built");
+        let stats = udp_bench.send_request(&client).await;
+
+        println!("{:?}", stats);
+        assert!(!stats.is_success);
+        assert!(!stats.fatal_error);
+        assert_eq!(LOST_PACKET_STATUS.to_string(), stats.status);
+        assert!(stats.duration >= Duration::from_millis(50));
+    }
+
+    #[tokio::test]
+    async fn test_late_reply_is_discarded() {
+        let server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = server.local_addr().unwrap().to_string();
+
+        let udp_bench = UdpBenchAdapterBuilder::default()
+            .target(vec![addr])
+            .payload(b"hello".to_vec())
+            .packet_timeout(Duration::from_millis(50))
+            .build()
+            .unwrap();
+
+        let client = udp_bench.build_client().expect("Client is built");
+        assert_eq!(
+            LOST_PACKET_STATUS.to_string(),
+            udp_bench.send_request(&client).await.status
+        );
+
+        // reply to the lost packet after the timeout
+        let mut buf = [0; 1024];
+        let (_, peer) = server.recv_from(&mut buf).await.unwrap();
+        server.send_to(b"late", peer).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        let stats = udp_bench.send_request(&client).await;
+        assert_eq!(LOST_PACKET_STATUS.to_string(), stats.status);
+    }
+}
@@ -0,0 +1,374 @@
+use crate::bench_run::BenchmarkProtocolAdapter;
+/// Copyright 2020 Developers of the perf-gauge project.
+///
+/// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+/// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+/// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+/// option. This file may not be copied, modified, or distributed
+/// except according to those terms.
+use crate::metrics::{RequestStats, RequestStatsBuilder};
+use async_trait::async_trait;
+#[cfg(feature = "tls-boring")]
+use boring::ssl::{SslConnector, SslMethod};
+use core::fmt;
+use futures_util::{SinkExt, StreamExt};
+use log::error;
+use rand::{thread_rng, Rng};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpStream;
+use tokio::sync::Mutex;
+use tokio::time::timeout;
+#[cfg(feature = "tls-native")]
+use tokio_native_tls::TlsConnector;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::handshake::client::Request;
+use tokio_tungstenite::tungstenite::http::{HeaderName, HeaderValue};
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::WebSocketStream;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Either plain TCP or TLS stream.
+trait WsIo: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> WsIo for T {}
+
+type WsConnection = WebSocketStream<Box<dyn WsIo>>;
+
+#[derive(Builder, Deserialize, Clone)]
+pub struct WsBenchAdapter {
+    target: Vec<String>,
+    #[builder(default)]
+    #[cfg_attr(not(feature = "tls-native"), allow(dead_code))]
+    ignore_cert: bool,
+    #[builder(default)]
+    conn_reuse: bool,
+    #[builder(default)]
+    headers: Vec<(String, Vec<String>)>,
+    #[builder(default)]
+    message: Vec<u8>,
+    #[builder(default)]
+    text: bool,
+}
+
+/// A connection is established lazily and kept open between messages
+/// if `conn_reuse` is set.
+pub struct WsClient {
+    connection: Mutex<Option<WsConnection>>,
+}
+
+impl WsBenchAdapter {
+    /// Establishes a connection, reporting the `connect` and `handshake` (TLS and WebSocket upgrade) phases.
+    async fn connect(
+        &self,
+        phases: &mut Vec<(&'static str, Duration)>,
+    ) -> Result<WsConnection, String> {
+        let request = self.build_handshake_request()?;
+        let uri = request.uri();
+        let secure = uri.scheme_str() == Some("wss");
+        let host = uri.host().ok_or("Target host is missing")?.to_string();
+        let port = uri.port_u16().unwrap_or(if secure { 443 } else { 80 });
+
+        let start = Instant::now();
+        let tcp_stream = timeout(CONNECT_TIMEOUT, TcpStream::connect((host.as_str(), port)))
+            .await
+            .map_err(|_| "Connect timed out".to_string())?
+            .map_err(|e| e.to_string())?;
+        tcp_stream.set_nodelay(true).map_err(|e| e.to_string())?;
+        phases.push(("connect", Instant::now().duration_since(start)));
+
+        let start = Instant::now();
+        let stream = if secure {
+            self.tls_handshake(&host, tcp_stream).await?
+        } else {
+            Box::new(tcp_stream) as Box<dyn WsIo>
+        };
+        let (connection, _) = tokio_tungstenite::client_async(request, stream)
+            .await
+            .map_err(|e| e.to_string())?;
+        phases.push(("handshake", Instant::now().duration_since(start)));
+
+        Ok(connection)
+    }
+
+    fn build_handshake_request(&self) -> Result<Request, String> {
+        let target = &self.target[thread_rng().gen_range(0..self.target.len())];
+        let mut request = target
+            .as_str()
+            .into_client_request()
+            .map_err(|e| e.to_string())?;
+
+        for (key, value) in self.headers.iter() {
+            request.headers_mut().insert(
+                HeaderName::from_str(key).map_err(|e| e.to_string())?,
+                HeaderValue::from_str(&value[thread_rng().gen_range(0..value.len())])
+                    .map_err(|e| e.to_string())?,
+            );
+        }
+
+        Ok(request)
+    }
+
+    #[cfg(not(feature = "tls"))]
+    async fn tls_handshake(&self, _: &str, _: TcpStream) -> Result<Box<dyn WsIo>, String> {
+        Err("TLS is not supported in this configuration".to_string())
+    }
+
+    #[cfg(feature = "tls-native")]
+    async fn tls_handshake(
+        &self,
+        host: &str,
+        tcp_stream: TcpStream,
+    ) -> Result<Box<dyn WsIo>, String> {
+        let mut native_tls_builder = native_tls::TlsConnector::builder();
+        if self.ignore_cert {
+            native_tls_builder.danger_accept_invalid_certs(true);
+        }
+
+        let connector = TlsConnector::from(
+            native_tls_builder
+                .build()
+                .expect("Cannot build TlsConnector"),
+        );
+        let tls_stream = connector
+            .connect(host, tcp_stream)
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(Box::new(tls_stream))
+    }
+
+    #[cfg(feature = "tls-boring")]
+    async fn tls_handshake(
+        &self,
+        host: &str,
+        tcp_stream: TcpStream,
+    ) -> Result<Box<dyn WsIo>, String> {
+        let config = SslConnector::builder(SslMethod::tls())
+            .expect("Cannot build BoringSSL builder")
+            .build()
+            .configure()
+            .map_err(|e| e.to_string())?;
+        let tls_stream = tokio_boring::connect(config, host, tcp_stream)
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(Box::new(tls_stream))
+    }
+
+    /// Sends the message and waits for the response frame. Returns the size of the response.
+    async fn exchange(&self, connection: &mut WsConnection) -> Result<usize, String> {
+        let message = if self.text {
+            Message::Text(String::from_utf8_lossy(&self.message).to_string())
+        } else {
+            Message::Binary(self.message.clone())
+        };
+        connection.send(message).await.map_err(|e| e.to_string())?;
+
+        while let Some(frame) = connection.next().await {
+            match frame.map_err(|e| e.to_string())? {
+                Message::Text(text) => return Ok(text.len()),
+                Message::Binary(data) => return Ok(data.len()),
+                Message::Close(_) => break,
+                // control frames are handled by the protocol implementation
+                _ => {}
+            }
+        }
+
+        Err("Connection closed".to_string())
+    }
+
+    fn failed_request(
+        status: String,
+        duration: Duration,
+        phases: Vec<(&'static str, Duration)>,
+    ) -> RequestStats {
+        RequestStatsBuilder::default()
+            .bytes_processed(0)
+            .status(status)
+            .is_success(false)
+            .duration(duration)
+            .phases(phases)
+            .fatal_error(false)
+            .build()
+            .expect("RequestStatsBuilder failed")
+    }
+}
+
+#[async_trait]
+impl BenchmarkProtocolAdapter for WsBenchAdapter {
+    type Client = WsClient;
+
+    fn build_client(&self) -> Result<Self::Client, String> {
+        Ok(WsClient {
+            connection: Mutex::new(None),
+        })
+    }
+
+    async fn send_request(&self, client: &Self::Client) -> RequestStats {
+        let mut connection = client.connection.lock().await;
+
+        let mut phases = vec![];
+        if connection.is_none() {
+            let start = Instant::now();
+            match self.connect(&mut phases).await {
+                Ok(c) => {
+                    *connection = Some(c);
+                }
+                Err(e) => {
+                    error!("Error connecting: {}", e);
+                    return WsBenchAdapter::failed_request(
+                        e,
+                        Instant::now().duration_since(start),
+                        phases,
+                    );
+                }
+            }
+        }
+
+        let ws = connection.as_mut().expect("Connection must be established");
+        let start = Instant::now();
+        let result = self.exchange(ws).await;
+        let duration = Instant::now().duration_since(start);
+
+        if result.is_ok() && !self.conn_reuse {
+            ws.close(None).await.unwrap_or_default();
+        }
+        if result.is_err() || !self.conn_reuse {
+            *connection = None;
+        }
+
+        match result {
+            Ok(total_size) => RequestStatsBuilder::default()
+                .bytes_processed(total_size)
+                .status("OK".to_string())
+                .is_success(true)
+                .duration(duration)
+                .phases(phases)
+                .fatal_error(false)
+                .build()
+                .expect("RequestStatsBuilder failed"),
+            Err(e) => {
+                error!("Error sending message: {}", e);
+                WsBenchAdapter::failed_request(e, duration, phases)
+            }
+        }
+    }
+}
+
+impl fmt::Display for WsBenchAdapter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Targets={}, first target={}, conn_reuse={}, headers={:?}, message size={}, text={}",
+            self.target.len(),
+            self.target[0],
+            self.conn_reuse,
+            self.headers,
+            self.message.len(),
+            self.text
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bench_run::BenchmarkProtocolAdapter;
+    use crate::ws_bench_session::WsBenchAdapterBuilder;
+    use futures_util::{SinkExt, StreamExt};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use tokio::net::TcpListener;
+
+    /// Echo server, which reports the number of accepted connections.
+    async fn start_echo_server() -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let connections = Arc::new(AtomicUsize::new(0));
+        let accepted = connections.clone();
+        tokio::spawn(async move {
+            while let Ok((socket, _)) = listener.accept().await {
+                accepted.fetch_add(1, Ordering::Relaxed);
+                tokio::spawn(async move {
+                    let mut ws = tokio_tungstenite::accept_async(socket).await.unwrap();
+                    while let Some(Ok(message)) = ws.next().await {
+                        if message.is_close() || ws.send(message).await.is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+        });
+        (format!("ws://{}/echo", addr), connections)
+    }
+
+    #[tokio::test]
+    async fn test_conn_reuse() {
+        let (url, connections) = start_echo_server().await;
+
+        let ws_bench = WsBenchAdapterBuilder::default()
+            .target(vec![url])
+            .conn_reuse(true)
+            .message(b"hello".to_vec())
+            .build()
+            .unwrap();
+
+        let client = ws_bench.build_client().expect("Client is built");
+        for i in 0..3 {
+            let stats = ws_bench.send_request(&client).await;
+            println!("{:?}", stats);
+            assert!(stats.is_success);
+            assert_eq!(5, stats.bytes_processed);
+            assert_eq!("OK".to_string(), stats.status);
+            if i == 0 {
+                let phases: Vec<&str> = stats.phases.iter().map(|(p, _)| *p).collect();
+                assert_eq!(vec!["connect", "handshake"], phases);
+            } else {
+                assert!(stats.phases.is_empty());
+            }
+        }
+
+        assert_eq!(1, connections.load(Ordering::Relaxed));
+    }
+
+    #[tokio::test]
+    async fn test_connection_per_message() {
+        let (url, connections) = start_echo_server().await;
+
+        let ws_bench = WsBenchAdapterBuilder::default()
+            .target(vec![url])
+            .message(b"hello".to_vec())
+            .text(true)
+            .build()
+            .unwrap();
+
+        let client = ws_bench.build_client().expect("Client is built");
+        for _ in 0..3 {
+            let stats = ws_bench.send_request(&client).await;
+            assert!(stats.is_success);
+            assert_eq!(2, stats.phases.len());
+        }
+
+        assert_eq!(3, connections.load(Ordering::Relaxed));
+    }
+
+    #[tokio::test]
+    async fn test_not_websocket_endpoint() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        // accept and close immediately
+        tokio::spawn(async move { while listener.accept().await.is_ok() {} });
+
+        let ws_bench = WsBenchAdapterBuilder::default()
+            .target(vec![format!("ws://{}/", addr)])
+            .build()
+            .unwrap();
+
+        let client = ws_bench.build_client().expect("Client is built");
+        let stats = ws_bench.send_request(&client).await;
+
+        assert!(!stats.is_success);
+        assert!(!stats.fatal_error);
+        assert_eq!(1, stats.phases.len());
+    }
+}